@@ -20,11 +20,7 @@ fn run_software(mut ui: impl FnMut(&Context) + 'static) {
 
     let mut gc = unsafe { softbuffer::Context::new(&window) }.unwrap();
     let mut softbuffer_surface = unsafe { softbuffer::Surface::new(&gc, &window).unwrap() };
-    let mut egui_skia = EguiSkiaWinit::new(&ev_loop);
-
-    egui_skia
-        .egui_winit
-        .set_pixels_per_point(window.scale_factor() as f32);
+    let mut egui_skia = EguiSkiaWinit::new(&window);
 
     let size = window.inner_size();
     let size = size.to_logical::<i32>(window.scale_factor());
@@ -32,8 +28,6 @@ fn run_software(mut ui: impl FnMut(&Context) + 'static) {
         Surface::new_raster_n32_premul((size.width as i32, size.height as i32)).unwrap();
 
     ev_loop.run(move |ev, _, control_flow| {
-        *control_flow = ControlFlow::Wait;
-
         match ev {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
@@ -50,28 +44,23 @@ fn run_software(mut ui: impl FnMut(&Context) + 'static) {
                 window.request_redraw();
             }
             Event::WindowEvent { event, .. } => {
-                let response = egui_skia.on_event(&event);
+                let response = egui_skia.on_event(&window, &event);
                 if response.repaint {
                     window.request_redraw();
                 }
             }
+            // Only repaint when egui asks for it, and sleep until then.
+            Event::MainEventsCleared => {
+                if egui_skia.needs_repaint() {
+                    window.request_redraw();
+                }
+                *control_flow = egui_skia.control_flow();
+            }
             Event::RedrawRequested(_) => {
                 let canvas = surface.canvas();
                 canvas.clear(skia_safe::Color::TRANSPARENT);
 
-                let repaint_after = egui_skia.run(&window, &mut ui);
-
-                *control_flow = if repaint_after.is_zero() {
-                    window.request_redraw();
-                    ControlFlow::Poll
-                } else if let Some(repaint_after_instant) =
-                    std::time::Instant::now().checked_add(repaint_after)
-                {
-                    ControlFlow::WaitUntil(repaint_after_instant)
-                } else {
-                    ControlFlow::Wait
-                };
-
+                egui_skia.run(&window, &mut ui);
                 egui_skia.paint(canvas);
 
                 let snapshot = surface.image_snapshot();
@@ -103,12 +92,10 @@ fn run_software(mut ui: impl FnMut(&Context) + 'static) {
 
 #[cfg(feature = "winit")]
 fn main() {
-    use std::sync::Arc;
-
     use egui::ScrollArea;
     use skia_safe::{Paint, Point};
 
-    use egui_skia::EguiSkiaPaintCallback;
+    use egui_skia::SkiaCanvas;
     #[cfg(not(feature = "cpu_fix"))]
     eprintln!("Warning! Feature cpu_fix should be enabled when using raster surfaces. See https://github.com/lucasmerlin/egui_skia/issues/1");
 
@@ -124,14 +111,12 @@ fn main() {
         demos.ui(ctx);
         egui::Window::new("Draw to skia").show(ctx, |ui| {
             ScrollArea::horizontal().show(ui, |ui| {
-                let (rect, _) =
-                    ui.allocate_exact_size(egui::Vec2::splat(300.0), egui::Sense::drag());
-                ui.painter().add(egui::PaintCallback {
-                    rect: rect.clone(),
-                    callback: Arc::new(EguiSkiaPaintCallback::new(move |canvas| {
+                ui.add(
+                    SkiaCanvas::new(egui::Vec2::splat(300.0), |canvas| {
                         canvas.draw_circle(Point::new(150.0, 150.0), 150.0, &Paint::default());
-                    })),
-                })
+                    })
+                    .sense(egui::Sense::drag()),
+                );
             });
         });
     });
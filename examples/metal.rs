@@ -63,10 +63,7 @@ fn main() {
 
     let mut context = DirectContext::new_metal(&backend, None).unwrap();
 
-    let mut gui = EguiSkiaWinit::new(&events_loop);
-
-    gui.egui_winit
-        .set_pixels_per_point(window.scale_factor() as f32);
+    let mut gui = EguiSkiaWinit::new(&window);
 
     let mut demo = egui_demo_lib::DemoWindows::default();
 
@@ -134,7 +131,7 @@ fn main() {
             match event {
                 Event::WindowEvent { event, .. } => {
                     // Update Egui integration so the UI works!
-                    let _pass_events_to_game = !gui.on_event(&event).consumed;
+                    let _pass_events_to_game = !gui.on_event(&window, &event).consumed;
                     match event {
                         WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
                             metal_layer.set_drawable_size(CGSize::new(
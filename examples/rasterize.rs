@@ -30,10 +30,11 @@ pub fn main() {
                 });
             });
         },
-        Some(RasterizeOptions {
-            pixels_per_point: 1.0,
-            frames_before_screenshot: 2
-        }),
+        Some(
+            RasterizeOptions::default()
+                .with_pixels_per_point(1.0)
+                .with_frames_before_screenshot(2),
+        ),
     );
 
     let data = surface
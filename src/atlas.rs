@@ -0,0 +1,341 @@
+use skia_safe::{
+    surfaces, Color, ColorSpace, FilterMode, IRect, Image, ImageInfo, MipmapMode, Point, Rect,
+    SamplingOptions, Surface,
+};
+
+/// Side length of a single atlas page in pixels.
+const PAGE_SIZE: i32 = 1024;
+
+/// Gutter around every packed texture. The gutter is filled with the texture's edge pixels so
+/// linear filtering doesn't bleed neighbouring textures into each other.
+const PADDING: i32 = 1;
+
+/// Location of a packed texture inside the atlas.
+#[derive(Clone, Copy)]
+pub(crate) struct AtlasEntry {
+    pub page: usize,
+    pub rect: IRect,
+}
+
+impl AtlasEntry {
+    /// Map a uv coordinate of the original texture to a uv coordinate on the atlas page.
+    ///
+    /// UVs are clamped to the texture, as packed textures always use [`skia_safe::TileMode::Clamp`].
+    pub fn map_uv(&self, uv: Point) -> Point {
+        let size = PAGE_SIZE as f32;
+        Point::new(
+            (self.rect.left as f32 + uv.x.clamp(0.0, 1.0) * self.rect.width() as f32) / size,
+            (self.rect.top as f32 + uv.y.clamp(0.0, 1.0) * self.rect.height() as f32) / size,
+        )
+    }
+
+    /// The space the entry takes on its page, including the gutter.
+    fn slot(&self) -> IRect {
+        self.rect.with_outset((PADDING, PADDING))
+    }
+}
+
+/// How packed textures sampled with `sampling` sample their atlas page.
+///
+/// Pages are sampled without mipmaps: their smaller levels would average neighbouring textures
+/// across the gutter. Minified icons are therefore filtered like unmipmapped images.
+pub(crate) fn page_sampling(sampling: SamplingOptions) -> SamplingOptions {
+    let filter = if sampling.use_cubic || sampling.max_aniso > 0 {
+        FilterMode::Linear
+    } else {
+        sampling.filter
+    };
+    SamplingOptions::new(filter, MipmapMode::None)
+}
+
+struct Shelf {
+    y: i32,
+    height: i32,
+    x: i32,
+}
+
+struct AtlasPage {
+    surface: Surface,
+    image: Option<Image>,
+    shelves: Vec<Shelf>,
+    /// Slots of removed entries, reused before the shelves grow.
+    free_slots: Vec<IRect>,
+    next_y: i32,
+    live_entries: usize,
+}
+
+impl AtlasPage {
+    fn new() -> Self {
+        // Tagged sRGB like standalone textures, so packed textures are color managed the same.
+        let info = ImageInfo::new_n32_premul((PAGE_SIZE, PAGE_SIZE), Some(ColorSpace::new_srgb()));
+        Self {
+            surface: surfaces::raster(&info, None, None).expect("Failed to create atlas surface"),
+            image: None,
+            shelves: Vec::new(),
+            free_slots: Vec::new(),
+            next_y: 0,
+            live_entries: 0,
+        }
+    }
+
+    fn allocate(&mut self, width: i32, height: i32) -> Option<IRect> {
+        let width = width + PADDING * 2;
+        let height = height + PADDING * 2;
+
+        if let Some(slot) = self.allocate_free_slot(width, height) {
+            return Some(slot.with_inset((PADDING, PADDING)));
+        }
+
+        let index = match self
+            .shelves
+            .iter()
+            .position(|shelf| shelf.height >= height && PAGE_SIZE - shelf.x >= width)
+        {
+            Some(index) => index,
+            None => {
+                if PAGE_SIZE - self.next_y < height || width > PAGE_SIZE {
+                    return None;
+                }
+                self.shelves.push(Shelf {
+                    y: self.next_y,
+                    height,
+                    x: 0,
+                });
+                self.next_y += height;
+                self.shelves.len() - 1
+            }
+        };
+        let shelf = &mut self.shelves[index];
+
+        let rect = IRect::from_xywh(
+            shelf.x + PADDING,
+            shelf.y + PADDING,
+            width - PADDING * 2,
+            height - PADDING * 2,
+        );
+        shelf.x += width;
+        Some(rect)
+    }
+
+    /// Take a `width` x `height` slot from the smallest freed slot it fits into, and give the
+    /// rest of that slot back.
+    fn allocate_free_slot(&mut self, width: i32, height: i32) -> Option<IRect> {
+        let index = self
+            .free_slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.width() >= width && slot.height() >= height)
+            .min_by_key(|(_, slot)| slot.width() as i64 * slot.height() as i64)
+            .map(|(index, _)| index)?;
+        let free = self.free_slots.swap_remove(index);
+
+        let slot = IRect::from_xywh(free.left, free.top, width, height);
+        let right = IRect::new(slot.right, free.top, free.right, slot.bottom);
+        let below = IRect::new(free.left, slot.bottom, free.right, free.bottom);
+        self.free_slots
+            .extend([right, below].into_iter().filter(|rest| !rest.is_empty()));
+        Some(slot)
+    }
+
+    fn draw(&mut self, image: &Image, rect: IRect) {
+        let canvas = self.surface.canvas();
+        let (w, h) = (image.width() as f32, image.height() as f32);
+        let dst = Rect::from_irect(rect);
+
+        canvas.draw_image(image, Point::new(dst.left, dst.top), None);
+
+        // Extrude the edges into the gutter.
+        let edges = [
+            (
+                Rect::new(0.0, 0.0, w, 1.0),
+                Rect::new(dst.left, dst.top - 1.0, dst.right, dst.top),
+            ),
+            (
+                Rect::new(0.0, h - 1.0, w, h),
+                Rect::new(dst.left, dst.bottom, dst.right, dst.bottom + 1.0),
+            ),
+            (
+                Rect::new(0.0, 0.0, 1.0, h),
+                Rect::new(dst.left - 1.0, dst.top, dst.left, dst.bottom),
+            ),
+            (
+                Rect::new(w - 1.0, 0.0, w, h),
+                Rect::new(dst.right, dst.top, dst.right + 1.0, dst.bottom),
+            ),
+        ];
+        for (src, dst) in edges {
+            canvas.draw_image_rect(
+                image,
+                Some((&src, skia_safe::canvas::SrcRectConstraint::Strict)),
+                dst,
+                &skia_safe::Paint::default(),
+            );
+        }
+
+        self.image = None;
+    }
+
+    fn reset(&mut self) {
+        self.surface.canvas().clear(Color::TRANSPARENT);
+        self.image = None;
+        self.shelves.clear();
+        self.free_slots.clear();
+        self.next_y = 0;
+    }
+}
+
+/// Shared atlas that small user textures get packed into, so icon-heavy UIs draw from a few
+/// large images instead of one image per icon.
+pub(crate) struct TextureAtlas {
+    max_texture_side: i32,
+    pages: Vec<AtlasPage>,
+}
+
+impl TextureAtlas {
+    pub fn new(max_texture_side: usize) -> Self {
+        Self {
+            max_texture_side: (max_texture_side as i32).min(PAGE_SIZE - PADDING * 2),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Pack `image` into the atlas. Returns `None` if the image is too large to be packed.
+    pub fn insert(&mut self, image: &Image) -> Option<AtlasEntry> {
+        if image.width() > self.max_texture_side || image.height() > self.max_texture_side {
+            return None;
+        }
+
+        let (page, rect) = match self
+            .pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(i, page)| Some((i, page.allocate(image.width(), image.height())?)))
+        {
+            Some(found) => found,
+            None => {
+                let mut page = AtlasPage::new();
+                let rect = page.allocate(image.width(), image.height())?;
+                self.pages.push(page);
+                (self.pages.len() - 1, rect)
+            }
+        };
+
+        let atlas_page = &mut self.pages[page];
+        atlas_page.draw(image, rect);
+        atlas_page.live_entries += 1;
+
+        Some(AtlasEntry { page, rect })
+    }
+
+    /// Release the space of `entry` for textures packed later. Pages are recycled once all
+    /// their entries are released.
+    pub fn remove(&mut self, entry: AtlasEntry) {
+        let page = &mut self.pages[entry.page];
+        page.live_entries -= 1;
+        if page.live_entries == 0 {
+            page.reset();
+        } else {
+            page.free_slots.push(entry.slot());
+        }
+    }
+
+    /// Snapshot all pages that changed since the last call and return their indices.
+    pub fn snapshot_dirty_pages(&mut self) -> Vec<usize> {
+        self.pages
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, page)| page.image.is_none() && page.live_entries > 0)
+            .map(|(i, page)| {
+                page.image = Some(page.surface.image_snapshot());
+                i
+            })
+            .collect()
+    }
+
+    /// The largest side of textures the atlas packs.
+    pub fn max_texture_side(&self) -> i32 {
+        self.max_texture_side
+    }
+
+    pub fn page_image(&self, page: usize) -> Option<&Image> {
+        self.pages[page].image.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use skia_safe::CubicResampler;
+
+    use super::*;
+
+    fn image(width: i32, height: i32) -> Image {
+        let mut surface = surfaces::raster_n32_premul((width, height)).unwrap();
+        surface.canvas().clear(Color::RED);
+        surface.image_snapshot()
+    }
+
+    fn overlap(a: IRect, b: IRect) -> bool {
+        a.left < b.right && b.left < a.right && a.top < b.bottom && b.top < a.bottom
+    }
+
+    #[test]
+    fn removed_slots_are_reused() {
+        let mut atlas = TextureAtlas::new(2048);
+        let first = atlas.insert(&image(32, 32)).unwrap();
+        let second = atlas.insert(&image(32, 32)).unwrap();
+        let third = atlas.insert(&image(32, 32)).unwrap();
+
+        atlas.remove(second);
+        let smaller = atlas.insert(&image(16, 8)).unwrap();
+        assert_eq!(smaller.page, second.page);
+        assert_eq!(
+            (smaller.rect.left, smaller.rect.top),
+            (second.rect.left, second.rect.top)
+        );
+
+        // The rest of the freed slot is still available.
+        let beside = atlas.insert(&image(14, 8)).unwrap();
+        assert_eq!(beside.rect.top, second.rect.top);
+        assert!(!overlap(beside.slot(), smaller.slot()));
+        for entry in [first, third] {
+            assert!(!overlap(beside.slot(), entry.slot()));
+            assert!(!overlap(smaller.slot(), entry.slot()));
+        }
+    }
+
+    #[test]
+    fn emptied_page_is_reset() {
+        let mut atlas = TextureAtlas::new(2048);
+        let entry = atlas.insert(&image(100, 100)).unwrap();
+        atlas.remove(entry);
+        let again = atlas.insert(&image(200, 50)).unwrap();
+        assert_eq!(again.page, entry.page);
+        assert_eq!(
+            (again.rect.left, again.rect.top),
+            (entry.rect.left, entry.rect.top)
+        );
+    }
+
+    #[test]
+    fn large_images_are_not_packed() {
+        let mut atlas = TextureAtlas::new(256);
+        assert_eq!(atlas.max_texture_side(), 256);
+        assert!(atlas.insert(&image(257, 16)).is_none());
+        assert!(atlas.insert(&image(256, 16)).is_some());
+    }
+
+    #[test]
+    fn pages_are_sampled_without_mipmaps() {
+        let mipmapped = SamplingOptions::new(FilterMode::Linear, MipmapMode::Linear);
+        let sampling = page_sampling(mipmapped);
+        assert_eq!(sampling.filter, FilterMode::Linear);
+        assert_eq!(sampling.mipmap, MipmapMode::None);
+
+        let sampling = page_sampling(SamplingOptions::from(CubicResampler::mitchell()));
+        assert!(!sampling.use_cubic);
+        assert_eq!(sampling.filter, FilterMode::Linear);
+
+        let nearest = SamplingOptions::new(FilterMode::Nearest, MipmapMode::Nearest);
+        assert_eq!(page_sampling(nearest).filter, FilterMode::Nearest);
+    }
+}
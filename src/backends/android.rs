@@ -0,0 +1,283 @@
+//! Paint egui directly onto an Android `ANativeWindow` (the native side of a `Surface`), and
+//! translate touches into egui input.
+
+use std::ptr::NonNull;
+
+use egui::{PointerButton, Pos2, RawInput, Rect, TouchDeviceId, TouchId, TouchPhase, ViewportId};
+use ndk_sys::{
+    AInputEvent, AMotionEvent_getAction, AMotionEvent_getPointerCount, AMotionEvent_getPointerId,
+    AMotionEvent_getX, AMotionEvent_getY, ANativeWindow, ANativeWindow_Buffer,
+    ANativeWindow_LegacyFormat, ANativeWindow_acquire, ANativeWindow_getHeight,
+    ANativeWindow_getWidth, ANativeWindow_lock, ANativeWindow_release,
+    ANativeWindow_setBuffersGeometry, ANativeWindow_unlockAndPost,
+};
+use skia_safe::{surfaces, AlphaType, Color, ColorType, ImageInfo};
+
+use crate::EguiSkia;
+
+const AMOTION_EVENT_ACTION_MASK: i32 = 0xff;
+const AMOTION_EVENT_ACTION_POINTER_INDEX_MASK: i32 = 0xff00;
+const AMOTION_EVENT_ACTION_POINTER_INDEX_SHIFT: i32 = 8;
+const AMOTION_EVENT_ACTION_DOWN: i32 = 0;
+const AMOTION_EVENT_ACTION_UP: i32 = 1;
+const AMOTION_EVENT_ACTION_MOVE: i32 = 2;
+const AMOTION_EVENT_ACTION_CANCEL: i32 = 3;
+const AMOTION_EVENT_ACTION_POINTER_DOWN: i32 = 5;
+const AMOTION_EVENT_ACTION_POINTER_UP: i32 = 6;
+
+/// An `ANativeWindow` whose buffers egui is painted into with a raster surface.
+///
+/// Every frame locks the next buffer of the window, wraps it into a Skia surface without
+/// copying, paints and posts it. Window buffers are cycled by the system and don't keep their
+/// contents, so the whole ui is repainted every frame.
+///
+/// For gpu rendering, create an EGL surface for the window and use [`gl_window`] instead.
+pub struct AndroidWindow {
+    window: NonNull<ANativeWindow>,
+}
+
+impl AndroidWindow {
+    /// Take a reference to `window`, e.g. from `ANativeWindow_fromSurface` or
+    /// `android-activity`'s `NativeWindow::ptr`, and configure its buffers as RGBA8888.
+    ///
+    /// # Safety
+    ///
+    /// `window` has to be a valid `ANativeWindow`.
+    pub unsafe fn new(window: *mut ANativeWindow) -> Option<Self> {
+        let window = NonNull::new(window)?;
+        ANativeWindow_acquire(window.as_ptr());
+        let window = Self { window };
+
+        let format = ANativeWindow_LegacyFormat::WINDOW_FORMAT_RGBA_8888.0 as i32;
+        if ANativeWindow_setBuffersGeometry(window.window.as_ptr(), 0, 0, format) != 0 {
+            return None;
+        }
+        Some(window)
+    }
+
+    pub fn as_ptr(&self) -> *mut ANativeWindow {
+        self.window.as_ptr()
+    }
+
+    /// The size of the window's buffers in pixels.
+    pub fn size(&self) -> (i32, i32) {
+        unsafe {
+            (
+                ANativeWindow_getWidth(self.window.as_ptr()),
+                ANativeWindow_getHeight(self.window.as_ptr()),
+            )
+        }
+    }
+
+    /// Paint the results of the last [`EguiSkia::run`] into the next buffer of the window and
+    /// post it to the display.
+    pub fn paint(&mut self, egui_skia: &mut EguiSkia) -> Result<(), &'static str> {
+        let mut buffer: ANativeWindow_Buffer = unsafe { std::mem::zeroed() };
+        if unsafe { ANativeWindow_lock(self.window.as_ptr(), &mut buffer, std::ptr::null_mut()) }
+            != 0
+        {
+            return Err("ANativeWindow_lock failed");
+        }
+
+        let result = unsafe { paint_buffer(&buffer, egui_skia) };
+        unsafe { ANativeWindow_unlockAndPost(self.window.as_ptr()) };
+        result
+    }
+}
+
+impl Drop for AndroidWindow {
+    fn drop(&mut self) {
+        unsafe { ANativeWindow_release(self.window.as_ptr()) };
+    }
+}
+
+unsafe fn paint_buffer(
+    buffer: &ANativeWindow_Buffer,
+    egui_skia: &mut EguiSkia,
+) -> Result<(), &'static str> {
+    if buffer.bits.is_null() {
+        return Err("window buffer has no pixels");
+    }
+    if buffer.format != ANativeWindow_LegacyFormat::WINDOW_FORMAT_RGBA_8888.0 as i32 {
+        return Err("window buffer isn't RGBA8888");
+    }
+
+    // The stride is in pixels.
+    let row_bytes = buffer.stride as usize * 4;
+    let pixels =
+        std::slice::from_raw_parts_mut(buffer.bits as *mut u8, row_bytes * buffer.height as usize);
+    let info = ImageInfo::new(
+        (buffer.width, buffer.height),
+        ColorType::RGBA8888,
+        AlphaType::Premul,
+        None,
+    );
+    let mut surface =
+        surfaces::wrap_pixels(&info, pixels, row_bytes, None).ok_or("wrap_pixels failed")?;
+
+    let canvas = surface.canvas();
+    canvas.clear(Color::TRANSPARENT);
+    egui_skia.paint(canvas);
+    Ok(())
+}
+
+/// Wrap the framebuffer of the window's EGL surface, see
+/// [`EguiSkiaGlWindow`](super::gl::EguiSkiaGlWindow). `get_proc_address` is usually
+/// `eglGetProcAddress`. Wrap the result into [`GpuOrRaster`](super::GpuOrRaster) to fall back
+/// to painting with [`AndroidWindow::paint`] if that fails.
+///
+/// # Safety
+///
+/// An EGL context with a surface for the window has to be current whenever the returned window
+/// is used.
+#[cfg(feature = "gl")]
+pub unsafe fn gl_window(
+    get_proc_address: impl Fn(&str) -> *const std::ffi::c_void,
+    window: &AndroidWindow,
+) -> Result<super::gl::EguiSkiaGlWindow, super::BackendError> {
+    super::gl::EguiSkiaGlWindow::new(get_proc_address, window.size())
+}
+
+/// Collects egui input from Android touches.
+///
+/// Every touch is reported to egui as a [`egui::Event::Touch`], the first finger additionally
+/// moves the pointer and presses the primary button, so regular widgets react to taps and drags.
+pub struct TouchInput {
+    raw_input: RawInput,
+    pixels_per_point: f32,
+    primary_pointer: Option<u64>,
+}
+
+impl TouchInput {
+    /// `pixels_per_point` is usually the display density, `AConfiguration_getDensity / 160`.
+    pub fn new(pixels_per_point: f32) -> Self {
+        Self {
+            raw_input: RawInput::default(),
+            pixels_per_point,
+            primary_pointer: None,
+        }
+    }
+
+    pub fn pixels_per_point(&self) -> f32 {
+        self.pixels_per_point
+    }
+
+    pub fn set_pixels_per_point(&mut self, pixels_per_point: f32) {
+        self.pixels_per_point = pixels_per_point;
+    }
+
+    /// Report a touch of the pointer with the id `pointer_id` at `pos` in pixels, for events
+    /// that don't come from an `AInputEvent`, e.g. a `MotionEvent` forwarded over JNI.
+    pub fn on_touch(&mut self, pointer_id: u64, phase: TouchPhase, pos: (f32, f32)) {
+        let pos = Pos2::new(pos.0, pos.1) / self.pixels_per_point;
+        let events = &mut self.raw_input.events;
+
+        events.push(egui::Event::Touch {
+            device_id: TouchDeviceId(0),
+            id: TouchId(pointer_id),
+            phase,
+            pos,
+            force: None,
+        });
+
+        if phase == TouchPhase::Start && self.primary_pointer.is_none() {
+            self.primary_pointer = Some(pointer_id);
+        }
+        if self.primary_pointer != Some(pointer_id) {
+            return;
+        }
+
+        match phase {
+            TouchPhase::Start => {
+                events.push(egui::Event::PointerMoved(pos));
+                events.push(egui::Event::PointerButton {
+                    pos,
+                    button: PointerButton::Primary,
+                    pressed: true,
+                    modifiers: Default::default(),
+                });
+            }
+            TouchPhase::Move => events.push(egui::Event::PointerMoved(pos)),
+            TouchPhase::End => {
+                self.primary_pointer = None;
+                events.push(egui::Event::PointerButton {
+                    pos,
+                    button: PointerButton::Primary,
+                    pressed: false,
+                    modifiers: Default::default(),
+                });
+                events.push(egui::Event::PointerGone);
+            }
+            TouchPhase::Cancel => {
+                self.primary_pointer = None;
+                events.push(egui::Event::PointerGone);
+            }
+        }
+    }
+
+    /// Translate a motion event from an `AInputQueue` or `android-activity`. Returns `false` for
+    /// actions that aren't touches, e.g. hover or scroll events.
+    ///
+    /// # Safety
+    ///
+    /// `event` has to be a valid motion event.
+    pub unsafe fn handle_motion_event(&mut self, event: *const AInputEvent) -> bool {
+        let action = AMotionEvent_getAction(event);
+        let action_index = ((action & AMOTION_EVENT_ACTION_POINTER_INDEX_MASK)
+            >> AMOTION_EVENT_ACTION_POINTER_INDEX_SHIFT) as usize;
+
+        let pointer = |index: usize| {
+            (
+                AMotionEvent_getPointerId(event, index) as u64,
+                (
+                    AMotionEvent_getX(event, index),
+                    AMotionEvent_getY(event, index),
+                ),
+            )
+        };
+
+        match action & AMOTION_EVENT_ACTION_MASK {
+            AMOTION_EVENT_ACTION_DOWN | AMOTION_EVENT_ACTION_POINTER_DOWN => {
+                let (id, pos) = pointer(action_index);
+                self.on_touch(id, TouchPhase::Start, pos);
+            }
+            AMOTION_EVENT_ACTION_UP | AMOTION_EVENT_ACTION_POINTER_UP => {
+                let (id, pos) = pointer(action_index);
+                self.on_touch(id, TouchPhase::End, pos);
+            }
+            // Move and cancel events carry all pointers that are down.
+            AMOTION_EVENT_ACTION_MOVE => {
+                for index in 0..AMotionEvent_getPointerCount(event) {
+                    let (id, pos) = pointer(index);
+                    self.on_touch(id, TouchPhase::Move, pos);
+                }
+            }
+            AMOTION_EVENT_ACTION_CANCEL => {
+                for index in 0..AMotionEvent_getPointerCount(event) {
+                    let (id, pos) = pointer(index);
+                    self.on_touch(id, TouchPhase::Cancel, pos);
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// The input collected since the last call, for [`EguiSkia::run`], for a window of
+    /// `window_size` pixels.
+    pub fn take_egui_input(&mut self, window_size: (i32, i32)) -> RawInput {
+        let screen_rect = Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(window_size.0 as f32, window_size.1 as f32) / self.pixels_per_point,
+        );
+
+        let mut raw_input = self.raw_input.take();
+        raw_input.screen_rect = Some(screen_rect);
+
+        let viewport = raw_input.viewports.entry(ViewportId::ROOT).or_default();
+        viewport.inner_rect = Some(screen_rect);
+        viewport.native_pixels_per_point = Some(self.pixels_per_point);
+
+        raw_input
+    }
+}
@@ -0,0 +1,158 @@
+//! Paint egui into the back buffers of a Direct3D 12 swapchain on Windows.
+//!
+//! The host keeps owning the device and the swapchain. It passes the back buffers to
+//! [`D3dBackend::set_back_buffers`], and every frame lets the backend paint into the current
+//! back buffer before presenting the swapchain.
+//!
+//! ```ignore
+//! backend.frame_limiter().begin_frame();
+//! let index = swapchain.GetCurrentBackBufferIndex() as usize;
+//! backend.paint(index, &mut egui_skia);
+//! backend.present(index);
+//! swapchain.Present(backend.sync_interval(), 0).ok()?;
+//! ```
+
+use skia_safe::gpu::d3d::{
+    self, ID3D12CommandQueue, ID3D12Device, ID3D12Resource, IDXGIAdapter1, TextureResourceInfo,
+};
+use skia_safe::gpu::{
+    backend_render_targets, direct_contexts, BackendSurfaceAccess, DirectContext, FlushInfo,
+    Protected, SurfaceOrigin,
+};
+use skia_safe::{gpu, ColorType, Surface};
+
+use super::{BackendError, FrameLimiter, Multisampling, PresentMode};
+use crate::EguiSkia;
+
+/// A Skia Direct3D context and the surfaces wrapping the back buffers of a swapchain.
+pub struct D3dBackend {
+    context: DirectContext,
+    surfaces: Vec<Surface>,
+    frame_limiter: FrameLimiter,
+    present_mode: PresentMode,
+    multisampling: Multisampling,
+}
+
+impl D3dBackend {
+    /// Create the Skia context for the host's device. `queue` has to be a direct command queue
+    /// of `device`, e.g. the one the swapchain was created with.
+    pub fn new(
+        adapter: IDXGIAdapter1,
+        device: ID3D12Device,
+        queue: ID3D12CommandQueue,
+    ) -> Result<Self, BackendError> {
+        let backend_context = d3d::BackendContext {
+            adapter,
+            device,
+            queue,
+            memory_allocator: None,
+            protected_context: Protected::No,
+        };
+        let context = unsafe { direct_contexts::make_d3d(&backend_context, None) }
+            .ok_or(BackendError::ContextCreationFailed)?;
+
+        Ok(Self {
+            context,
+            surfaces: Vec::new(),
+            frame_limiter: FrameLimiter::default(),
+            present_mode: PresentMode::default(),
+            multisampling: Multisampling::default(),
+        })
+    }
+
+    pub fn direct_context(&mut self) -> &mut DirectContext {
+        &mut self.context
+    }
+
+    /// Limits the frame rate and reports frame times. The host presents the swapchain, so it
+    /// calls [`FrameLimiter::begin_frame`] before waiting for the next back buffer.
+    pub fn frame_limiter(&mut self) -> &mut FrameLimiter {
+        &mut self.frame_limiter
+    }
+
+    /// Choose how frames are synchronized with the display. Takes effect through the sync
+    /// interval the host passes to `Present`, see [`Self::sync_interval`].
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        self.present_mode = present_mode;
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    /// The sync interval to pass to the swapchain's `Present`, see
+    /// [`PresentMode::sync_interval`]. Tearing with [`PresentMode::Immediate`] also needs a
+    /// swapchain created with `DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING` and the
+    /// `DXGI_PRESENT_ALLOW_TEARING` flag.
+    pub fn sync_interval(&self) -> u32 {
+        self.present_mode.sync_interval()
+    }
+
+    /// Paint the ui with `sample_count` samples per pixel for hardware antialiasing.
+    ///
+    /// The ui is then painted into a multisampled offscreen surface, which is resolved into the
+    /// back buffer every frame. The sample count is clamped to what the gpu supports. `None`
+    /// paints directly into the back buffers again.
+    pub fn set_sample_count(&mut self, sample_count: Option<usize>) {
+        self.multisampling.set_sample_count(sample_count);
+    }
+
+    /// Wrap the back buffers of a (re)created swapchain, in the order of their indices. Release
+    /// them with [`Self::release_back_buffers`] before calling `ResizeBuffers`.
+    ///
+    /// The swapchain has to use `DXGI_FORMAT_R8G8B8A8_UNORM`. Fails if a buffer can't be
+    /// wrapped.
+    pub fn set_back_buffers(
+        &mut self,
+        buffers: Vec<ID3D12Resource>,
+        size: (i32, i32),
+    ) -> Result<(), BackendError> {
+        self.surfaces.clear();
+
+        for buffer in buffers {
+            let texture_info = TextureResourceInfo::from_resource(buffer);
+            let render_target = backend_render_targets::make_d3d(size, &texture_info);
+            let Some(surface) = gpu::surfaces::wrap_backend_render_target(
+                &mut self.context,
+                &render_target,
+                SurfaceOrigin::TopLeft,
+                ColorType::RGBA8888,
+                None,
+                None,
+            ) else {
+                self.surfaces.clear();
+                return Err(BackendError::SurfaceCreationFailed);
+            };
+            self.surfaces.push(surface);
+        }
+        Ok(())
+    }
+
+    /// Drop the surfaces referencing the back buffers and wait for the gpu, so the swapchain
+    /// can be resized.
+    pub fn release_back_buffers(&mut self) {
+        self.surfaces.clear();
+        self.context.flush_submit_and_sync_cpu();
+    }
+
+    /// Clear back buffer `index` and paint the last frame of `egui_skia` into it.
+    pub fn paint(&mut self, index: usize, egui_skia: &mut EguiSkia) {
+        if let Some(surface) = self.surfaces.get_mut(index) {
+            self.multisampling
+                .paint(&mut self.context, surface, egui_skia);
+        }
+    }
+
+    /// Flush the drawing into back buffer `index`, transition it for presenting and submit the
+    /// work. Afterwards the host can present the swapchain.
+    pub fn present(&mut self, index: usize) {
+        if let Some(surface) = self.surfaces.get_mut(index) {
+            self.context.flush_surface_with_access(
+                surface,
+                BackendSurfaceAccess::Present,
+                &FlushInfo::default(),
+            );
+        }
+        self.context.submit(None);
+    }
+}
@@ -0,0 +1,304 @@
+//! Paint egui into the framebuffer of an OpenGL window, e.g. one created with glutin or sdl2.
+
+use std::ffi::c_void;
+
+use egui::Context;
+use skia_safe::gpu::gl::{Format, FramebufferInfo, Interface};
+use skia_safe::gpu::{backend_render_targets, direct_contexts, DirectContext, SurfaceOrigin};
+use skia_safe::{gpu, Color, ColorSpace, ColorType, SamplingOptions, Surface};
+
+use super::{BackendError, FrameLimiter, PresentMode};
+use crate::EguiSkia;
+
+const GL_FRAMEBUFFER: u32 = 0x8D40;
+const GL_FRAMEBUFFER_BINDING: u32 = 0x8CA6;
+const GL_SAMPLES: u32 = 0x80A9;
+const GL_STENCIL_BITS: u32 = 0x0D57;
+const GL_BACK_LEFT: u32 = 0x0402;
+const GL_COLOR_ATTACHMENT0: u32 = 0x8CE0;
+const GL_FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING: u32 = 0x8210;
+const GL_SRGB: u32 = 0x8C40;
+
+type GetIntegerv = unsafe extern "system" fn(u32, *mut i32);
+type GetFramebufferAttachmentParameteriv = unsafe extern "system" fn(u32, u32, u32, *mut i32);
+
+/// The platform's function setting the swap interval of the current gl context.
+#[derive(Clone, Copy)]
+enum SwapControl {
+    /// `wglSwapIntervalEXT`, returns `TRUE` on success.
+    Wgl(unsafe extern "system" fn(i32) -> i32),
+    /// `glXSwapIntervalMESA`, returns `0` on success and doesn't take negative intervals.
+    GlxMesa(unsafe extern "system" fn(u32) -> i32),
+}
+
+/// The framebuffer of a gl window, wrapped into a Skia surface that egui is painted onto.
+///
+/// Takes care of creating the Skia context, querying the framebuffer's format, recreating the
+/// surface on resize and flushing every frame. The host only creates the window and its gl
+/// context, forwards input and swaps buffers.
+pub struct EguiSkiaGlWindow {
+    pub egui_skia: EguiSkia,
+    context: DirectContext,
+    framebuffer_info: FramebufferInfo,
+    color_type: ColorType,
+    color_space: Option<ColorSpace>,
+    sample_count: usize,
+    stencil_bits: usize,
+    surface: Option<Surface>,
+    /// The sample count requested with [`Self::set_sample_count`], and the surface the ui is
+    /// painted into with it.
+    msaa_sample_count: Option<usize>,
+    msaa_surface: Option<Surface>,
+    frame_limiter: FrameLimiter,
+    swap_control: Option<SwapControl>,
+}
+
+impl EguiSkiaGlWindow {
+    /// Wrap the framebuffer currently bound in the current gl context, with a size of `size`
+    /// pixels. `get_proc_address` loads gl functions, e.g. glutin's `Display::get_proc_address`.
+    ///
+    /// sRGB framebuffers are detected, so egui's colors are encoded correctly on them. Fails if
+    /// Skia can't create a gl context or wrap the framebuffer, see
+    /// [`GpuOrRaster`](super::GpuOrRaster) for falling back to raster surfaces.
+    ///
+    /// # Safety
+    ///
+    /// The gl context has to be current on this thread whenever the window is used.
+    pub unsafe fn new(
+        get_proc_address: impl Fn(&str) -> *const c_void,
+        size: (i32, i32),
+    ) -> Result<Self, BackendError> {
+        let interface = Interface::new_load_with(|name| {
+            if name == "eglGetCurrentDisplay" {
+                return std::ptr::null();
+            }
+            get_proc_address(name)
+        })
+        .ok_or(BackendError::ContextCreationFailed)?;
+        let context =
+            direct_contexts::make_gl(interface, None).ok_or(BackendError::ContextCreationFailed)?;
+
+        let get_integerv = get_proc_address("glGetIntegerv");
+        if get_integerv.is_null() {
+            return Err(BackendError::MissingFunction("glGetIntegerv"));
+        }
+        let get_integerv: GetIntegerv = std::mem::transmute(get_integerv);
+        let get_integer = |name| {
+            let mut value = 0;
+            get_integerv(name, &mut value);
+            value
+        };
+
+        let fboid = get_integer(GL_FRAMEBUFFER_BINDING) as u32;
+        let is_srgb = {
+            let get_parameter = get_proc_address("glGetFramebufferAttachmentParameteriv");
+            !get_parameter.is_null() && {
+                let get_parameter: GetFramebufferAttachmentParameteriv =
+                    std::mem::transmute(get_parameter);
+                let attachment = if fboid == 0 {
+                    GL_BACK_LEFT
+                } else {
+                    GL_COLOR_ATTACHMENT0
+                };
+                let mut encoding = 0;
+                get_parameter(
+                    GL_FRAMEBUFFER,
+                    attachment,
+                    GL_FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING,
+                    &mut encoding,
+                );
+                encoding as u32 == GL_SRGB
+            }
+        };
+
+        let swap_control = {
+            let wgl = get_proc_address("wglSwapIntervalEXT");
+            let glx = get_proc_address("glXSwapIntervalMESA");
+            if !wgl.is_null() {
+                Some(SwapControl::Wgl(std::mem::transmute(wgl)))
+            } else if !glx.is_null() {
+                Some(SwapControl::GlxMesa(std::mem::transmute(glx)))
+            } else {
+                None
+            }
+        };
+
+        let (format, color_type, color_space) = if is_srgb {
+            (
+                Format::SRGB8_ALPHA8,
+                ColorType::SRGBA8888,
+                Some(ColorSpace::new_srgb()),
+            )
+        } else {
+            (Format::RGBA8, ColorType::RGBA8888, None)
+        };
+
+        let mut window = Self {
+            egui_skia: EguiSkia::new(),
+            context,
+            framebuffer_info: FramebufferInfo {
+                fboid,
+                format: format.into(),
+                ..Default::default()
+            },
+            color_type,
+            color_space,
+            sample_count: get_integer(GL_SAMPLES).max(0) as usize,
+            stencil_bits: get_integer(GL_STENCIL_BITS).max(0) as usize,
+            surface: None,
+            msaa_sample_count: None,
+            msaa_surface: None,
+            frame_limiter: FrameLimiter::default(),
+            swap_control,
+        };
+        window.resize(size)?;
+        Ok(window)
+    }
+
+    pub fn direct_context(&mut self) -> &mut DirectContext {
+        &mut self.context
+    }
+
+    /// Limits the frame rate of [`Self::run_and_paint`], before it runs the ui, and reports
+    /// frame times.
+    pub fn frame_limiter(&mut self) -> &mut FrameLimiter {
+        &mut self.frame_limiter
+    }
+
+    /// Set the swap interval of the gl context, see [`PresentMode::swap_interval`]. Adaptive
+    /// vsync falls back to vsync where the driver doesn't support it.
+    ///
+    /// Works with WGL (`wglSwapIntervalEXT`) and Mesa's GLX (`glXSwapIntervalMESA`). Fails with
+    /// [`BackendError::MissingFunction`] elsewhere, e.g. with EGL, where the host sets the swap
+    /// interval with its windowing library, e.g. glutin's `Surface::set_swap_interval`.
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) -> Result<(), BackendError> {
+        let interval = present_mode.swap_interval();
+        let applied = match self.swap_control {
+            Some(SwapControl::Wgl(swap_interval)) => unsafe {
+                swap_interval(interval) != 0 || (interval < 0 && swap_interval(1) != 0)
+            },
+            Some(SwapControl::GlxMesa(swap_interval)) => unsafe {
+                // Without adaptive vsync, `-1` becomes plain vsync.
+                swap_interval(interval.unsigned_abs()) == 0
+            },
+            None => return Err(BackendError::MissingFunction("wglSwapIntervalEXT")),
+        };
+        match applied {
+            true => Ok(()),
+            false => Err(BackendError::UnsupportedPresentMode),
+        }
+    }
+
+    /// Tag the framebuffer with `color_space`, e.g. the color space of the display the window
+    /// is on, so Skia converts egui's sRGB colors into it. `None` uses the detected color space
+    /// again. On sRGB framebuffers the color space has to use the sRGB transfer function.
+    ///
+    /// Recreates the surface, so it fails like [`Self::resize`].
+    pub fn set_color_space(&mut self, color_space: Option<ColorSpace>) -> Result<(), BackendError> {
+        self.color_space = match color_space {
+            Some(color_space) => Some(color_space),
+            None if self.color_type == ColorType::SRGBA8888 => Some(ColorSpace::new_srgb()),
+            None => None,
+        };
+        match &self.surface {
+            Some(surface) => {
+                let size = (surface.width(), surface.height());
+                self.resize(size)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Paint the ui with `sample_count` samples per pixel for hardware antialiasing, if the
+    /// window's framebuffer has fewer samples.
+    ///
+    /// The ui is then painted into a multisampled offscreen surface, which is resolved into the
+    /// framebuffer every frame. The sample count is clamped to what the gpu supports. `None`
+    /// paints directly into the framebuffer again.
+    pub fn set_sample_count(&mut self, sample_count: Option<usize>) -> Result<(), BackendError> {
+        if self.msaa_sample_count == sample_count {
+            return Ok(());
+        }
+        self.msaa_sample_count = sample_count;
+        match &self.surface {
+            Some(surface) => {
+                let size = (surface.width(), surface.height());
+                self.resize(size)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Recreate the surface for a framebuffer of `size` pixels, e.g. after the window was
+    /// resized.
+    ///
+    /// Fails if the framebuffer can't be wrapped, nothing is painted then.
+    pub fn resize(&mut self, size: (i32, i32)) -> Result<(), BackendError> {
+        self.surface = None;
+        self.msaa_surface = self
+            .msaa_sample_count
+            .filter(|sample_count| *sample_count > self.sample_count.max(1))
+            .and_then(|sample_count| {
+                super::msaa_surface(
+                    &mut self.context,
+                    size,
+                    self.color_type,
+                    self.color_space.clone(),
+                    sample_count,
+                )
+            });
+
+        let render_target = backend_render_targets::make_gl(
+            size,
+            self.sample_count,
+            self.stencil_bits,
+            self.framebuffer_info,
+        );
+        self.surface = gpu::surfaces::wrap_backend_render_target(
+            &mut self.context,
+            &render_target,
+            SurfaceOrigin::BottomLeft,
+            self.color_type,
+            self.color_space.clone(),
+            None,
+        );
+        match self.surface {
+            Some(_) => Ok(()),
+            None => Err(BackendError::SurfaceCreationFailed),
+        }
+    }
+
+    /// Run a frame of the ui, paint it into the framebuffer and flush it to the gpu.
+    ///
+    /// Swap the window's buffers afterwards.
+    pub fn run_and_paint(
+        &mut self,
+        input: egui::RawInput,
+        run_ui: impl FnMut(&Context),
+    ) -> egui::PlatformOutput {
+        self.frame_limiter.begin_frame();
+        let platform_output = self.egui_skia.run(input, run_ui);
+
+        if let Some(surface) = &mut self.surface {
+            match &mut self.msaa_surface {
+                Some(msaa_surface) => {
+                    let canvas = msaa_surface.canvas();
+                    canvas.clear(Color::TRANSPARENT);
+                    self.egui_skia.paint(canvas);
+
+                    let canvas = surface.canvas();
+                    canvas.clear(Color::TRANSPARENT);
+                    msaa_surface.draw(canvas, (0, 0), SamplingOptions::default(), None);
+                }
+                None => {
+                    let canvas = surface.canvas();
+                    canvas.clear(Color::TRANSPARENT);
+                    self.egui_skia.paint(canvas);
+                }
+            }
+            self.context.flush_and_submit_surface(surface, None);
+        }
+
+        platform_output
+    }
+}
@@ -0,0 +1,152 @@
+//! Paint egui into a `CAMetalLayer` on macOS.
+
+use core_graphics_types::geometry::CGSize;
+use foreign_types_shared::ForeignType;
+use metal::{CommandQueue, Device, MTLPixelFormat, MetalLayer, MetalLayerRef};
+use objc::rc::autoreleasepool;
+use objc::runtime::Object;
+use objc::{msg_send, sel, sel_impl};
+use skia_safe::gpu::{
+    self, backend_render_targets, direct_contexts, mtl, DirectContext, SurfaceOrigin,
+};
+use skia_safe::{ColorSpace, ColorType};
+
+use super::{BackendError, FrameLimiter, Multisampling, PresentMode};
+use crate::EguiSkia;
+
+/// A Skia Metal context drawing into the drawables of a `CAMetalLayer`.
+pub struct MetalBackend {
+    layer: MetalLayer,
+    queue: CommandQueue,
+    context: DirectContext,
+    color_space: Option<ColorSpace>,
+    frame_limiter: FrameLimiter,
+    multisampling: Multisampling,
+}
+
+impl MetalBackend {
+    /// Render into `layer` with `device`. The layer's pixel format is set to `BGRA8Unorm`.
+    ///
+    /// Attach the layer to the window's view (`setWantsLayer:` / `setLayer:`) before painting.
+    pub fn new(device: &Device, layer: MetalLayer) -> Result<Self, BackendError> {
+        layer.set_device(device);
+        layer.set_pixel_format(MTLPixelFormat::BGRA8Unorm);
+        layer.set_presents_with_transaction(false);
+
+        let queue = device.new_command_queue();
+        let backend = unsafe {
+            mtl::BackendContext::new(
+                device.as_ptr() as mtl::Handle,
+                queue.as_ptr() as mtl::Handle,
+            )
+        };
+        let context = direct_contexts::make_metal(&backend, None)
+            .ok_or(BackendError::ContextCreationFailed)?;
+
+        Ok(Self {
+            layer,
+            queue,
+            context,
+            color_space: None,
+            frame_limiter: FrameLimiter::default(),
+            multisampling: Multisampling::default(),
+        })
+    }
+
+    pub fn layer(&self) -> &MetalLayerRef {
+        &self.layer
+    }
+
+    pub fn direct_context(&mut self) -> &mut DirectContext {
+        &mut self.context
+    }
+
+    /// Limits the frame rate of [`Self::paint`], before it acquires a drawable, and reports frame
+    /// times.
+    pub fn frame_limiter(&mut self) -> &mut FrameLimiter {
+        &mut self.frame_limiter
+    }
+
+    /// Synchronize presenting with the display (`CAMetalLayer.displaySyncEnabled`). Metal
+    /// doesn't have adaptive vsync, [`PresentMode::AdaptiveVSync`] waits for the display.
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        self.layer
+            .set_display_sync_enabled(present_mode != PresentMode::Immediate);
+    }
+
+    /// The scale of the layer's contents, i.e. physical pixels per point.
+    ///
+    /// Set this as the egui context's pixels per point, e.g. after the window moved to a
+    /// monitor with a different scale.
+    pub fn pixels_per_point(&self) -> f32 {
+        let layer = self.layer.as_ptr() as *mut Object;
+        let scale: f64 = unsafe { msg_send![layer, contentsScale] };
+        scale as f32
+    }
+
+    /// Tag the drawables with `color_space`, so Skia converts egui's sRGB colors and textures
+    /// into it. Set this to the color space of the layer (`CAMetalLayer.colorspace`), e.g.
+    /// [`ColorSpace::new_rgb`] with Display P3 primaries, otherwise egui's colors are displayed
+    /// oversaturated on wide-gamut layers.
+    pub fn set_color_space(&mut self, color_space: Option<ColorSpace>) {
+        self.color_space = color_space;
+    }
+
+    /// Paint the ui with `sample_count` samples per pixel for hardware antialiasing.
+    ///
+    /// The ui is then painted into a multisampled offscreen surface, which is resolved into the
+    /// drawable every frame. The sample count is clamped to what the gpu supports. `None`
+    /// paints directly into the drawable again.
+    pub fn set_sample_count(&mut self, sample_count: Option<usize>) {
+        self.multisampling.set_sample_count(sample_count);
+    }
+
+    /// Resize the drawables to `size` pixels, e.g. when the window was resized.
+    pub fn resize(&mut self, size: (u32, u32)) {
+        self.layer
+            .set_drawable_size(CGSize::new(size.0 as f64, size.1 as f64));
+    }
+
+    /// Acquire the next drawable, paint the last frame of `egui_skia` into it and present it.
+    ///
+    /// Returns `Ok(false)` if no drawable was available, e.g. because the window is hidden, and
+    /// fails if the drawable couldn't be wrapped into a surface.
+    pub fn paint(&mut self, egui_skia: &mut EguiSkia) -> Result<bool, BackendError> {
+        autoreleasepool(|| {
+            self.frame_limiter.begin_frame();
+            let Some(drawable) = self.layer.next_drawable() else {
+                return Ok(false);
+            };
+            let size = self.layer.drawable_size();
+
+            let render_target = unsafe {
+                let texture_info =
+                    mtl::TextureInfo::new(drawable.texture().as_ptr() as mtl::Handle);
+                backend_render_targets::make_mtl(
+                    (size.width as i32, size.height as i32),
+                    &texture_info,
+                )
+            };
+            let Some(mut surface) = gpu::surfaces::wrap_backend_render_target(
+                &mut self.context,
+                &render_target,
+                SurfaceOrigin::TopLeft,
+                ColorType::BGRA8888,
+                self.color_space.clone(),
+                None,
+            ) else {
+                return Err(BackendError::SurfaceCreationFailed);
+            };
+
+            self.multisampling
+                .paint(&mut self.context, &mut surface, egui_skia);
+            self.context.flush_and_submit_surface(&mut surface, None);
+            drop(surface);
+
+            let command_buffer = self.queue.new_command_buffer();
+            command_buffer.present_drawable(drawable);
+            command_buffer.commit();
+            Ok(true)
+        })
+    }
+}
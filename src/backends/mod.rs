@@ -0,0 +1,436 @@
+//! Creation of the surfaces egui is painted onto, with a transparent fallback from the gpu to
+//! raster surfaces.
+//!
+//! Gpu context or surface creation fails on machines without working drivers, in virtual
+//! machines and on headless CI. Instead of erroring out, [`RenderBackend`] then continues with
+//! raster surfaces, whose pixels can be presented with e.g. softbuffer. Use
+//! [`RenderBackend::active`] to find out which path is used.
+//!
+//! The window helpers report failures as a [`BackendError`]. Wrap them into [`GpuOrRaster`] to
+//! fall back to a raster surface the same way:
+//!
+//! ```ignore
+//! let mut window = GpuOrRaster::new(|| unsafe { sdl2::gl_window(&video, &window) });
+//! match &mut window {
+//!     GpuOrRaster::Gpu(gl_window) => { gl_window.run_and_paint(input, ui); }
+//!     GpuOrRaster::Raster { .. } => { /* paint into a raster surface, then present_raster */ }
+//! }
+//! ```
+
+#[cfg(all(feature = "android", target_os = "android"))]
+pub mod android;
+#[cfg(all(feature = "d3d", windows))]
+pub mod d3d;
+#[cfg(feature = "gl")]
+pub mod gl;
+#[cfg(all(feature = "metal", target_os = "macos"))]
+pub mod metal;
+#[cfg(feature = "sdl2")]
+pub mod sdl2;
+#[cfg(feature = "vulkan")]
+pub mod vulkan;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use skia_safe::{surfaces, ColorSpace, ISize, ImageInfo, Surface};
+
+#[cfg(feature = "gpu")]
+use skia_safe::gpu::{self, Budgeted, DirectContext, SurfaceOrigin};
+#[cfg(feature = "gpu")]
+use skia_safe::{AlphaType, Color, ColorType, SamplingOptions};
+
+/// The kind of surfaces a [`RenderBackend`] creates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActiveBackend {
+    /// Gpu surfaces of a [`DirectContext`](skia_safe::gpu::DirectContext).
+    Gpu,
+    /// Cpu backed surfaces. Enable the `cpu_fix` feature when rendering with these.
+    Raster,
+}
+
+/// Why a gpu window helper couldn't be created or paint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BackendError {
+    /// Skia couldn't create a gpu context, e.g. because of missing or broken drivers.
+    ContextCreationFailed,
+    /// A function of the graphics api couldn't be loaded.
+    MissingFunction(&'static str),
+    /// The window's framebuffer, swapchain image or drawable couldn't be wrapped into a
+    /// surface.
+    SurfaceCreationFailed,
+    /// The pixel format of the window's buffers isn't supported.
+    UnsupportedFormat,
+    /// The driver rejected the present mode.
+    UnsupportedPresentMode,
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ContextCreationFailed => write!(f, "failed to create the gpu context"),
+            Self::MissingFunction(name) => write!(f, "failed to load {name}"),
+            Self::SurfaceCreationFailed => write!(f, "failed to create the window surface"),
+            Self::UnsupportedFormat => write!(f, "unsupported pixel format"),
+            Self::UnsupportedPresentMode => write!(f, "unsupported present mode"),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+/// A gpu window helper, or the reason painting fell back to raster surfaces.
+pub enum GpuOrRaster<T> {
+    Gpu(T),
+    /// Paint into a raster surface, e.g. created by [`RenderBackend::raster`], and present its
+    /// pixels, e.g. with softbuffer or `sdl2::present_raster`.
+    Raster {
+        error: BackendError,
+    },
+}
+
+impl<T> GpuOrRaster<T> {
+    /// Create the gpu helper with `create`, falling back to raster surfaces if that fails.
+    pub fn new(create: impl FnOnce() -> Result<T, BackendError>) -> Self {
+        match create() {
+            Ok(gpu) => Self::Gpu(gpu),
+            Err(error) => {
+                log::warn!("Falling back to raster surfaces: {error}");
+                Self::Raster { error }
+            }
+        }
+    }
+
+    /// Continue with raster surfaces, e.g. after the gpu helper failed to wrap a resized
+    /// swapchain. The gpu helper is dropped.
+    pub fn fall_back(&mut self, error: BackendError) {
+        log::warn!("Falling back to raster surfaces: {error}");
+        *self = Self::Raster { error };
+    }
+
+    /// Which kind of surfaces are painted onto.
+    pub fn active(&self) -> ActiveBackend {
+        match self {
+            Self::Gpu(_) => ActiveBackend::Gpu,
+            Self::Raster { .. } => ActiveBackend::Raster,
+        }
+    }
+
+    /// The gpu helper, if the gpu backend is active.
+    pub fn gpu(&mut self) -> Option<&mut T> {
+        match self {
+            Self::Gpu(gpu) => Some(gpu),
+            Self::Raster { .. } => None,
+        }
+    }
+
+    /// Why the gpu helper isn't used, if it isn't.
+    pub fn fallback_reason(&self) -> Option<&BackendError> {
+        match self {
+            Self::Gpu(_) => None,
+            Self::Raster { error } => Some(error),
+        }
+    }
+}
+
+#[cfg(feature = "gpu")]
+type GpuSurfaceFactory =
+    Box<dyn FnMut(&mut DirectContext, ISize, Option<ColorSpace>) -> Option<Surface>>;
+
+#[cfg(feature = "gpu")]
+struct GpuBackend {
+    context: DirectContext,
+    create_surface: GpuSurfaceFactory,
+}
+
+pub struct RenderBackend {
+    #[cfg(feature = "gpu")]
+    gpu: Option<GpuBackend>,
+    color_space: Option<ColorSpace>,
+}
+
+impl RenderBackend {
+    /// A backend that only creates raster surfaces.
+    pub fn raster() -> Self {
+        Self {
+            #[cfg(feature = "gpu")]
+            gpu: None,
+            color_space: None,
+        }
+    }
+
+    /// A backend that creates gpu surfaces with `create_surface`, falling back to raster
+    /// surfaces if `create_context` returns `None`, or once `create_surface` fails.
+    ///
+    /// `create_surface` gets the size and the color space set with [`Self::set_color_space`].
+    #[cfg(feature = "gpu")]
+    pub fn gpu_or_raster(
+        create_context: impl FnOnce() -> Option<DirectContext>,
+        create_surface: impl FnMut(&mut DirectContext, ISize, Option<ColorSpace>) -> Option<Surface>
+            + 'static,
+    ) -> Self {
+        Self {
+            gpu: create_context().map(|context| GpuBackend {
+                context,
+                create_surface: Box::new(create_surface),
+            }),
+            color_space: None,
+        }
+    }
+
+    /// Create the following surfaces with `color_space`, e.g. the color space of the display
+    /// the window is on, see `EguiSkiaWinit::color_space`. Recreate the surface afterwards.
+    pub fn set_color_space(&mut self, color_space: Option<ColorSpace>) {
+        self.color_space = color_space;
+    }
+
+    /// Which kind of surfaces [`Self::create_surface`] currently creates.
+    pub fn active(&self) -> ActiveBackend {
+        #[cfg(feature = "gpu")]
+        if self.gpu.is_some() {
+            return ActiveBackend::Gpu;
+        }
+        ActiveBackend::Raster
+    }
+
+    /// The gpu context, if the gpu backend is active.
+    #[cfg(feature = "gpu")]
+    pub fn direct_context(&mut self) -> Option<&mut DirectContext> {
+        self.gpu.as_mut().map(|gpu| &mut gpu.context)
+    }
+
+    /// Create a surface of `size`, e.g. after the window was resized.
+    ///
+    /// If the gpu surface can't be created, the gpu context is abandoned and this and all
+    /// following calls return raster surfaces.
+    pub fn create_surface(&mut self, size: impl Into<ISize>) -> Option<Surface> {
+        let size = size.into();
+
+        #[cfg(feature = "gpu")]
+        if let Some(gpu) = &mut self.gpu {
+            if let Some(surface) =
+                (gpu.create_surface)(&mut gpu.context, size, self.color_space.clone())
+            {
+                return Some(surface);
+            }
+            gpu.context.abandon();
+            self.gpu = None;
+        }
+
+        surfaces::raster(
+            &ImageInfo::new_n32_premul(size, self.color_space.clone()),
+            None,
+            None,
+        )
+    }
+}
+
+/// A gpu surface of `size` that is rendered with `sample_count` samples per pixel, so egui's
+/// meshes get hardware antialiasing on top of their feathering.
+///
+/// The sample count is clamped to what the gpu supports for `color_type`. Paint into the
+/// surface and draw it onto the window's surface with [`Surface::draw`], which resolves the
+/// samples. Use it in the `create_surface` function of [`RenderBackend::gpu_or_raster`] for
+/// offscreen rendering, or see `set_sample_count` of the window helpers.
+#[cfg(feature = "gpu")]
+pub fn msaa_surface(
+    context: &mut DirectContext,
+    size: impl Into<ISize>,
+    color_type: ColorType,
+    color_space: Option<ColorSpace>,
+    sample_count: usize,
+) -> Option<Surface> {
+    let sample_count = sample_count
+        .min(context.max_surface_sample_count_for_color_type(color_type))
+        .max(1);
+    let info = ImageInfo::new(size, color_type, AlphaType::Premul, color_space);
+    gpu::surfaces::render_target(
+        context,
+        Budgeted::No,
+        &info,
+        sample_count,
+        SurfaceOrigin::TopLeft,
+        None,
+        false,
+        None,
+    )
+}
+
+/// The multisampled surface the Metal, Vulkan and Direct3D helpers paint the ui into, see
+/// their `set_sample_count`. It is recreated whenever the window's surface changes size.
+#[cfg(feature = "gpu")]
+#[derive(Default)]
+pub(crate) struct Multisampling {
+    sample_count: Option<usize>,
+    surface: Option<Surface>,
+}
+
+#[cfg(feature = "gpu")]
+impl Multisampling {
+    pub fn set_sample_count(&mut self, sample_count: Option<usize>) {
+        if self.sample_count != sample_count {
+            self.sample_count = sample_count;
+            self.surface = None;
+        }
+    }
+
+    /// Clear `surface` and paint the last frame of `egui_skia` onto it, resolved from the
+    /// multisampled surface if a sample count is set.
+    pub fn paint(
+        &mut self,
+        context: &mut DirectContext,
+        surface: &mut Surface,
+        egui_skia: &mut crate::EguiSkia,
+    ) {
+        let size = ISize::new(surface.width(), surface.height());
+        if self
+            .surface
+            .as_ref()
+            .is_some_and(|msaa| ISize::new(msaa.width(), msaa.height()) != size)
+        {
+            self.surface = None;
+        }
+        if self.surface.is_none() {
+            let info = surface.image_info();
+            self.surface = self
+                .sample_count
+                .filter(|sample_count| *sample_count > 1)
+                .and_then(|sample_count| {
+                    msaa_surface(
+                        context,
+                        size,
+                        info.color_type(),
+                        info.color_space(),
+                        sample_count,
+                    )
+                });
+        }
+
+        let canvas = surface.canvas();
+        canvas.clear(Color::TRANSPARENT);
+        match &mut self.surface {
+            Some(msaa_surface) => {
+                let msaa_canvas = msaa_surface.canvas();
+                msaa_canvas.clear(Color::TRANSPARENT);
+                egui_skia.paint(msaa_canvas);
+                msaa_surface.draw(canvas, (0, 0), SamplingOptions::default(), None);
+            }
+            None => egui_skia.paint(canvas),
+        }
+    }
+}
+
+impl Default for RenderBackend {
+    fn default() -> Self {
+        Self::raster()
+    }
+}
+
+/// How presented frames are synchronized with the display's refresh.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Wait for the vertical blank, no tearing. Limits the frame rate to the refresh rate.
+    #[default]
+    VSync,
+    /// Like [`Self::VSync`], but present late frames immediately instead of waiting for the
+    /// next vertical blank, which can tear.
+    AdaptiveVSync,
+    /// Present immediately, which can tear.
+    Immediate,
+}
+
+impl PresentMode {
+    /// The swap interval for `eglSwapInterval`, `wglSwapIntervalEXT` or
+    /// `SDL_GL_SetSwapInterval`. Adaptive vsync is the negative interval `-1`, which needs the
+    /// `EXT_swap_control_tear` extensions.
+    pub fn swap_interval(self) -> i32 {
+        match self {
+            PresentMode::VSync => 1,
+            PresentMode::AdaptiveVSync => -1,
+            PresentMode::Immediate => 0,
+        }
+    }
+
+    /// The sync interval to pass to Direct3D's `Present`, which only accepts 0 to 4. Direct3D
+    /// doesn't have adaptive vsync, [`Self::AdaptiveVSync`] waits for the vertical blank.
+    pub fn sync_interval(self) -> u32 {
+        match self {
+            PresentMode::VSync | PresentMode::AdaptiveVSync => 1,
+            PresentMode::Immediate => 0,
+        }
+    }
+
+    /// The `VkPresentModeKHR` to create a Vulkan swapchain with: `VK_PRESENT_MODE_FIFO_KHR`,
+    /// `VK_PRESENT_MODE_FIFO_RELAXED_KHR` or `VK_PRESENT_MODE_IMMEDIATE_KHR`.
+    pub fn vk_present_mode(self) -> i32 {
+        match self {
+            PresentMode::VSync => 2,
+            PresentMode::AdaptiveVSync => 3,
+            PresentMode::Immediate => 0,
+        }
+    }
+}
+
+/// Limits the frame rate by sleeping until the next frame is due, and reports how long frames
+/// actually took.
+///
+/// Frames are paced when they begin, before the window helpers acquire an image or run the ui,
+/// so the frame that is painted after the wait isn't older than the wait. Hosts that acquire
+/// swapchain images themselves call [`Self::begin_frame`] before acquiring. Without a maximum
+/// frame rate, frames are only measured.
+#[derive(Default)]
+pub struct FrameLimiter {
+    frame_interval: Option<Duration>,
+    last_frame: Option<Instant>,
+    on_frame: Option<Box<dyn FnMut(Duration)>>,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: Option<f32>) -> Self {
+        let mut limiter = Self::default();
+        limiter.set_max_fps(max_fps);
+        limiter
+    }
+
+    /// Paint at most `max_fps` frames per second. `None`, and rates too close to zero for the
+    /// interval between frames to be represented, don't limit the frame rate.
+    pub fn set_max_fps(&mut self, max_fps: Option<f32>) {
+        self.frame_interval = max_fps
+            .filter(|fps| *fps > 0.0)
+            .and_then(|fps| Duration::try_from_secs_f32(1.0 / fps).ok());
+    }
+
+    /// Call `on_frame` with the time since the previous frame whenever a frame begins, e.g. to
+    /// show the frame rate or to advance animations by the real frame time.
+    pub fn set_frame_time_callback(&mut self, on_frame: impl FnMut(Duration) + 'static) {
+        self.on_frame = Some(Box::new(on_frame));
+    }
+
+    /// When the next frame is due, to let the event loop wait until then instead of sleeping
+    /// in [`Self::begin_frame`].
+    pub fn next_frame(&self) -> Option<Instant> {
+        Some(self.last_frame? + self.frame_interval?)
+    }
+
+    /// Sleep until the next frame is due and return the time since the previous frame began.
+    pub fn begin_frame(&mut self) -> Duration {
+        if let Some(next_frame) = self.next_frame() {
+            let now = Instant::now();
+            if next_frame > now {
+                std::thread::sleep(next_frame - now);
+            }
+        }
+
+        let now = Instant::now();
+        let frame_time = self
+            .last_frame
+            .map(|last_frame| now - last_frame)
+            .unwrap_or_default();
+        self.last_frame = Some(now);
+
+        if let Some(on_frame) = &mut self.on_frame {
+            on_frame(frame_time);
+        }
+        frame_time
+    }
+}
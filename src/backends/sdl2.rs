@@ -0,0 +1,322 @@
+//! Use egui_skia in SDL2 applications: translate SDL2 events into egui input, apply egui's
+//! platform output, and present frames with a raster or gl surface.
+//!
+//! Screen readers aren't supported here, the `accesskit` feature only covers the winit
+//! integration.
+
+use std::time::Instant;
+
+use egui::{Key, Modifiers, PointerButton, Pos2, RawInput, Rect, ViewportId};
+use sdl2::clipboard::ClipboardUtil;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::{Cursor, MouseButton, SystemCursor};
+use sdl2::video::Window;
+use sdl2::{EventPump, VideoSubsystem};
+use skia_safe::{AlphaType, ColorType, ImageInfo, Surface};
+
+/// Collects egui input from SDL2 events.
+pub struct Sdl2Input {
+    raw_input: RawInput,
+    clipboard: ClipboardUtil,
+    video: VideoSubsystem,
+    pixels_per_point: f32,
+    pointer_pos: Pos2,
+    modifiers: Modifiers,
+    start_time: Instant,
+    cursor: Option<(egui::CursorIcon, Cursor)>,
+}
+
+impl Sdl2Input {
+    pub fn new(video: &VideoSubsystem, window: &Window) -> Self {
+        Self {
+            raw_input: RawInput::default(),
+            clipboard: video.clipboard(),
+            video: video.clone(),
+            pixels_per_point: pixels_per_point(window),
+            pointer_pos: Pos2::ZERO,
+            modifiers: Modifiers::default(),
+            start_time: Instant::now(),
+            cursor: None,
+        }
+    }
+
+    /// The ratio of the window's drawable size to its size, `2.0` on high dpi displays.
+    pub fn pixels_per_point(&self) -> f32 {
+        self.pixels_per_point
+    }
+
+    /// Translate `event` into egui input. Events of other windows are ignored.
+    pub fn handle_event(&mut self, window: &Window, event: &Event) {
+        if event.get_window_id().is_some_and(|id| id != window.id()) {
+            return;
+        }
+
+        let events = &mut self.raw_input.events;
+        match event {
+            Event::MouseMotion { x, y, .. } => {
+                // SDL's window coordinates are points, the drawable size is in pixels.
+                self.pointer_pos = Pos2::new(*x as f32, *y as f32);
+                events.push(egui::Event::PointerMoved(self.pointer_pos));
+            }
+            Event::MouseButtonDown { mouse_btn, .. } | Event::MouseButtonUp { mouse_btn, .. } => {
+                let button = match mouse_btn {
+                    MouseButton::Left => PointerButton::Primary,
+                    MouseButton::Right => PointerButton::Secondary,
+                    MouseButton::Middle => PointerButton::Middle,
+                    MouseButton::X1 => PointerButton::Extra1,
+                    MouseButton::X2 => PointerButton::Extra2,
+                    MouseButton::Unknown => return,
+                };
+                events.push(egui::Event::PointerButton {
+                    pos: self.pointer_pos,
+                    button,
+                    pressed: matches!(event, Event::MouseButtonDown { .. }),
+                    modifiers: self.modifiers,
+                });
+            }
+            Event::MouseWheel { x, y, .. } => {
+                events.push(egui::Event::MouseWheel {
+                    unit: egui::MouseWheelUnit::Line,
+                    delta: egui::vec2(*x as f32, *y as f32),
+                    modifiers: self.modifiers,
+                });
+            }
+            Event::KeyDown {
+                keycode,
+                keymod,
+                repeat,
+                ..
+            }
+            | Event::KeyUp {
+                keycode,
+                keymod,
+                repeat,
+                ..
+            } => {
+                let pressed = matches!(event, Event::KeyDown { .. });
+                self.modifiers = modifiers(*keymod);
+                self.raw_input.modifiers = self.modifiers;
+
+                let Some(key) = (*keycode).and_then(translate_key) else {
+                    return;
+                };
+                if pressed && self.modifiers.command {
+                    match key {
+                        Key::C => events.push(egui::Event::Copy),
+                        Key::X => events.push(egui::Event::Cut),
+                        Key::V => {
+                            if let Ok(text) = self.clipboard.clipboard_text() {
+                                events.push(egui::Event::Paste(text));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                events.push(egui::Event::Key {
+                    key,
+                    physical_key: None,
+                    pressed,
+                    repeat: *repeat,
+                    modifiers: self.modifiers,
+                });
+            }
+            Event::TextInput { text, .. } => {
+                if !text.chars().any(char::is_control) {
+                    events.push(egui::Event::Text(text.clone()));
+                }
+            }
+            // SDL doesn't report files while they are dragged over the window, only once they
+            // are dropped.
+            Event::DropFile { filename, .. } => {
+                self.raw_input.dropped_files.push(egui::DroppedFile {
+                    path: Some(filename.into()),
+                    ..Default::default()
+                });
+            }
+            Event::Window { win_event, .. } => match win_event {
+                WindowEvent::SizeChanged(..) | WindowEvent::Resized(..) => {
+                    self.pixels_per_point = pixels_per_point(window);
+                }
+                WindowEvent::FocusGained => events.push(egui::Event::WindowFocused(true)),
+                WindowEvent::FocusLost => events.push(egui::Event::WindowFocused(false)),
+                WindowEvent::Leave => events.push(egui::Event::PointerGone),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// The input collected since the last call, for [`EguiSkia::run`](crate::EguiSkia::run).
+    pub fn take_egui_input(&mut self, window: &Window) -> RawInput {
+        let (width, height) = window.drawable_size();
+        let screen_rect = Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(width as f32, height as f32) / self.pixels_per_point,
+        );
+
+        let mut raw_input = self.raw_input.take();
+        raw_input.screen_rect = Some(screen_rect);
+        raw_input.time = Some(self.start_time.elapsed().as_secs_f64());
+        raw_input.modifiers = self.modifiers;
+        raw_input.focused = window.has_input_focus();
+
+        let viewport = raw_input.viewports.entry(ViewportId::ROOT).or_default();
+        viewport.inner_rect = Some(screen_rect);
+        viewport.native_pixels_per_point = Some(self.pixels_per_point);
+
+        raw_input
+    }
+
+    /// Apply egui's platform output: copied text, the cursor and text input for IME.
+    pub fn handle_platform_output(&mut self, platform_output: &egui::PlatformOutput) {
+        for command in &platform_output.commands {
+            if let egui::OutputCommand::CopyText(text) = command {
+                let _ = self.clipboard.set_clipboard_text(text);
+            }
+        }
+
+        let text_input = self.video.text_input();
+        match (platform_output.ime.is_some(), text_input.is_active()) {
+            (true, false) => text_input.start(),
+            (false, true) => text_input.stop(),
+            _ => {}
+        }
+
+        self.set_cursor(platform_output.cursor_icon);
+    }
+
+    fn set_cursor(&mut self, icon: egui::CursorIcon) {
+        if matches!(&self.cursor, Some((current, _)) if *current == icon) {
+            return;
+        }
+
+        let mouse = self.video.sdl().mouse();
+        if icon == egui::CursorIcon::None {
+            mouse.show_cursor(false);
+            self.cursor = None;
+            return;
+        }
+        mouse.show_cursor(true);
+
+        let system_cursor = match icon {
+            egui::CursorIcon::Text | egui::CursorIcon::VerticalText => SystemCursor::IBeam,
+            egui::CursorIcon::Crosshair | egui::CursorIcon::Cell => SystemCursor::Crosshair,
+            egui::CursorIcon::PointingHand => SystemCursor::Hand,
+            egui::CursorIcon::Wait => SystemCursor::Wait,
+            egui::CursorIcon::Progress => SystemCursor::WaitArrow,
+            egui::CursorIcon::NotAllowed | egui::CursorIcon::NoDrop => SystemCursor::No,
+            egui::CursorIcon::Move
+            | egui::CursorIcon::AllScroll
+            | egui::CursorIcon::Grab
+            | egui::CursorIcon::Grabbing => SystemCursor::SizeAll,
+            egui::CursorIcon::ResizeHorizontal
+            | egui::CursorIcon::ResizeEast
+            | egui::CursorIcon::ResizeWest
+            | egui::CursorIcon::ResizeColumn => SystemCursor::SizeWE,
+            egui::CursorIcon::ResizeVertical
+            | egui::CursorIcon::ResizeNorth
+            | egui::CursorIcon::ResizeSouth
+            | egui::CursorIcon::ResizeRow => SystemCursor::SizeNS,
+            egui::CursorIcon::ResizeNeSw
+            | egui::CursorIcon::ResizeNorthEast
+            | egui::CursorIcon::ResizeSouthWest => SystemCursor::SizeNESW,
+            egui::CursorIcon::ResizeNwSe
+            | egui::CursorIcon::ResizeNorthWest
+            | egui::CursorIcon::ResizeSouthEast => SystemCursor::SizeNWSE,
+            _ => SystemCursor::Arrow,
+        };
+        if let Ok(cursor) = Cursor::from_system(system_cursor) {
+            cursor.set();
+            self.cursor = Some((icon, cursor));
+        }
+    }
+}
+
+/// Copy a raster surface into the window's surface and show it, for rendering without a gpu.
+///
+/// `surface` should have the size of the window.
+pub fn present_raster(
+    surface: &mut Surface,
+    window: &Window,
+    event_pump: &EventPump,
+) -> Result<(), String> {
+    let mut window_surface = window.surface(event_pump)?;
+    let (width, height) = (window_surface.width(), window_surface.height());
+    let row_bytes = window_surface.pitch() as usize;
+
+    // SDL's 32 bit RGB formats are packed, which is BGRA in memory on little endian machines.
+    let info = ImageInfo::new(
+        (width as i32, height as i32),
+        ColorType::BGRA8888,
+        AlphaType::Premul,
+        None,
+    );
+    window_surface.with_lock_mut(|pixels| {
+        surface.read_pixels(&info, pixels, row_bytes, (0, 0));
+    });
+    window_surface.update_window()
+}
+
+/// Wrap the framebuffer of the window's current gl context, see
+/// [`EguiSkiaGlWindow`](super::gl::EguiSkiaGlWindow). Wrap the result into
+/// [`GpuOrRaster`](super::GpuOrRaster) to fall back to [`present_raster`] if that fails.
+///
+/// # Safety
+///
+/// The window's gl context has to be current whenever the returned window is used.
+#[cfg(feature = "gl")]
+pub unsafe fn gl_window(
+    video: &VideoSubsystem,
+    window: &Window,
+) -> Result<super::gl::EguiSkiaGlWindow, super::BackendError> {
+    let (width, height) = window.drawable_size();
+    super::gl::EguiSkiaGlWindow::new(
+        |name| video.gl_get_proc_address(name) as *const _,
+        (width as i32, height as i32),
+    )
+}
+
+/// Set the vsync of the current gl context, for windows painted with [`gl_window`].
+#[cfg(feature = "gl")]
+pub fn set_present_mode(
+    video: &VideoSubsystem,
+    present_mode: super::PresentMode,
+) -> Result<(), String> {
+    use sdl2::video::SwapInterval;
+
+    video.gl_set_swap_interval(match present_mode {
+        super::PresentMode::VSync => SwapInterval::VSync,
+        super::PresentMode::AdaptiveVSync => SwapInterval::LateSwapTearing,
+        super::PresentMode::Immediate => SwapInterval::Immediate,
+    })
+}
+
+fn pixels_per_point(window: &Window) -> f32 {
+    let (drawable_width, _) = window.drawable_size();
+    let (width, _) = window.size();
+    if width == 0 {
+        1.0
+    } else {
+        drawable_width as f32 / width as f32
+    }
+}
+
+fn modifiers(keymod: Mod) -> Modifiers {
+    let alt = keymod.intersects(Mod::LALTMOD | Mod::RALTMOD);
+    let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+    let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+    let gui = keymod.intersects(Mod::LGUIMOD | Mod::RGUIMOD);
+
+    Modifiers {
+        alt,
+        ctrl,
+        shift,
+        mac_cmd: cfg!(target_os = "macos") && gui,
+        command: if cfg!(target_os = "macos") { gui } else { ctrl },
+    }
+}
+
+fn translate_key(keycode: Keycode) -> Option<Key> {
+    Key::from_name(&keycode.name())
+}
@@ -0,0 +1,236 @@
+//! Paint egui into the swapchain images of a Vulkan window.
+//!
+//! The host keeps owning the Vulkan objects (e.g. created with ash or vulkano) and drives the
+//! swapchain: it acquires an image, lets [`VulkanBackend`] paint into it, and presents it. The
+//! gpu work waits on the semaphore signalled by the acquire, and signals a semaphore the
+//! present waits on.
+//!
+//! ```ignore
+//! backend.frame_limiter().begin_frame();
+//! let image_index = acquire_next_image(image_available, ..);
+//! unsafe {
+//!     backend.paint(image_index, image_available, &mut egui_skia);
+//!     backend.present(image_index, render_finished);
+//! }
+//! queue_present(image_index, render_finished, ..);
+//! ```
+
+use std::ffi::c_void;
+
+use skia_safe::gpu::vk::{self, GetProcOf};
+use skia_safe::gpu::{
+    backend_render_targets, backend_semaphores, direct_contexts, BackendSurfaceAccess,
+    DirectContext, FlushInfo, SurfaceOrigin,
+};
+use skia_safe::{gpu, ColorSpace, ColorType, Surface};
+
+use super::{BackendError, FrameLimiter, Multisampling, PresentMode};
+use crate::EguiSkia;
+
+/// A Skia Vulkan context and the surfaces wrapping the images of a swapchain.
+pub struct VulkanBackend {
+    context: DirectContext,
+    surfaces: Vec<Surface>,
+    frame_limiter: FrameLimiter,
+    present_mode: PresentMode,
+    multisampling: Multisampling,
+}
+
+impl VulkanBackend {
+    /// Create the Skia context for the given Vulkan objects.
+    ///
+    /// `get_proc` resolves Vulkan functions, e.g. with `vkGetInstanceProcAddr` and
+    /// `vkGetDeviceProcAddr`.
+    ///
+    /// # Safety
+    ///
+    /// The handles have to be valid and outlive the backend. The queue must be of the given
+    /// family and support graphics.
+    pub unsafe fn new(
+        instance: vk::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: vk::Device,
+        queue: vk::Queue,
+        queue_family_index: usize,
+        get_proc: impl Fn(GetProcOf) -> *const c_void,
+    ) -> Result<Self, BackendError> {
+        let backend_context = vk::BackendContext::new(
+            instance,
+            physical_device,
+            device,
+            (queue, queue_family_index),
+            &get_proc,
+        );
+        let context = direct_contexts::make_vulkan(&backend_context, None)
+            .ok_or(BackendError::ContextCreationFailed)?;
+
+        Ok(Self {
+            context,
+            surfaces: Vec::new(),
+            frame_limiter: FrameLimiter::default(),
+            present_mode: PresentMode::default(),
+            multisampling: Multisampling::default(),
+        })
+    }
+
+    pub fn direct_context(&mut self) -> &mut DirectContext {
+        &mut self.context
+    }
+
+    /// Limits the frame rate and reports frame times. The host acquires the swapchain images,
+    /// so it calls [`FrameLimiter::begin_frame`] before acquiring the next one.
+    pub fn frame_limiter(&mut self) -> &mut FrameLimiter {
+        &mut self.frame_limiter
+    }
+
+    /// Choose how frames are synchronized with the display. The host owns the swapchain, so
+    /// the mode takes effect when it (re)creates the swapchain with
+    /// [`Self::vk_present_mode`], e.g. right after this call.
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        self.present_mode = present_mode;
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    /// The `VkPresentModeKHR` to create the swapchain with, see
+    /// [`PresentMode::vk_present_mode`]. Fall back to `VK_PRESENT_MODE_FIFO_KHR` if the surface
+    /// doesn't support it, FIFO is always available.
+    pub fn vk_present_mode(&self) -> i32 {
+        self.present_mode.vk_present_mode()
+    }
+
+    /// Paint the ui with `sample_count` samples per pixel for hardware antialiasing in
+    /// [`Self::paint`].
+    ///
+    /// The ui is then painted into a multisampled offscreen surface, which is resolved into the
+    /// swapchain image every frame. The sample count is clamped to what the gpu supports.
+    /// `None` paints directly into the swapchain images again.
+    pub fn set_sample_count(&mut self, sample_count: Option<usize>) {
+        self.multisampling.set_sample_count(sample_count);
+    }
+
+    /// Wrap the images of a (re)created swapchain. Call this after creating the swapchain and
+    /// whenever it is recreated, e.g. after a resize.
+    ///
+    /// Fails if `format` isn't supported or an image can't be wrapped. Supported are
+    /// `B8G8R8A8_UNORM`, `R8G8B8A8_UNORM` and `R8G8B8A8_SRGB`. Skia has no color type for
+    /// `B8G8R8A8_SRGB`, so request a UNORM swapchain, which also blends like egui's other
+    /// backends.
+    ///
+    /// # Safety
+    ///
+    /// `images` have to be the images of the swapchain, created with `format`, `size` and
+    /// color attachment usage, and have to outlive their use by the backend.
+    pub unsafe fn set_swapchain_images(
+        &mut self,
+        images: &[vk::Image],
+        format: vk::Format,
+        size: (i32, i32),
+    ) -> Result<(), BackendError> {
+        self.surfaces.clear();
+
+        let (color_type, color_space) = match format {
+            vk::Format::B8G8R8A8_UNORM => (ColorType::BGRA8888, None),
+            vk::Format::R8G8B8A8_UNORM => (ColorType::RGBA8888, None),
+            vk::Format::R8G8B8A8_SRGB => (ColorType::SRGBA8888, Some(ColorSpace::new_srgb())),
+            _ => return Err(BackendError::UnsupportedFormat),
+        };
+
+        for image in images {
+            let image_info = vk::ImageInfo::new(
+                *image,
+                vk::Alloc::default(),
+                vk::ImageTiling::OPTIMAL,
+                vk::ImageLayout::UNDEFINED,
+                format,
+                1,
+                None,
+                None,
+                None,
+                None,
+            );
+            let render_target = backend_render_targets::make_vk(size, &image_info);
+            let Some(surface) = gpu::surfaces::wrap_backend_render_target(
+                &mut self.context,
+                &render_target,
+                SurfaceOrigin::TopLeft,
+                color_type,
+                color_space.clone(),
+                None,
+            ) else {
+                self.surfaces.clear();
+                return Err(BackendError::SurfaceCreationFailed);
+            };
+            self.surfaces.push(surface);
+        }
+        Ok(())
+    }
+
+    /// The surface of the swapchain image `image_index`, to draw the rest of the frame.
+    ///
+    /// The drawing waits on `image_available`, the semaphore passed to `vkAcquireNextImageKHR`
+    /// for this image.
+    ///
+    /// # Safety
+    ///
+    /// `image_available` has to be a valid binary semaphore with a pending signal. It stays
+    /// owned by the host, which must not destroy it before the frame has been submitted.
+    pub unsafe fn begin_frame(
+        &mut self,
+        image_index: usize,
+        image_available: vk::Semaphore,
+    ) -> Option<&mut Surface> {
+        let surface = self.surfaces.get_mut(image_index)?;
+        let semaphore = backend_semaphores::make_vk(image_available);
+        surface.wait(&[semaphore], Some(false)).then_some(surface)
+    }
+
+    /// Clear the swapchain image `image_index` and paint the last frame of `egui_skia` into it,
+    /// see [`Self::begin_frame`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::begin_frame`].
+    pub unsafe fn paint(
+        &mut self,
+        image_index: usize,
+        image_available: vk::Semaphore,
+        egui_skia: &mut EguiSkia,
+    ) {
+        let Some(surface) = self.surfaces.get_mut(image_index) else {
+            return;
+        };
+        let semaphore = backend_semaphores::make_vk(image_available);
+        if surface.wait(&[semaphore], Some(false)) {
+            self.multisampling
+                .paint(&mut self.context, surface, egui_skia);
+        }
+    }
+
+    /// Flush the drawing into swapchain image `image_index`, transition it for presenting and
+    /// submit the work. `render_finished` is signalled when the image is done, pass it to
+    /// `vkQueuePresentKHR`.
+    ///
+    /// # Safety
+    ///
+    /// `render_finished` has to be a valid, unsignalled binary semaphore. It stays owned by the
+    /// host.
+    pub unsafe fn present(&mut self, image_index: usize, render_finished: vk::Semaphore) {
+        if let Some(surface) = self.surfaces.get_mut(image_index) {
+            let mut semaphores = [backend_semaphores::make_vk(render_finished)];
+            let flush_info = FlushInfo {
+                num_semaphores: semaphores.len(),
+                signal_semaphores: semaphores.as_mut_ptr(),
+                ..FlushInfo::default()
+            };
+            self.context.flush_surface_with_access(
+                surface,
+                BackendSurfaceAccess::Present,
+                &flush_info,
+            );
+        }
+        self.context.submit(None);
+    }
+}
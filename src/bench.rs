@@ -0,0 +1,304 @@
+//! Standardized synthetic workloads for measuring the performance of egui_skia.
+//!
+//! Every workload is run headless on a raster surface, timing the phases of each frame
+//! separately, so changes in egui's layout, the tessellation and the painter can be told apart.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use egui::epaint::{Mesh, Vertex, WHITE_UV};
+use egui::{Color32, ColorImage, Context, Pos2, Shape, TextureHandle, TextureOptions};
+use skia_safe::{surfaces, Color};
+
+use crate::egui_skia::headless_input;
+use crate::{EguiSkia, Painter};
+
+/// A synthetic workload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Workload {
+    /// 10,000 text labels.
+    TextLabels,
+    /// A single mesh of 200,000 triangles, like a dense plot.
+    PlotMesh,
+    /// 50 overlapping windows, each clipping its content.
+    ClippedWindows,
+    /// Upload and free a 256x256 texture every frame.
+    TextureChurn,
+}
+
+impl Workload {
+    pub const ALL: [Workload; 4] = [
+        Workload::TextLabels,
+        Workload::PlotMesh,
+        Workload::ClippedWindows,
+        Workload::TextureChurn,
+    ];
+}
+
+#[derive(Clone, Debug)]
+pub struct BenchOptions {
+    /// The size of the surface in pixels.
+    pub size: (i32, i32),
+    pub pixels_per_point: f32,
+    /// Frames that are rendered before measuring, so fonts and textures are uploaded.
+    pub warmup_frames: usize,
+    /// The number of measured frames.
+    pub frames: usize,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            size: (1920, 1080),
+            pixels_per_point: 1.0,
+            warmup_frames: 3,
+            frames: 60,
+        }
+    }
+}
+
+/// The time spent in each phase, summed over all measured frames.
+#[derive(Clone, Debug)]
+pub struct BenchReport {
+    pub workload: Workload,
+    pub frames: usize,
+    /// Running the ui closure and egui's layout.
+    pub ui: Duration,
+    /// Tessellating the shapes into meshes.
+    pub tessellate: Duration,
+    /// Updating textures and painting the primitives.
+    pub paint: Duration,
+}
+
+impl BenchReport {
+    pub fn total(&self) -> Duration {
+        self.ui + self.tessellate + self.paint
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let per_frame =
+            |duration: Duration| duration.as_secs_f64() * 1000.0 / self.frames.max(1) as f64;
+        write!(
+            f,
+            "{:?}: {:.3} ms/frame (ui {:.3} ms, tessellate {:.3} ms, paint {:.3} ms)",
+            self.workload,
+            per_frame(self.total()),
+            per_frame(self.ui),
+            per_frame(self.tessellate),
+            per_frame(self.paint),
+        )
+    }
+}
+
+/// Run every workload.
+pub fn run_all(options: &BenchOptions) -> Vec<BenchReport> {
+    Workload::ALL
+        .iter()
+        .map(|workload| run(*workload, options))
+        .collect()
+}
+
+/// Run `workload` and report the time spent in each phase.
+pub fn run(workload: Workload, options: &BenchOptions) -> BenchReport {
+    let mut surface = surfaces::raster_n32_premul(options.size).expect("Failed to create surface");
+    let mut backend = EguiSkia::new();
+    backend
+        .egui_ctx
+        .set_pixels_per_point(options.pixels_per_point);
+    let input = headless_input(options.size);
+
+    let mut ui = workload_ui(workload);
+    let mut report = BenchReport {
+        workload,
+        frames: options.frames,
+        ui: Duration::ZERO,
+        tessellate: Duration::ZERO,
+        paint: Duration::ZERO,
+    };
+
+    for frame in 0..options.warmup_frames + options.frames {
+        let start = Instant::now();
+        backend.run(input.clone(), &mut ui);
+        let ran = Instant::now();
+        let (primitives, textures_delta) = backend.take_frame();
+        let tessellated = Instant::now();
+
+        let canvas = surface.canvas();
+        canvas.clear(Color::TRANSPARENT);
+        backend.painter.paint_and_update_textures(
+            canvas,
+            backend.egui_ctx.pixels_per_point(),
+            primitives,
+            textures_delta,
+        );
+        // Make sure the painting isn't deferred past the measurement.
+        surface.image_snapshot();
+        let painted = Instant::now();
+
+        if frame >= options.warmup_frames {
+            report.ui += ran - start;
+            report.tessellate += tessellated - ran;
+            report.paint += painted - tessellated;
+        }
+    }
+
+    report
+}
+
+/// The time spent painting a workload with the painter's vertex cache, and with the cache
+/// cleared before every frame, summed over all measured frames.
+#[derive(Clone, Debug)]
+pub struct VertexCacheReport {
+    pub workload: Workload,
+    pub frames: usize,
+    pub cached: Duration,
+    pub uncached: Duration,
+}
+
+impl fmt::Display for VertexCacheReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let per_frame =
+            |duration: Duration| duration.as_secs_f64() * 1000.0 / self.frames.max(1) as f64;
+        write!(
+            f,
+            "{:?}: paint {:.3} ms/frame with the vertex cache, {:.3} ms/frame without",
+            self.workload,
+            per_frame(self.cached),
+            per_frame(self.uncached),
+        )
+    }
+}
+
+/// Compare painting `workload` with and without reusing the vertices of the previous frame.
+///
+/// Both painters paint the same primitives every frame, only the painting is measured.
+pub fn vertex_cache(workload: Workload, options: &BenchOptions) -> VertexCacheReport {
+    let mut surface = surfaces::raster_n32_premul(options.size).expect("Failed to create surface");
+    let mut backend = EguiSkia::new();
+    backend
+        .egui_ctx
+        .set_pixels_per_point(options.pixels_per_point);
+    let mut uncached_painter = Painter::new();
+    let input = headless_input(options.size);
+
+    let mut ui = workload_ui(workload);
+    let mut report = VertexCacheReport {
+        workload,
+        frames: options.frames,
+        cached: Duration::ZERO,
+        uncached: Duration::ZERO,
+    };
+
+    for frame in 0..options.warmup_frames + options.frames {
+        backend.run(input.clone(), &mut ui);
+        let (primitives, textures_delta) = backend.take_frame();
+        let pixels_per_point = backend.egui_ctx.pixels_per_point();
+
+        // Setting the options drops the cached vertices.
+        uncached_painter.set_options(uncached_painter.options().clone());
+        let mut paint = |painter: &mut Painter| {
+            let start = Instant::now();
+            let canvas = surface.canvas();
+            canvas.clear(Color::TRANSPARENT);
+            painter.paint_and_update_textures(
+                canvas,
+                pixels_per_point,
+                primitives.clone(),
+                textures_delta.clone(),
+            );
+            surface.image_snapshot();
+            start.elapsed()
+        };
+        let cached = paint(&mut backend.painter);
+        let uncached = paint(&mut uncached_painter);
+
+        if frame >= options.warmup_frames {
+            report.cached += cached;
+            report.uncached += uncached;
+        }
+    }
+
+    report
+}
+
+fn workload_ui(workload: Workload) -> Box<dyn FnMut(&Context)> {
+    match workload {
+        Workload::TextLabels => Box::new(|ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.spacing_mut().item_spacing = egui::Vec2::ZERO;
+                ui.columns(50, |columns| {
+                    for (column, ui) in columns.iter_mut().enumerate() {
+                        for row in 0..200 {
+                            ui.small(format!("{column}:{row}"));
+                        }
+                    }
+                });
+            });
+        }),
+        Workload::PlotMesh => Box::new(|ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let rect = ui.max_rect();
+                ui.painter().add(Shape::mesh(plot_mesh(rect, 100_000)));
+            });
+        }),
+        Workload::ClippedWindows => Box::new(|ctx| {
+            for index in 0..50 {
+                egui::Window::new(format!("Window {index}"))
+                    .default_pos(Pos2::new(20.0 * index as f32, 15.0 * index as f32))
+                    .default_size(egui::vec2(240.0, 160.0))
+                    .show(ctx, |ui| {
+                        egui::ScrollArea::both().show(ui, |ui| {
+                            for line in 0..40 {
+                                ui.label(format!("Line {line} of window {index}"));
+                            }
+                        });
+                    });
+            }
+        }),
+        Workload::TextureChurn => {
+            let mut frame = 0u8;
+            let mut texture: Option<TextureHandle> = None;
+            Box::new(move |ctx| {
+                frame = frame.wrapping_add(1);
+                let image = ColorImage::new([256, 256], Color32::from_gray(frame));
+                // Replacing the handle frees the previous texture.
+                let handle =
+                    texture.insert(ctx.load_texture("churn", image, TextureOptions::LINEAR));
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.image((handle.id(), egui::vec2(256.0, 256.0)));
+                });
+            })
+        }
+    }
+}
+
+/// A strip of `quads` colored quads filling `rect`, two triangles each.
+fn plot_mesh(rect: egui::Rect, quads: usize) -> Mesh {
+    let mut mesh = Mesh::default();
+    let step = rect.width() / quads as f32;
+    for index in 0..quads {
+        let x = rect.left() + index as f32 * step;
+        let t = index as f32 / quads as f32;
+        let y = rect.center().y + (t * std::f32::consts::TAU * 20.0).sin() * rect.height() / 3.0;
+        let color = Color32::from_rgb((t * 255.0) as u8, 128, 255 - (t * 255.0) as u8);
+
+        let base = mesh.vertices.len() as u32;
+        for pos in [
+            Pos2::new(x, rect.bottom()),
+            Pos2::new(x + step, rect.bottom()),
+            Pos2::new(x, y),
+            Pos2::new(x + step, y),
+        ] {
+            mesh.vertices.push(Vertex {
+                pos,
+                uv: WHITE_UV,
+                color,
+            });
+        }
+        mesh.indices
+            .extend([base, base + 1, base + 2, base + 2, base + 1, base + 3]);
+    }
+    mesh
+}
@@ -0,0 +1,59 @@
+//! Access to the system clipboard with [arboard](https://crates.io/crates/arboard), for the
+//! backends that don't bring their own.
+
+use egui::{OutputCommand, PlatformOutput};
+
+/// The system clipboard. Clipboard errors are logged and otherwise ignored, so a missing
+/// clipboard (e.g. on a headless machine) only disables copy and paste.
+pub struct Clipboard {
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        let clipboard = arboard::Clipboard::new()
+            .map_err(|err| log::warn!("Failed to open the clipboard: {err}"))
+            .ok();
+        Self { clipboard }
+    }
+
+    /// The text on the clipboard, if there is any.
+    pub fn get(&mut self) -> Option<String> {
+        self.clipboard
+            .as_mut()?
+            .get_text()
+            .map_err(|err| log::debug!("Failed to read the clipboard: {err}"))
+            .ok()
+    }
+
+    pub fn set(&mut self, text: &str) {
+        if let Some(clipboard) = &mut self.clipboard {
+            if let Err(err) = clipboard.set_text(text) {
+                log::warn!("Failed to write to the clipboard: {err}");
+            }
+        }
+    }
+
+    /// Write the text egui copied during the frame to the clipboard.
+    pub fn handle_platform_output(&mut self, platform_output: &PlatformOutput) {
+        for command in &platform_output.commands {
+            if let OutputCommand::CopyText(text) = command {
+                self.set(text);
+            }
+        }
+    }
+
+    /// A paste event with the text on the clipboard, to add to egui's input when the paste
+    /// shortcut is pressed.
+    pub fn paste_event(&mut self) -> Option<egui::Event> {
+        self.get()
+            .filter(|text| !text.is_empty())
+            .map(egui::Event::Paste)
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
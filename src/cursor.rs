@@ -0,0 +1,150 @@
+//! Draw the mouse cursor into the frame, for setups without a hardware cursor like DRM/KMS
+//! kiosks, video capture or remote frames.
+
+use egui::{CursorIcon, Pos2};
+use skia_safe::{Canvas, Color, Matrix, Paint, PaintStyle, Path, PathOp, Point};
+
+/// Draw the cursor shape for `icon` with its hotspot at `pos`, in canvas units.
+///
+/// `scale` is the size of a point in canvas units, usually `pixels_per_point`. Cursor icons
+/// without a dedicated shape are drawn as the default arrow.
+pub fn draw_cursor(canvas: &Canvas, icon: CursorIcon, pos: Pos2, scale: f32) {
+    let Some(shape) = cursor_shape(icon) else {
+        return;
+    };
+
+    canvas.save();
+    canvas.translate((pos.x, pos.y));
+    canvas.scale((scale, scale));
+
+    match shape {
+        Shape::Filled(path) => {
+            canvas.draw_path(&path, &fill_paint(Color::WHITE));
+            canvas.draw_path(&path, &stroke_paint(Color::BLACK, 1.0));
+        }
+        Shape::Lines(path) => {
+            canvas.draw_path(&path, &stroke_paint(Color::WHITE, 3.0));
+            canvas.draw_path(&path, &stroke_paint(Color::BLACK, 1.0));
+        }
+        Shape::NotAllowed => {
+            let mut path = Path::new();
+            path.add_circle((0.0, 0.0), 7.0, None);
+            path.move_to((-5.0, 5.0));
+            path.line_to((5.0, -5.0));
+            canvas.draw_path(&path, &stroke_paint(Color::WHITE, 4.5));
+            canvas.draw_path(&path, &stroke_paint(Color::from_rgb(220, 40, 40), 2.5));
+        }
+    }
+
+    canvas.restore();
+}
+
+enum Shape {
+    /// A white shape with a black outline.
+    Filled(Path),
+    /// Black lines with a white outline.
+    Lines(Path),
+    NotAllowed,
+}
+
+fn cursor_shape(icon: CursorIcon) -> Option<Shape> {
+    let shape = match icon {
+        CursorIcon::None => return None,
+        CursorIcon::Text | CursorIcon::VerticalText => Shape::Lines(i_beam()),
+        CursorIcon::Crosshair | CursorIcon::Cell => Shape::Lines(crosshair()),
+        CursorIcon::NotAllowed | CursorIcon::NoDrop => Shape::NotAllowed,
+        CursorIcon::Move | CursorIcon::AllScroll | CursorIcon::Grab | CursorIcon::Grabbing => {
+            let horizontal = double_arrow(0.0);
+            let vertical = double_arrow(90.0);
+            Shape::Filled(skia_safe::op(&horizontal, &vertical, PathOp::Union)?)
+        }
+        CursorIcon::ResizeHorizontal
+        | CursorIcon::ResizeEast
+        | CursorIcon::ResizeWest
+        | CursorIcon::ResizeColumn => Shape::Filled(double_arrow(0.0)),
+        CursorIcon::ResizeVertical
+        | CursorIcon::ResizeNorth
+        | CursorIcon::ResizeSouth
+        | CursorIcon::ResizeRow => Shape::Filled(double_arrow(90.0)),
+        CursorIcon::ResizeNeSw | CursorIcon::ResizeNorthEast | CursorIcon::ResizeSouthWest => {
+            Shape::Filled(double_arrow(-45.0))
+        }
+        CursorIcon::ResizeNwSe | CursorIcon::ResizeNorthWest | CursorIcon::ResizeSouthEast => {
+            Shape::Filled(double_arrow(45.0))
+        }
+        _ => Shape::Filled(arrow()),
+    };
+    Some(shape)
+}
+
+fn fill_paint(color: Color) -> Paint {
+    let mut paint = Paint::default();
+    paint.set_anti_alias(true);
+    paint.set_color(color);
+    paint
+}
+
+fn stroke_paint(color: Color, width: f32) -> Paint {
+    let mut paint = fill_paint(color);
+    paint.set_style(PaintStyle::Stroke);
+    paint.set_stroke_width(width);
+    paint
+}
+
+/// The default pointer, with its hotspot at the tip.
+fn arrow() -> Path {
+    polygon(&[
+        (0.0, 0.0),
+        (0.0, 17.0),
+        (4.0, 13.0),
+        (7.0, 20.0),
+        (10.0, 19.0),
+        (7.0, 12.0),
+        (12.0, 12.0),
+    ])
+}
+
+/// A double headed arrow centered on the hotspot, rotated by `degrees`.
+fn double_arrow(degrees: f32) -> Path {
+    polygon(&[
+        (-10.0, 0.0),
+        (-5.0, -5.0),
+        (-5.0, -1.5),
+        (5.0, -1.5),
+        (5.0, -5.0),
+        (10.0, 0.0),
+        (5.0, 5.0),
+        (5.0, 1.5),
+        (-5.0, 1.5),
+        (-5.0, 5.0),
+    ])
+    .with_transform(&Matrix::rotate_deg(degrees))
+}
+
+fn i_beam() -> Path {
+    let mut path = Path::new();
+    path.move_to((0.0, -8.0));
+    path.line_to((0.0, 8.0));
+    for y in [-8.0, 8.0] {
+        path.move_to((-3.0, y));
+        path.line_to((3.0, y));
+    }
+    path
+}
+
+fn crosshair() -> Path {
+    let mut path = Path::new();
+    path.move_to((-10.0, 0.0));
+    path.line_to((10.0, 0.0));
+    path.move_to((0.0, -10.0));
+    path.line_to((0.0, 10.0));
+    path
+}
+
+fn polygon(points: &[(f32, f32)]) -> Path {
+    let points = points
+        .iter()
+        .map(|(x, y)| Point::new(*x, *y))
+        .collect::<Vec<_>>();
+    Path::polygon(&points, true, None, None)
+}
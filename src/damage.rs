@@ -0,0 +1,156 @@
+//! Find the area of the frame that changed since the previous frame.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use egui::epaint::ahash::AHashMap;
+use egui::epaint::Primitive;
+use egui::{ClippedPrimitive, Rect, TexturesDelta};
+
+/// Past this many rects of primitives painted below, they are merged into their bounding rect,
+/// so frames with thousands of primitives don't compare every pair of them.
+const MAX_BELOW_RECTS: usize = 64;
+
+/// Tracks the primitives of consecutive frames to report which area of the frame changed.
+///
+/// A primitive counts as unchanged if a primitive with the same content, clip rect and the
+/// same primitives below it was painted in the previous frame. Paint callbacks and texture
+/// updates always damage the area they cover.
+pub struct DamageTracker {
+    previous: AHashMap<u64, Rect>,
+    /// Damage everything on the next update, e.g. because the target was recreated.
+    invalidated: bool,
+}
+
+impl DamageTracker {
+    pub fn new() -> Self {
+        Self {
+            previous: AHashMap::new(),
+            invalidated: true,
+        }
+    }
+
+    /// Treat the whole frame as damaged on the next call to [`Self::update`].
+    pub fn invalidate(&mut self) {
+        self.invalidated = true;
+    }
+
+    /// Compare `primitives` to those of the previous update and return the bounding rect of
+    /// everything that changed, in points. Returns `None` if nothing changed.
+    ///
+    /// `screen_rect` is returned if the whole frame has to be repainted.
+    pub fn update(
+        &mut self,
+        primitives: &[ClippedPrimitive],
+        textures_delta: &TexturesDelta,
+        screen_rect: Rect,
+    ) -> Option<Rect> {
+        let mut damage = Rect::NOTHING;
+        if std::mem::take(&mut self.invalidated) {
+            damage = screen_rect;
+        }
+
+        let updated_textures = textures_delta
+            .set
+            .iter()
+            .map(|(id, _)| *id)
+            .chain(textures_delta.free.iter().copied())
+            .collect::<Vec<_>>();
+
+        let mut current: AHashMap<u64, Rect> = AHashMap::with_capacity(primitives.len());
+        let mut painted: Vec<(u64, Rect)> = Vec::with_capacity(primitives.len());
+
+        for primitive in primitives {
+            let bounds = primitive_bounds(primitive);
+            if !bounds.is_positive() {
+                continue;
+            }
+
+            let mut hasher = DefaultHasher::new();
+            let always_damaged = hash_primitive(primitive, &updated_textures, &mut hasher);
+            // Include everything painted below, so reordered overlapping content is detected.
+            for (below_hash, below_bounds) in &painted {
+                if below_bounds.intersects(bounds) {
+                    below_hash.hash(&mut hasher);
+                }
+            }
+            let hash = hasher.finish();
+
+            if always_damaged || !self.previous.contains_key(&hash) {
+                damage = damage.union(bounds);
+            }
+            if painted.len() >= MAX_BELOW_RECTS {
+                // Primitives above the merged rect depend on everything in it, which only
+                // damages more than necessary.
+                let mut hasher = DefaultHasher::new();
+                let mut merged = Rect::NOTHING;
+                for (below_hash, below_bounds) in painted.drain(..) {
+                    below_hash.hash(&mut hasher);
+                    merged = merged.union(below_bounds);
+                }
+                painted.push((hasher.finish(), merged));
+            }
+            painted.push((hash, bounds));
+            current.insert(hash, bounds);
+        }
+
+        for (hash, bounds) in &self.previous {
+            if !current.contains_key(hash) {
+                damage = damage.union(*bounds);
+            }
+        }
+        self.previous = current;
+
+        let damage = damage.intersect(screen_rect);
+        damage.is_positive().then_some(damage)
+    }
+}
+
+impl Default for DamageTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The area a primitive may paint to, in points.
+fn primitive_bounds(primitive: &ClippedPrimitive) -> Rect {
+    let bounds = match &primitive.primitive {
+        Primitive::Mesh(mesh) => mesh.calc_bounds(),
+        Primitive::Callback(callback) => callback.rect,
+    };
+    bounds.intersect(primitive.clip_rect)
+}
+
+/// Hash the content of a primitive. Returns `true` if the primitive has to be repainted
+/// regardless of its hash.
+fn hash_primitive(
+    primitive: &ClippedPrimitive,
+    updated_textures: &[egui::TextureId],
+    hasher: &mut DefaultHasher,
+) -> bool {
+    hash_rect(primitive.clip_rect, hasher);
+
+    match &primitive.primitive {
+        Primitive::Mesh(mesh) => {
+            mesh.texture_id.hash(hasher);
+            mesh.indices.hash(hasher);
+            for vertex in &mesh.vertices {
+                [vertex.pos.x, vertex.pos.y, vertex.uv.x, vertex.uv.y]
+                    .map(f32::to_bits)
+                    .hash(hasher);
+                vertex.color.hash(hasher);
+            }
+            updated_textures.contains(&mesh.texture_id)
+        }
+        Primitive::Callback(callback) => {
+            hash_rect(callback.rect, hasher);
+            true
+        }
+    }
+}
+
+fn hash_rect(rect: Rect, hasher: &mut DefaultHasher) {
+    [rect.min.x, rect.min.y, rect.max.x, rect.max.y]
+        .map(f32::to_bits)
+        .hash(hasher);
+}
@@ -1,14 +1,30 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use egui::{Context, Pos2, ViewportId, ViewportInfo};
 use egui::viewport::ViewportIdMap;
-use skia_safe::{surfaces, Canvas, Surface};
+use skia_safe::{
+    surfaces, AlphaType, Canvas, ClipOp, Color, ColorSpace, ColorType, IRect, Image, ImageInfo,
+    PictureRecorder, Rect, Surface,
+};
 
-use crate::painter::Painter;
+use crate::damage::DamageTracker;
+use crate::error::EguiSkiaError;
+use crate::painter::{CallbackOrder, Painter};
 
+/// Options of [`rasterize`] and the functions built on it.
+///
+/// Start from [`RasterizeOptions::default`] and the `with_` methods. Options added after
+/// `pixels_per_point` and `frames_before_screenshot` are private, so options added later don't
+/// break the code.
+#[derive(Clone)]
 pub struct RasterizeOptions {
     pub pixels_per_point: f32,
     /// The number of frames to render before a screenshot is taken.
     /// Default is 2, so egui will be able to display windows
     pub frames_before_screenshot: usize,
+    color_type: ColorType,
+    color_space: Option<ColorSpace>,
 }
 
 impl Default for RasterizeOptions {
@@ -16,54 +32,243 @@ impl Default for RasterizeOptions {
         Self {
             pixels_per_point: 1.0,
             frames_before_screenshot: 2,
+            color_type: ColorType::N32,
+            color_space: None,
         }
     }
 }
 
+impl RasterizeOptions {
+    pub fn with_pixels_per_point(mut self, pixels_per_point: f32) -> Self {
+        self.pixels_per_point = pixels_per_point;
+        self
+    }
+
+    pub fn with_frames_before_screenshot(mut self, frames_before_screenshot: usize) -> Self {
+        self.frames_before_screenshot = frames_before_screenshot;
+        self
+    }
+
+    /// The color type of the surface created by [`rasterize`], [`ColorType::N32`] by default.
+    /// Use [`ColorType::RGBA1010102`] for 10-bit output that doesn't band on deep-color panels.
+    ///
+    /// Only the surface gets the deeper format: egui's textures, including the font atlas,
+    /// stay 8-bit RGBA, so gradients drawn from vertex colors gain precision but images don't.
+    pub fn with_color_type(mut self, color_type: ColorType) -> Self {
+        self.color_type = color_type;
+        self
+    }
+
+    /// The color space of the surface created by [`rasterize`], e.g.
+    /// [`ColorSpace::new_srgb`] for an image that is tagged as sRGB. egui's colors are sRGB and
+    /// are converted into a wide-gamut space like Display P3. Untagged (`None`) by default.
+    pub fn with_color_space(mut self, color_space: Option<ColorSpace>) -> Self {
+        self.color_space = color_space;
+        self
+    }
+}
+
 pub fn rasterize(
     size: (i32, i32),
     ui: impl FnMut(&Context),
     options: Option<RasterizeOptions>,
 ) -> Surface {
-    let mut surface = surfaces::raster_n32_premul(size).expect("Failed to create surface");
+    let (color_type, color_space) = options
+        .as_ref()
+        .map(|options| (options.color_type, options.color_space.clone()))
+        .unwrap_or((ColorType::N32, None));
+    let mut surface = surfaces::raster(
+        &ImageInfo::new(size, color_type, AlphaType::Premul, color_space),
+        None,
+        None,
+    )
+    .expect("Failed to create surface");
     draw_onto_surface(&mut surface, ui, options);
     surface
 }
 
 pub fn draw_onto_surface(
     surface: &mut Surface,
+    ui: impl FnMut(&Context),
+    options: Option<RasterizeOptions>,
+) {
+    let size = (surface.width(), surface.height());
+    draw_onto_canvas(surface.canvas(), size, ui, options);
+}
+
+/// Like [`draw_onto_surface`], but paints onto any canvas, e.g. a pdf page or a picture recorder.
+///
+/// `size` is the size of the area egui may use, in canvas units.
+pub fn draw_onto_canvas(
+    canvas: &Canvas,
+    size: (i32, i32),
     mut ui: impl FnMut(&Context),
     options: Option<RasterizeOptions>,
 ) {
     let RasterizeOptions {
         pixels_per_point,
         frames_before_screenshot,
+        ..
     } = options.unwrap_or_default();
     let mut backend = EguiSkia::new();
+    let input = headless_input(size);
+
+    // Set pixels_per_point on the context
+    backend.egui_ctx.set_pixels_per_point(pixels_per_point);
+
+    for _ in 0..frames_before_screenshot {
+        backend.run(input.clone(), &mut ui);
+    }
+    backend.paint(canvas);
+}
+
+/// Run a frame of `ctx`, and paint it onto `surface` with `painter`.
+///
+/// The ui fills the surface at the context's current pixels per point. `painter` keeps the
+/// textures of `ctx`, so pass the same painter for every call with the same context, and
+/// repeated calls only upload what changed. Returns the platform output of the frame.
+pub fn render_ui_onto_surface(
+    surface: &mut Surface,
+    ctx: &Context,
+    painter: &mut Painter,
+    mut run_ui: impl FnMut(&Context),
+) -> egui::PlatformOutput {
+    let pixels_per_point = ctx.pixels_per_point();
+    let screen_rect = egui::Rect::from_min_size(
+        Pos2::ZERO,
+        egui::vec2(surface.width() as f32, surface.height() as f32) / pixels_per_point,
+    );
+
+    let mut input = headless_input((surface.width(), surface.height()));
+    input.screen_rect = Some(screen_rect);
+    if let Some(viewport) = input.viewports.get_mut(&ViewportId::ROOT) {
+        viewport.inner_rect = Some(screen_rect);
+    }
+
+    let egui::FullOutput {
+        platform_output,
+        textures_delta,
+        shapes,
+        pixels_per_point,
+        ..
+    } = ctx.run(input, |ctx| {
+        run_ui(ctx);
+        CallbackOrder::apply(ctx);
+    });
+    let primitives = ctx.tessellate(shapes, pixels_per_point);
+
+    painter.paint_and_update_textures(
+        surface.canvas(),
+        pixels_per_point,
+        primitives,
+        textures_delta,
+    );
+
+    platform_output
+}
+
+/// Read back the pixels of `surface`, converted to `color_type`.
+///
+/// Returns tightly packed rows, e.g. one `u32` per pixel for [`ColorType::RGBA1010102`].
+pub fn read_pixels(surface: &mut Surface, color_type: ColorType) -> Option<Vec<u8>> {
+    let info = ImageInfo::new(
+        (surface.width(), surface.height()),
+        color_type,
+        AlphaType::Premul,
+        surface.image_info().color_space(),
+    );
+    let row_bytes = info.min_row_bytes();
+    let mut pixels = vec![0; row_bytes * info.height() as usize];
+    surface
+        .read_pixels(&info, &mut pixels, row_bytes, (0, 0))
+        .then_some(pixels)
+}
+
+/// Like [`rasterize`], but for images too large for a single surface or for memory, e.g. a
+/// 16k × 16k export of a dashboard.
+///
+/// The ui is recorded once and replayed into surfaces of at most `tile_size` × `tile_size`
+/// pixels. The tiles next to each other are stitched into bands of `tile_size` rows, which are
+/// passed to `on_rows` from top to bottom, with the y coordinate of their first row. Their rows
+/// are tightly packed pixels of the options' color type, ready to be streamed into an image
+/// encoder. Only one band is kept in memory.
+///
+/// Tiles are clamped to `size`, so small images don't allocate a full tile. Fails with
+/// [`EguiSkiaError::ExportFailed`] if a tile couldn't be rendered, after which `on_rows` isn't
+/// called anymore.
+pub fn rasterize_tiled(
+    size: (i32, i32),
+    ui: impl FnMut(&Context),
+    options: Option<RasterizeOptions>,
+    tile_size: i32,
+    mut on_rows: impl FnMut(i32, &[u8]),
+) -> Result<(), EguiSkiaError> {
+    let (color_type, color_space) = options
+        .as_ref()
+        .map(|options| (options.color_type, options.color_space.clone()))
+        .unwrap_or((ColorType::N32, None));
+
+    let mut recorder = PictureRecorder::new();
+    let canvas = recorder.begin_recording(Rect::from_wh(size.0 as f32, size.1 as f32), false);
+    draw_onto_canvas(canvas, size, ui, options);
+    let picture = recorder
+        .finish_recording_as_picture(None)
+        .ok_or(EguiSkiaError::ExportFailed("recording the ui"))?;
+
+    let tile_size = tile_size.max(1);
+    let info = ImageInfo::new(
+        (tile_size.min(size.0).max(1), tile_size.min(size.1).max(1)),
+        color_type,
+        AlphaType::Premul,
+        color_space,
+    );
+    let mut tile = surfaces::raster(&info, None, None)
+        .ok_or(EguiSkiaError::ExportFailed("creating the tile surface"))?;
+    let row_bytes = info.bytes_per_pixel() * size.0.max(0) as usize;
+    let mut band = Vec::new();
+
+    for top in (0..size.1).step_by(tile_size as usize) {
+        let band_height = tile_size.min(size.1 - top);
+        band.clear();
+        band.resize(row_bytes * band_height as usize, 0);
+
+        for left in (0..size.0).step_by(tile_size as usize) {
+            let canvas = tile.canvas();
+            canvas.clear(Color::TRANSPARENT);
+            canvas.save();
+            canvas.translate((-left as f32, -top as f32));
+            canvas.draw_picture(&picture, None, None);
+            canvas.restore();
+
+            // Read the tile straight into its place in the band.
+            let tile_info = info.with_dimensions((tile_size.min(size.0 - left), band_height));
+            let offset = left as usize * info.bytes_per_pixel();
+            if !tile.read_pixels(&tile_info, &mut band[offset..], row_bytes, (0, 0)) {
+                return Err(EguiSkiaError::ExportFailed("reading the tile's pixels"));
+            }
+        }
+        on_rows(top, &band);
+    }
+    Ok(())
+}
 
+/// Input for rendering a ui of `size` without a window.
+pub(crate) fn headless_input(size: (i32, i32)) -> egui::RawInput {
     // Create viewport info with pixels_per_point
     let viewport_id = ViewportId::ROOT;
     let mut viewport_info = ViewportInfo::default();
     viewport_info.inner_rect = Some([
         Pos2::default(),
-        Pos2::new(surface.width() as f32, surface.height() as f32),
+        Pos2::new(size.0 as f32, size.1 as f32),
     ].into());
 
     let mut viewports = ViewportIdMap::default();
     viewports.insert(viewport_id, viewport_info);
 
-    let input = egui::RawInput {
+    egui::RawInput {
         viewports,
         ..Default::default()
-    };
-
-    // Set pixels_per_point on the context
-    backend.egui_ctx.set_pixels_per_point(pixels_per_point);
-
-    for _ in 0..frames_before_screenshot {
-        backend.run(input.clone(), &mut ui);
     }
-    backend.paint(surface.canvas());
 }
 
 /// Convenience wrapper for using [`egui`] from a [`skia`] app.
@@ -72,7 +277,15 @@ pub struct EguiSkia {
     pub painter: Painter,
 
     shapes: Vec<egui::epaint::ClippedShape>,
-    textures_delta: egui::TexturesDelta,
+    pub(crate) textures_delta: egui::TexturesDelta,
+    software_cursor: bool,
+    cursor_icon: egui::CursorIcon,
+    viewport_output: ViewportIdMap<egui::viewport::ViewportOutput>,
+    /// The user data of screenshots requested with [`egui::ViewportCommand::Screenshot`], taken
+    /// when the frame is painted.
+    pending_screenshots: Vec<egui::UserData>,
+    /// Screenshot events for the next call to [`Self::run`].
+    screenshot_events: Vec<egui::Event>,
 }
 
 impl EguiSkia {
@@ -83,42 +296,203 @@ impl EguiSkia {
             painter,
             shapes: Default::default(),
             textures_delta: Default::default(),
+            software_cursor: false,
+            cursor_icon: Default::default(),
+            viewport_output: Default::default(),
+            pending_screenshots: Default::default(),
+            screenshot_events: Default::default(),
         }
     }
 
+    /// Draw the cursor requested by egui into the frame in [`Self::paint`], for platforms
+    /// without a hardware cursor. See [`crate::cursor`].
+    pub fn set_software_cursor(&mut self, enabled: bool) {
+        self.software_cursor = enabled;
+    }
+
     /// Run egui and return the platform output.
     ///
     /// Call [`Self::paint`] later to paint.
     pub fn run(
         &mut self,
-        input: egui::RawInput,
-        run_ui: impl FnMut(&Context),
+        mut input: egui::RawInput,
+        mut run_ui: impl FnMut(&Context),
     ) -> egui::PlatformOutput {
+        input.events.append(&mut self.screenshot_events);
+
         let egui::FullOutput {
             platform_output,
             textures_delta,
             shapes,
             pixels_per_point: _,
-            viewport_output: _,
-        } = self.egui_ctx.run(input, run_ui);
+            viewport_output,
+        } = self.egui_ctx.run(input, |ctx| {
+            run_ui(ctx);
+            CallbackOrder::apply(ctx);
+        });
 
         self.shapes = shapes;
         self.textures_delta.append(textures_delta);
+        self.cursor_icon = platform_output.cursor_icon;
+        if let Some(output) = viewport_output.get(&ViewportId::ROOT) {
+            self.pending_screenshots
+                .extend(output.commands.iter().filter_map(|command| match command {
+                    egui::ViewportCommand::Screenshot(user_data) => Some(user_data.clone()),
+                    _ => None,
+                }));
+        }
+        self.viewport_output = viewport_output;
 
         platform_output
     }
 
-    /// Paint the results of the last call to [`Self::run`].
-    pub fn paint(&mut self, canvas: &Canvas) {
+    /// How long to wait before running the next frame, as requested by the last call to
+    /// [`Self::run`]. [`Duration::MAX`] if egui doesn't need a repaint.
+    pub fn repaint_delay(&self) -> Duration {
+        self.viewport_output
+            .get(&ViewportId::ROOT)
+            .map(|output| output.repaint_delay)
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Tessellate the results of the last call to [`Self::run`].
+    pub(crate) fn take_frame(&mut self) -> (Vec<egui::ClippedPrimitive>, egui::TexturesDelta) {
         let shapes = std::mem::take(&mut self.shapes);
         let textures_delta = std::mem::take(&mut self.textures_delta);
         let clipped_primitives = self.egui_ctx.tessellate(shapes, self.egui_ctx.pixels_per_point());
+        (clipped_primitives, textures_delta)
+    }
+
+    /// Paint the results of the last call to [`Self::run`].
+    pub fn paint(&mut self, canvas: &Canvas) {
+        let (clipped_primitives, textures_delta) = self.take_frame();
         self.painter.paint_and_update_textures(
             canvas,
             self.egui_ctx.pixels_per_point(),
             clipped_primitives,
             textures_delta,
         );
+        self.take_screenshots(canvas);
+
+        if self.software_cursor {
+            let pixels_per_point = self.egui_ctx.pixels_per_point();
+            if let Some(pos) = self.egui_ctx.input(|input| input.pointer.latest_pos()) {
+                crate::cursor::draw_cursor(
+                    canvas,
+                    self.cursor_icon,
+                    (pos.to_vec2() * pixels_per_point).to_pos2(),
+                    pixels_per_point,
+                );
+            }
+        }
+    }
+
+    /// Paint the results of the last call to [`Self::run`] onto `surface` as an overlay, and
+    /// return the frame as an image for compositing over other content.
+    ///
+    /// Paints with [`PainterOptions::overlay`](crate::PainterOptions::overlay) enabled for this
+    /// call only: the surface is cleared to transparent first, and the image only contains the
+    /// ui. The painter's options are left as they were. Its pixels are
+    /// premultiplied as long as the surface is [`AlphaType::Premul`], so composite it with a
+    /// premultiplied blend, e.g. Skia's [`skia_safe::BlendMode::SrcOver`] or
+    /// `ONE, ONE_MINUS_SRC_ALPHA` in gl.
+    pub fn paint_overlay(&mut self, surface: &mut Surface) -> Image {
+        let overlay = self.painter.set_overlay(true);
+        self.paint(surface.canvas());
+        self.painter.set_overlay(overlay);
+        surface.image_snapshot()
+    }
+
+    /// Paint the results of the last call to [`Self::run`], but only repaint the area that
+    /// changed since the previous frame painted with `damage`.
+    ///
+    /// The canvas has to keep the previous frame, e.g. a raster surface that is reused or a
+    /// buffer that isn't swapped. Everything outside of the damaged area is left untouched,
+    /// which saves most of the work for mostly static uis. Call
+    /// [`DamageTracker::invalidate`] when the canvas lost its contents.
+    ///
+    /// The damaged area is cleared to transparent before the ui is painted into it, which also
+    /// wipes anything the host drew there below the ui. Like the painter, the damaged area is
+    /// in pixels of the canvas, regardless of its matrix.
+    ///
+    /// Returns the damaged area in pixels, or `None` if nothing changed. The software cursor
+    /// isn't drawn by this method.
+    pub fn paint_damaged(
+        &mut self,
+        canvas: &Canvas,
+        damage_tracker: &mut DamageTracker,
+    ) -> Option<IRect> {
+        let (primitives, textures_delta) = self.take_frame();
+        let pixels_per_point = self.egui_ctx.pixels_per_point();
+
+        let size = canvas.base_layer_size();
+        let screen_rect = egui::Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(size.width as f32, size.height as f32) / pixels_per_point,
+        );
+        let damage = damage_tracker
+            .update(&primitives, &textures_delta, screen_rect)
+            .map(|rect| {
+                let rect = rect * pixels_per_point;
+                Rect::new(rect.min.x, rect.min.y, rect.max.x, rect.max.y).round_out()
+            });
+
+        let save_count = canvas.save();
+        // The damage is in device pixels.
+        canvas.reset_matrix();
+        match damage {
+            Some(damage) => {
+                canvas.clip_irect(damage, ClipOp::Intersect);
+                canvas.clear(Color::TRANSPARENT);
+            }
+            None => {
+                // Textures still have to be updated and freed.
+                canvas.clip_rect(Rect::new_empty(), ClipOp::Intersect, false);
+            }
+        }
+        self.painter.paint_and_update_textures(
+            canvas,
+            pixels_per_point,
+            primitives,
+            textures_delta,
+        );
+        canvas.restore_to_count(save_count);
+        self.take_screenshots(canvas);
+
+        damage
+    }
+
+    /// Read back the frame painted onto `canvas` for the requested screenshots, which egui
+    /// receives as [`egui::Event::Screenshot`] in the next frame.
+    ///
+    /// Canvases that can't be read back, e.g. of a picture recorder, don't deliver screenshots.
+    fn take_screenshots(&mut self, canvas: &Canvas) {
+        if self.pending_screenshots.is_empty() {
+            return;
+        }
+
+        let size = canvas.base_layer_size();
+        let info = ImageInfo::new(size, ColorType::RGBA8888, AlphaType::Unpremul, None);
+        let row_bytes = info.min_row_bytes();
+        let mut pixels = vec![0; row_bytes * size.height as usize];
+        if !canvas.read_pixels(&info, &mut pixels, row_bytes, (0, 0)) {
+            self.pending_screenshots.clear();
+            return;
+        }
+
+        let image = Arc::new(egui::ColorImage::from_rgba_unmultiplied(
+            [size.width as usize, size.height as usize],
+            &pixels,
+        ));
+        for user_data in self.pending_screenshots.drain(..) {
+            self.screenshot_events.push(egui::Event::Screenshot {
+                viewport_id: ViewportId::ROOT,
+                user_data,
+                image: image.clone(),
+            });
+        }
+        // egui only sees the event in the next frame.
+        self.egui_ctx.request_repaint();
     }
 }
 
@@ -1,26 +1,170 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use egui::Context;
+use egui::epaint::ahash::AHashMap;
+use egui::{Context, ViewportId};
 pub use egui_winit;
-use egui_winit::winit::event_loop::EventLoopWindowTarget;
-use egui_winit::winit::window::Window;
+use egui_winit::winit::event::{TouchPhase, WindowEvent};
+use egui_winit::winit::event_loop::ControlFlow;
+#[cfg(feature = "accesskit")]
+use egui_winit::winit::event_loop::{ActiveEventLoop, EventLoopProxy};
+use egui_winit::winit::window::{Window, WindowId};
 use egui_winit::EventResponse;
-use skia_safe::Canvas;
+use skia_safe::{Canvas, ColorSpace};
 
-use crate::EguiSkia;
+use crate::viewport::ViewportPainter;
+use crate::{EguiSkia, EguiSkiaError};
 
+type ColorSpaceProvider = Box<dyn Fn(&Window) -> Option<ColorSpace>>;
+
+/// Glue between winit and egui_skia: owns the egui [`Context`] (in [`EguiSkia`]), converts
+/// winit events to egui input, runs the ui, handles its platform output and paints it.
 pub struct EguiSkiaWinit {
     pub egui_skia: EguiSkia,
     pub egui_winit: egui_winit::State,
+
+    color_space_provider: Option<ColorSpaceProvider>,
+    color_space: Option<ColorSpace>,
+    color_space_outdated: bool,
+    color_space_changed: bool,
+    rotation_gesture: Option<RotationGesture>,
+    /// When egui wants to run again, as requested by the last call to [`Self::run`].
+    repaint_deadline: Option<Instant>,
+}
+
+/// A trackpad rotation, replayed to egui as two touches circling the pointer.
+struct RotationGesture {
+    center: egui::Pos2,
+    /// The accumulated rotation in radians, clockwise on screen.
+    angle: f32,
+}
+
+impl RotationGesture {
+    /// The distance of the synthesized touches from the center, in points.
+    const RADIUS: f32 = 50.0;
+    /// Not used by any real touch device.
+    const DEVICE_ID: egui::TouchDeviceId = egui::TouchDeviceId(u64::MAX);
+
+    fn touch_events(&self, phase: egui::TouchPhase) -> [egui::Event; 2] {
+        let offset = egui::Vec2::angled(self.angle) * Self::RADIUS;
+        [(0, self.center + offset), (1, self.center - offset)].map(|(id, pos)| {
+            egui::Event::Touch {
+                device_id: Self::DEVICE_ID,
+                id: egui::TouchId(id),
+                phase,
+                pos,
+                force: None,
+            }
+        })
+    }
 }
 
 impl EguiSkiaWinit {
-    pub fn new<T>(el: &EventLoopWindowTarget<T>) -> Self {
-        let egui_winit = egui_winit::State::new(el);
+    pub fn new(window: &Window) -> Self {
+        let egui_skia = EguiSkia::new();
+        let egui_winit = egui_winit::State::new(
+            egui_skia.egui_ctx.clone(),
+            ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            None,
+        );
 
         Self {
             egui_winit,
-            egui_skia: EguiSkia::new(),
+            egui_skia,
+            #[cfg(target_os = "macos")]
+            color_space_provider: Some(Box::new(display_color_space)),
+            #[cfg(not(target_os = "macos"))]
+            color_space_provider: None,
+            color_space: None,
+            color_space_outdated: true,
+            color_space_changed: false,
+            rotation_gesture: None,
+            repaint_deadline: Some(Instant::now()),
+        }
+    }
+
+    /// Expose the ui to the platform's accessibility apis, so screen readers can read and
+    /// operate it.
+    ///
+    /// Call this before the window is shown. AccessKit sends its events through
+    /// `event_loop_proxy`, pass them on to [`Self::on_accesskit_event`].
+    #[cfg(feature = "accesskit")]
+    pub fn init_accesskit<T: From<egui_winit::accesskit_winit::Event> + Send>(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window: &Window,
+        event_loop_proxy: EventLoopProxy<T>,
+    ) {
+        self.egui_winit
+            .init_accesskit(event_loop, window, event_loop_proxy);
+    }
+
+    /// Handle an AccessKit event of this window: a screen reader starting or stopping, or
+    /// asking to perform an action like clicking a button. Returns `true` if egui should
+    /// repaint.
+    #[cfg(feature = "accesskit")]
+    pub fn on_accesskit_event(&mut self, event: &egui_winit::accesskit_winit::WindowEvent) -> bool {
+        use egui_winit::accesskit_winit::WindowEvent;
+
+        match event {
+            WindowEvent::InitialTreeRequested => {
+                self.egui_skia.egui_ctx.enable_accesskit();
+            }
+            WindowEvent::ActionRequested(request) => {
+                self.egui_winit.on_accesskit_action_request(request.clone());
+            }
+            WindowEvent::AccessibilityDeactivated => {
+                self.egui_skia.egui_ctx.disable_accesskit();
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Set the function that queries the color profile of the display a window is on.
+    ///
+    /// winit doesn't expose display color profiles. On macOS [`display_color_space`] is used
+    /// by default, elsewhere this has to be implemented with the platform apis where they are
+    /// available. The provider is queried again whenever the window moves or its scale factor
+    /// changes.
+    pub fn set_color_space_provider(
+        &mut self,
+        provider: impl Fn(&Window) -> Option<ColorSpace> + 'static,
+    ) {
+        self.color_space_provider = Some(Box::new(provider));
+        self.color_space_outdated = true;
+    }
+
+    /// The color space of the display the window is currently on, as reported by the
+    /// provider set with [`Self::set_color_space_provider`].
+    ///
+    /// Create the window's surface with this color space, so colors stay consistent across
+    /// monitors, e.g. with `set_color_space` of [`RenderBackend`](crate::backends::RenderBackend),
+    /// `EguiSkiaGlWindow` or `MetalBackend`.
+    pub fn color_space(&self) -> Option<&ColorSpace> {
+        self.color_space.as_ref()
+    }
+
+    /// Returns `true` once after [`Self::color_space`] changed, e.g. because the window moved
+    /// to another monitor. The surface should be recreated when this happens.
+    pub fn take_color_space_changed(&mut self) -> bool {
+        std::mem::take(&mut self.color_space_changed)
+    }
+
+    fn update_color_space(&mut self, window: &Window) {
+        if !std::mem::take(&mut self.color_space_outdated) {
+            return;
+        }
+        let Some(provider) = &self.color_space_provider else {
+            return;
+        };
+
+        let color_space = provider(window);
+        if color_space != self.color_space {
+            self.color_space = color_space;
+            self.color_space_changed = true;
         }
     }
 
@@ -30,25 +174,319 @@ impl EguiSkiaWinit {
     /// and only when this returns `false` pass on the events to your game.
     ///
     /// Note that egui uses `tab` to move focus between elements, so this will always return `true` for tabs.
-    pub fn on_event(&mut self, event: &egui_winit::winit::event::WindowEvent<'_>) -> EventResponse {
-        self.egui_winit.on_event(&self.egui_skia.egui_ctx, event)
+    pub fn on_event(&mut self, window: &Window, event: &WindowEvent) -> EventResponse {
+        if matches!(
+            event,
+            WindowEvent::Moved(_) | WindowEvent::ScaleFactorChanged { .. }
+        ) {
+            self.color_space_outdated = true;
+        }
+
+        match event {
+            WindowEvent::RotationGesture { delta, phase, .. } => {
+                let events = self.rotation_events(*delta, *phase);
+                self.push_gesture_events(events)
+            }
+            // Pinch gestures are translated into zoom events by egui-winit. Pixel deltas,
+            // including the momentum phase of trackpad scrolling, are passed on as smooth
+            // scroll events.
+            _ => self.egui_winit.on_window_event(window, event),
+        }
+    }
+
+    fn push_gesture_events(
+        &mut self,
+        events: impl IntoIterator<Item = egui::Event>,
+    ) -> EventResponse {
+        self.egui_winit.egui_input_mut().events.extend(events);
+        EventResponse {
+            consumed: self.egui_skia.egui_ctx.wants_pointer_input(),
+            repaint: true,
+        }
+    }
+
+    /// Translate a trackpad rotation into touches, so egui reports it in
+    /// [`egui::MultiTouchInfo::rotation_delta`].
+    fn rotation_events(&mut self, delta: f32, phase: TouchPhase) -> Vec<egui::Event> {
+        match phase {
+            TouchPhase::Started => {
+                let center = self
+                    .egui_skia
+                    .egui_ctx
+                    .input(|input| input.pointer.latest_pos())
+                    .unwrap_or_else(|| self.egui_skia.egui_ctx.screen_rect().center());
+                let gesture = RotationGesture { center, angle: 0.0 };
+                let events = gesture.touch_events(egui::TouchPhase::Start).to_vec();
+                self.rotation_gesture = Some(gesture);
+                events
+            }
+            TouchPhase::Moved => match &mut self.rotation_gesture {
+                Some(gesture) => {
+                    // winit reports counterclockwise rotation as positive.
+                    gesture.angle -= delta.to_radians();
+                    gesture.touch_events(egui::TouchPhase::Move).to_vec()
+                }
+                None => Vec::new(),
+            },
+            TouchPhase::Ended | TouchPhase::Cancelled => match self.rotation_gesture.take() {
+                Some(gesture) => {
+                    let phase = if phase == TouchPhase::Ended {
+                        egui::TouchPhase::End
+                    } else {
+                        egui::TouchPhase::Cancel
+                    };
+                    gesture.touch_events(phase).to_vec()
+                }
+                None => Vec::new(),
+            },
+        }
     }
 
     /// Returns a duration after witch egui should repaint.
     ///
     /// Call [`Self::paint`] later to paint.
     pub fn run(&mut self, window: &Window, run_ui: impl FnMut(&Context)) -> Duration {
+        self.update_color_space(window);
+
         let raw_input = self.egui_winit.take_egui_input(window);
 
-        let (repaint_after, platform_output) = self.egui_skia.run(raw_input, run_ui);
+        let platform_output = self.egui_skia.run(raw_input, run_ui);
 
         self.egui_winit
-            .handle_platform_output(window, &self.egui_skia.egui_ctx, platform_output);
-        repaint_after
+            .handle_platform_output(window, platform_output);
+
+        let repaint_delay = self.egui_skia.repaint_delay();
+        self.repaint_deadline = Instant::now().checked_add(repaint_delay);
+        repaint_delay
+    }
+
+    /// Returns `true` once the time egui asked to be repainted after has passed. Check this
+    /// when the event loop wakes up, and request a redraw of the window if it returns `true`.
+    pub fn needs_repaint(&self) -> bool {
+        self.repaint_deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+    }
+
+    /// The control flow that keeps the event loop asleep until the next event or until egui
+    /// wants to be repainted, whichever comes first. Set it before the event loop waits, so
+    /// idle uis don't use any cpu.
+    ///
+    /// Repaints requested from other threads don't wake the event loop by themselves, send a
+    /// user event from [`Context::set_request_repaint_callback`] for them.
+    pub fn control_flow(&self) -> ControlFlow {
+        control_flow(self.repaint_deadline)
     }
 
     /// Paint the results of the last call to [`Self::run`].
-    pub fn paint(&mut self, canvas: &mut Canvas) {
+    pub fn paint(&mut self, canvas: &Canvas) {
         self.egui_skia.paint(canvas);
     }
 }
+
+struct WinitWindow {
+    viewport_id: ViewportId,
+    egui_winit: egui_winit::State,
+    shapes: Vec<egui::epaint::ClippedShape>,
+    textures_delta: egui::TexturesDelta,
+    pixels_per_point: f32,
+    repaint_deadline: Option<Instant>,
+}
+
+/// Several winit windows that show the same egui [`Context`], e.g. a main window and
+/// detached tool palettes.
+///
+/// Every window is an egui viewport with its own input and its own [`Painter`](crate::Painter)
+/// in a [`ViewportPainter`], so the windows can be painted onto surfaces of different gpu
+/// contexts. Textures are shared: egui's texture updates are forwarded to all windows.
+///
+/// Add the main window as [`ViewportId::ROOT`] and the others with ids of their own, e.g.
+/// `ViewportId::from_hash_of("palette")`. Pass every window event to [`Self::on_event`], and
+/// run and paint each window when it is redrawn.
+pub struct EguiSkiaWinitWindows {
+    pub egui_ctx: Context,
+    pub painters: ViewportPainter,
+    windows: AHashMap<WindowId, WinitWindow>,
+}
+
+impl EguiSkiaWinitWindows {
+    pub fn new() -> Self {
+        Self {
+            egui_ctx: Context::default(),
+            painters: ViewportPainter::new(),
+            windows: AHashMap::new(),
+        }
+    }
+
+    /// Show `viewport_id` in `window`.
+    pub fn add_window(&mut self, window: &Window, viewport_id: ViewportId) {
+        let egui_winit = egui_winit::State::new(
+            self.egui_ctx.clone(),
+            viewport_id,
+            window,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            None,
+        );
+        self.windows.insert(
+            window.id(),
+            WinitWindow {
+                viewport_id,
+                egui_winit,
+                shapes: Vec::new(),
+                textures_delta: Default::default(),
+                pixels_per_point: window.scale_factor() as f32,
+                repaint_deadline: Some(Instant::now()),
+            },
+        );
+    }
+
+    /// Stop showing anything in the window `window_id`, e.g. after it was closed, and free its
+    /// painter.
+    pub fn remove_window(&mut self, window_id: WindowId) {
+        self.windows.remove(&window_id);
+        self.painters
+            .retain_viewports(self.windows.values().map(|window| window.viewport_id));
+    }
+
+    /// The viewport shown in the window `window_id`.
+    pub fn viewport_id(&self, window_id: WindowId) -> Option<ViewportId> {
+        self.windows
+            .get(&window_id)
+            .map(|window| window.viewport_id)
+    }
+
+    /// Translate an event of `window` into egui input of its viewport. Returns `None` for
+    /// windows that weren't added, see [`EguiSkiaWinit::on_event`] otherwise.
+    pub fn on_event(&mut self, window: &Window, event: &WindowEvent) -> Option<EventResponse> {
+        let state = self.windows.get_mut(&window.id())?;
+        Some(state.egui_winit.on_window_event(window, event))
+    }
+
+    /// Run the ui of `window`'s viewport and return a duration after which it should be
+    /// repainted.
+    ///
+    /// Call [`Self::paint`] later to paint.
+    pub fn run(&mut self, window: &Window, mut run_ui: impl FnMut(&Context)) -> Duration {
+        let Some(state) = self.windows.get_mut(&window.id()) else {
+            return Duration::MAX;
+        };
+
+        let mut raw_input = state.egui_winit.take_egui_input(window);
+        raw_input.viewport_id = state.viewport_id;
+
+        let egui::FullOutput {
+            platform_output,
+            textures_delta,
+            shapes,
+            pixels_per_point,
+            viewport_output,
+        } = self.egui_ctx.run(raw_input, |ctx| {
+            run_ui(ctx);
+            crate::CallbackOrder::apply(ctx);
+        });
+
+        state.shapes = shapes;
+        state.textures_delta.append(textures_delta);
+        state.pixels_per_point = pixels_per_point;
+        let repaint_delay = viewport_output
+            .get(&state.viewport_id)
+            .map(|output| output.repaint_delay)
+            .unwrap_or(Duration::MAX);
+        state.repaint_deadline = Instant::now().checked_add(repaint_delay);
+        state
+            .egui_winit
+            .handle_platform_output(window, platform_output);
+
+        repaint_delay
+    }
+
+    /// Paint the results of the last call to [`Self::run`] for the window `window_id` onto
+    /// `canvas`, the canvas of its surface.
+    pub fn paint(&mut self, window_id: WindowId, canvas: &Canvas) -> Result<(), EguiSkiaError> {
+        let Some(state) = self.windows.get_mut(&window_id) else {
+            return Ok(());
+        };
+
+        let shapes = std::mem::take(&mut state.shapes);
+        let textures_delta = std::mem::take(&mut state.textures_delta);
+        let primitives = self.egui_ctx.tessellate(shapes, state.pixels_per_point);
+        self.painters.paint(
+            state.viewport_id,
+            canvas,
+            state.pixels_per_point,
+            primitives,
+            textures_delta,
+        )
+    }
+
+    /// The windows whose repaint egui asked for is due, see [`EguiSkiaWinit::needs_repaint`].
+    pub fn windows_to_repaint(&self) -> impl Iterator<Item = WindowId> + '_ {
+        let now = Instant::now();
+        self.windows.iter().filter_map(move |(id, window)| {
+            window
+                .repaint_deadline
+                .is_some_and(|deadline| deadline <= now)
+                .then_some(*id)
+        })
+    }
+
+    /// The control flow that keeps the event loop asleep until the next event or until one of
+    /// the windows needs to be repainted, see [`EguiSkiaWinit::control_flow`].
+    pub fn control_flow(&self) -> ControlFlow {
+        control_flow(
+            self.windows
+                .values()
+                .filter_map(|window| window.repaint_deadline)
+                .min(),
+        )
+    }
+}
+
+impl Default for EguiSkiaWinitWindows {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The color space of the display `window` is on: Display P3 on wide-gamut displays, sRGB
+/// otherwise. The default color space provider of [`EguiSkiaWinit`] on macOS.
+///
+/// AppKit only reports which gamut a display covers here, calibrations within a gamut aren't
+/// reflected.
+#[cfg(target_os = "macos")]
+pub fn display_color_space(window: &Window) -> Option<ColorSpace> {
+    use egui_winit::winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+    use objc::runtime::{Object, BOOL, YES};
+    use objc::{msg_send, sel, sel_impl};
+    use skia_safe::{named_gamut, named_transfer_fn};
+
+    /// `NSDisplayGamutP3`.
+    const DISPLAY_GAMUT_P3: isize = 2;
+
+    let RawWindowHandle::AppKit(handle) = window.window_handle().ok()?.as_raw() else {
+        return None;
+    };
+    let view = handle.ns_view.as_ptr() as *mut Object;
+    let wide_gamut = unsafe {
+        let ns_window: *mut Object = msg_send![view, window];
+        let screen: *mut Object = msg_send![ns_window, screen];
+        if screen.is_null() {
+            return None;
+        }
+        let wide_gamut: BOOL = msg_send![screen, canRepresentDisplayGamut: DISPLAY_GAMUT_P3];
+        wide_gamut == YES
+    };
+
+    Some(if wide_gamut {
+        ColorSpace::new_rgb(&named_transfer_fn::SRGB, &named_gamut::DISPLAY_P3)
+    } else {
+        ColorSpace::new_srgb()
+    })
+}
+
+fn control_flow(repaint_deadline: Option<Instant>) -> ControlFlow {
+    match repaint_deadline {
+        Some(deadline) => ControlFlow::WaitUntil(deadline),
+        None => ControlFlow::Wait,
+    }
+}
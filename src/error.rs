@@ -0,0 +1,57 @@
+use std::fmt;
+use std::sync::Arc;
+
+use egui::TextureId;
+
+/// Errors reported by [`Painter::try_paint_and_update_textures`](crate::Painter::try_paint_and_update_textures)
+/// and the export functions like [`rasterize_tiled`](crate::rasterize_tiled).
+///
+/// None of the painter's errors are fatal: the painter skips the texture or callback that
+/// failed and paints the rest of the frame.
+#[derive(Clone, Debug)]
+pub enum EguiSkiaError {
+    /// Skia couldn't create an image from the pixels of a texture delta, e.g. because its size
+    /// doesn't match its pixel data.
+    UnsupportedImage(TextureId),
+    /// Skia couldn't create a surface for compositing the patches of a texture.
+    SurfaceCreationFailed(TextureId),
+    /// Skia couldn't create the shader sampling a texture.
+    ShaderCreationFailed(TextureId),
+    /// A partial update was sent for a texture the painter doesn't know.
+    UnknownTexture(TextureId),
+    /// A paint callback wasn't created with [`EguiSkiaPaintCallback`](crate::EguiSkiaPaintCallback).
+    BadCallback,
+    /// A fallible paint callback returned an error, see
+    /// [`EguiSkiaPaintCallback::try_new`](crate::EguiSkiaPaintCallback::try_new).
+    CallbackFailed(Arc<dyn std::error::Error + Send + Sync>),
+    /// An export couldn't be completed, e.g. because Skia couldn't create its surface. The
+    /// reason says which step failed.
+    ExportFailed(&'static str),
+}
+
+impl fmt::Display for EguiSkiaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedImage(id) => write!(f, "unsupported image data for texture {id:?}"),
+            Self::SurfaceCreationFailed(id) => {
+                write!(f, "failed to create a surface for texture {id:?}")
+            }
+            Self::ShaderCreationFailed(id) => {
+                write!(f, "failed to create a shader for texture {id:?}")
+            }
+            Self::UnknownTexture(id) => write!(f, "partial update of unknown texture {id:?}"),
+            Self::BadCallback => write!(f, "paint callback isn't an EguiSkiaPaintCallback"),
+            Self::CallbackFailed(err) => write!(f, "paint callback failed: {err}"),
+            Self::ExportFailed(reason) => write!(f, "export failed: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for EguiSkiaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::CallbackFailed(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
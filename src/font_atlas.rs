@@ -0,0 +1,83 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use egui::epaint::ahash::AHashMap;
+use egui::epaint::ImageDelta;
+use egui::ImageData;
+use skia_safe::Image;
+
+/// A font atlas image cache shared between painters, so apps running several egui
+/// [`Context`](egui::Context)s with the same fonts only keep one copy of the glyph atlas.
+///
+/// Contexts with identical fonts and glyph usage send identical font texture deltas. The
+/// painters identify the resulting atlas by the chain of deltas it was built from, and reuse
+/// the image another painter already built. Images are kept as long as a painter uses them.
+///
+/// Attach it with [`Painter::set_shared_font_atlas`](crate::Painter::set_shared_font_atlas).
+/// Painters on different threads can share an atlas.
+#[derive(Clone, Default)]
+pub struct SharedFontAtlas {
+    images: Arc<Mutex<AHashMap<u64, (Image, usize)>>>,
+}
+
+impl SharedFontAtlas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached images. A painter that panicked while holding the lock left them consistent,
+    /// every change is a single map or counter update.
+    fn images(&self) -> MutexGuard<'_, AHashMap<u64, (Image, usize)>> {
+        self.images.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The key of the atlas built by applying `deltas` to the atlas identified by `previous`.
+    ///
+    /// Returns `None` if the deltas patch an atlas whose key isn't known.
+    pub(crate) fn key(previous: Option<u64>, deltas: &[&ImageDelta]) -> Option<u64> {
+        let (mut key, patches) = match deltas.iter().rposition(|delta| delta.pos.is_none()) {
+            Some(index) => (None, &deltas[index..]),
+            None => (Some(previous?), deltas),
+        };
+
+        for delta in patches {
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            delta.pos.hash(&mut hasher);
+            delta.options.hash(&mut hasher);
+            match &delta.image {
+                ImageData::Color(image) => {
+                    image.size.hash(&mut hasher);
+                    image.pixels.hash(&mut hasher);
+                }
+            }
+            key = Some(hasher.finish());
+        }
+        key
+    }
+
+    /// The image stored under `key`, counting the caller as a user.
+    pub(crate) fn acquire(&self, key: u64) -> Option<Image> {
+        let mut images = self.images();
+        let (image, users) = images.get_mut(&key)?;
+        *users += 1;
+        Some(image.clone())
+    }
+
+    /// Store `image` under `key`, with the caller as its only user.
+    pub(crate) fn insert(&self, key: u64, image: &Image) {
+        self.images().insert(key, (image.clone(), 1));
+    }
+
+    /// Stop using the image stored under `key`, dropping it if it has no users left.
+    pub(crate) fn release(&self, key: u64) {
+        let mut images = self.images();
+        if let Some((_, users)) = images.get_mut(&key) {
+            *users -= 1;
+            if *users == 0 {
+                images.remove(&key);
+            }
+        }
+    }
+}
@@ -0,0 +1,439 @@
+//! Collect egui input in applications that run their own event loop, without egui-winit.
+//!
+//! Report events to [`InputState`] in physical pixels and take the collected [`RawInput`] once
+//! per frame. With the `winit-input` feature, [`InputState::on_winit_event`] translates winit's
+//! window events directly.
+
+use std::path::PathBuf;
+use std::time::Instant;
+
+use egui::{
+    Key, Modifiers, MouseWheelUnit, PointerButton, Pos2, RawInput, Rect, TouchDeviceId, TouchId,
+    TouchPhase, Vec2, ViewportId,
+};
+
+/// Collects egui input from pointer, keyboard and touch events.
+///
+/// The first touch additionally moves the pointer and presses the primary button, so regular
+/// widgets react to taps and drags.
+pub struct InputState {
+    raw_input: RawInput,
+    pixels_per_point: f32,
+    /// The size of the window in pixels.
+    size: Vec2,
+    pointer_pos: Option<Pos2>,
+    modifiers: Modifiers,
+    focused: bool,
+    primary_touch: Option<(TouchDeviceId, TouchId)>,
+    start_time: Instant,
+    #[cfg(feature = "winit-input")]
+    cursor_icon: Option<egui::CursorIcon>,
+    #[cfg(feature = "clipboard")]
+    clipboard: crate::clipboard::Clipboard,
+}
+
+impl InputState {
+    /// `size` is the size of the window in pixels.
+    pub fn new(size: [u32; 2], pixels_per_point: f32) -> Self {
+        Self {
+            raw_input: RawInput::default(),
+            pixels_per_point,
+            size: egui::vec2(size[0] as f32, size[1] as f32),
+            pointer_pos: None,
+            modifiers: Modifiers::default(),
+            focused: true,
+            primary_touch: None,
+            start_time: Instant::now(),
+            #[cfg(feature = "winit-input")]
+            cursor_icon: None,
+            #[cfg(feature = "clipboard")]
+            clipboard: crate::clipboard::Clipboard::new(),
+        }
+    }
+
+    pub fn pixels_per_point(&self) -> f32 {
+        self.pixels_per_point
+    }
+
+    pub fn set_pixels_per_point(&mut self, pixels_per_point: f32) {
+        self.pixels_per_point = pixels_per_point;
+    }
+
+    /// The size of the window in pixels.
+    pub fn set_size(&mut self, size: [u32; 2]) {
+        self.size = egui::vec2(size[0] as f32, size[1] as f32);
+    }
+
+    /// The pointer moved to `pos`, in pixels relative to the top left corner of the window.
+    pub fn on_pointer_moved(&mut self, pos: (f32, f32)) {
+        let pos = Pos2::new(pos.0, pos.1) / self.pixels_per_point;
+        self.pointer_pos = Some(pos);
+        self.raw_input.events.push(egui::Event::PointerMoved(pos));
+    }
+
+    /// The pointer left the window.
+    pub fn on_pointer_left(&mut self) {
+        self.pointer_pos = None;
+        self.raw_input.events.push(egui::Event::PointerGone);
+    }
+
+    /// A button was pressed or released at the last position of the pointer.
+    pub fn on_pointer_button(&mut self, button: PointerButton, pressed: bool) {
+        let Some(pos) = self.pointer_pos else {
+            return;
+        };
+        self.raw_input.events.push(egui::Event::PointerButton {
+            pos,
+            button,
+            pressed,
+            modifiers: self.modifiers,
+        });
+    }
+
+    /// A scroll of the mouse wheel or the trackpad. A `delta` in [`MouseWheelUnit::Point`] is
+    /// given in pixels, like all other positions.
+    pub fn on_wheel(&mut self, unit: MouseWheelUnit, delta: Vec2) {
+        let delta = match unit {
+            MouseWheelUnit::Point => delta / self.pixels_per_point,
+            MouseWheelUnit::Line | MouseWheelUnit::Page => delta,
+        };
+        self.raw_input.events.push(egui::Event::MouseWheel {
+            unit,
+            delta,
+            modifiers: self.modifiers,
+        });
+    }
+
+    pub fn set_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+    }
+
+    /// A key was pressed or released. `key` is the key in the current keyboard layout,
+    /// `physical_key` the key at its position on a US keyboard.
+    ///
+    /// The copy and cut shortcuts are reported as [`egui::Event::Copy`] and
+    /// [`egui::Event::Cut`]. The paste shortcut reads the system clipboard with the `clipboard`
+    /// feature, without it push an [`egui::Event::Paste`] with [`Self::push_event`].
+    pub fn on_key(&mut self, key: Key, physical_key: Option<Key>, pressed: bool, repeat: bool) {
+        let events = &mut self.raw_input.events;
+        if pressed && self.modifiers.command {
+            match key {
+                Key::C => events.push(egui::Event::Copy),
+                Key::X => events.push(egui::Event::Cut),
+                #[cfg(feature = "clipboard")]
+                Key::V => events.extend(self.clipboard.paste_event()),
+                _ => {}
+            }
+        }
+        events.push(egui::Event::Key {
+            key,
+            physical_key,
+            pressed,
+            repeat,
+            modifiers: self.modifiers,
+        });
+    }
+
+    /// Text typed or committed by an input method. Control characters are ignored, they are
+    /// reported as keys.
+    pub fn on_text(&mut self, text: &str) {
+        let text: String = text.chars().filter(|chr| !chr.is_control()).collect();
+        if !text.is_empty() {
+            self.raw_input.events.push(egui::Event::Text(text));
+        }
+    }
+
+    /// A touch with the id `id` of the device `device_id` at `pos` in pixels. `force` is the
+    /// normalized pressure between `0.0` and `1.0`, if the device reports it.
+    pub fn on_touch(
+        &mut self,
+        device_id: u64,
+        id: u64,
+        phase: TouchPhase,
+        pos: (f32, f32),
+        force: Option<f32>,
+    ) {
+        let pos = Pos2::new(pos.0, pos.1) / self.pixels_per_point;
+        let touch = (TouchDeviceId(device_id), TouchId(id));
+        let events = &mut self.raw_input.events;
+
+        events.push(egui::Event::Touch {
+            device_id: touch.0,
+            id: touch.1,
+            phase,
+            pos,
+            force,
+        });
+
+        if phase == TouchPhase::Start && self.primary_touch.is_none() {
+            self.primary_touch = Some(touch);
+        }
+        if self.primary_touch != Some(touch) {
+            return;
+        }
+
+        match phase {
+            TouchPhase::Start => {
+                self.pointer_pos = Some(pos);
+                events.push(egui::Event::PointerMoved(pos));
+                events.push(egui::Event::PointerButton {
+                    pos,
+                    button: PointerButton::Primary,
+                    pressed: true,
+                    modifiers: self.modifiers,
+                });
+            }
+            TouchPhase::Move => {
+                self.pointer_pos = Some(pos);
+                events.push(egui::Event::PointerMoved(pos));
+            }
+            TouchPhase::End => {
+                self.primary_touch = None;
+                self.pointer_pos = None;
+                events.push(egui::Event::PointerButton {
+                    pos,
+                    button: PointerButton::Primary,
+                    pressed: false,
+                    modifiers: self.modifiers,
+                });
+                events.push(egui::Event::PointerGone);
+            }
+            TouchPhase::Cancel => {
+                self.primary_touch = None;
+                self.pointer_pos = None;
+                events.push(egui::Event::PointerGone);
+            }
+        }
+    }
+
+    pub fn on_focus(&mut self, focused: bool) {
+        self.focused = focused;
+        self.raw_input
+            .events
+            .push(egui::Event::WindowFocused(focused));
+    }
+
+    /// A file is dragged over the window. Called once for every file when dragging several.
+    pub fn on_file_hovered(&mut self, path: PathBuf) {
+        self.raw_input.hovered_files.push(egui::HoveredFile {
+            path: Some(path),
+            ..Default::default()
+        });
+    }
+
+    /// The files dragged over the window left it without being dropped.
+    pub fn on_file_hover_cancelled(&mut self) {
+        self.raw_input.hovered_files.clear();
+    }
+
+    /// A file was dropped onto the window. Called once for every file when dropping several.
+    pub fn on_file_dropped(&mut self, path: PathBuf) {
+        self.raw_input.hovered_files.clear();
+        self.raw_input.dropped_files.push(egui::DroppedFile {
+            path: Some(path),
+            ..Default::default()
+        });
+    }
+
+    /// Add an event that has no dedicated method, e.g. [`egui::Event::Paste`].
+    pub fn push_event(&mut self, event: egui::Event) {
+        self.raw_input.events.push(event);
+    }
+
+    /// The input collected since the last call, for [`EguiSkia::run`](crate::EguiSkia::run).
+    pub fn take_egui_input(&mut self) -> RawInput {
+        let screen_rect = Rect::from_min_size(Pos2::ZERO, self.size / self.pixels_per_point);
+
+        let mut raw_input = self.raw_input.take();
+        raw_input.screen_rect = Some(screen_rect);
+        raw_input.time = Some(self.start_time.elapsed().as_secs_f64());
+        raw_input.modifiers = self.modifiers;
+        raw_input.focused = self.focused;
+
+        let viewport = raw_input.viewports.entry(ViewportId::ROOT).or_default();
+        viewport.inner_rect = Some(screen_rect);
+        viewport.native_pixels_per_point = Some(self.pixels_per_point);
+
+        raw_input
+    }
+
+    /// Write the text egui copied during the frame to the system clipboard.
+    #[cfg(feature = "clipboard")]
+    pub fn handle_platform_output(&mut self, platform_output: &egui::PlatformOutput) {
+        self.clipboard.handle_platform_output(platform_output);
+    }
+
+    /// Translate a winit window event. Returns `false` for events that aren't input, e.g.
+    /// redraw requests.
+    #[cfg(feature = "winit-input")]
+    pub fn on_winit_event(&mut self, event: &winit::event::WindowEvent) -> bool {
+        use winit::event::{ElementState, Ime, MouseButton, MouseScrollDelta, WindowEvent};
+
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.on_pointer_moved((position.x as f32, position.y as f32));
+            }
+            WindowEvent::CursorLeft { .. } => self.on_pointer_left(),
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => PointerButton::Primary,
+                    MouseButton::Right => PointerButton::Secondary,
+                    MouseButton::Middle => PointerButton::Middle,
+                    MouseButton::Back => PointerButton::Extra1,
+                    MouseButton::Forward => PointerButton::Extra2,
+                    MouseButton::Other(_) => return false,
+                };
+                self.on_pointer_button(button, *state == ElementState::Pressed);
+            }
+            WindowEvent::MouseWheel { delta, .. } => match delta {
+                MouseScrollDelta::LineDelta(x, y) => {
+                    self.on_wheel(MouseWheelUnit::Line, egui::vec2(*x, *y));
+                }
+                MouseScrollDelta::PixelDelta(delta) => {
+                    self.on_wheel(
+                        MouseWheelUnit::Point,
+                        egui::vec2(delta.x as f32, delta.y as f32),
+                    );
+                }
+            },
+            WindowEvent::ModifiersChanged(modifiers) => {
+                let state = modifiers.state();
+                let is_mac = cfg!(target_os = "macos");
+                self.set_modifiers(Modifiers {
+                    alt: state.alt_key(),
+                    ctrl: state.control_key(),
+                    shift: state.shift_key(),
+                    mac_cmd: is_mac && state.super_key(),
+                    command: if is_mac {
+                        state.super_key()
+                    } else {
+                        state.control_key()
+                    },
+                });
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                let pressed = event.state == ElementState::Pressed;
+                let physical_key = winit_physical_key(event.physical_key);
+                if let Some(key) = winit_key(&event.logical_key).or(physical_key) {
+                    self.on_key(key, physical_key, pressed, event.repeat);
+                }
+                // Shortcuts also carry text, e.g. "c" for ctrl+c.
+                if pressed && !self.modifiers.command {
+                    if let Some(text) = &event.text {
+                        self.on_text(text);
+                    }
+                }
+            }
+            WindowEvent::Ime(Ime::Commit(text)) => self.on_text(text),
+            WindowEvent::Touch(touch) => {
+                let phase = match touch.phase {
+                    winit::event::TouchPhase::Started => TouchPhase::Start,
+                    winit::event::TouchPhase::Moved => TouchPhase::Move,
+                    winit::event::TouchPhase::Ended => TouchPhase::End,
+                    winit::event::TouchPhase::Cancelled => TouchPhase::Cancel,
+                };
+                self.on_touch(
+                    egui::epaint::util::hash(touch.device_id),
+                    touch.id,
+                    phase,
+                    (touch.location.x as f32, touch.location.y as f32),
+                    touch.force.map(|force| force.normalized() as f32),
+                );
+            }
+            WindowEvent::Resized(size) => self.set_size([size.width, size.height]),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.set_pixels_per_point(*scale_factor as f32);
+            }
+            WindowEvent::Focused(focused) => self.on_focus(*focused),
+            WindowEvent::HoveredFile(path) => self.on_file_hovered(path.clone()),
+            WindowEvent::HoveredFileCancelled => self.on_file_hover_cancelled(),
+            WindowEvent::DroppedFile(path) => self.on_file_dropped(path.clone()),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Show the cursor egui asks for, e.g. [`egui::PlatformOutput::cursor_icon`], over `window`.
+    /// [`egui::CursorIcon::None`] hides the cursor.
+    #[cfg(feature = "winit-input")]
+    pub fn set_winit_cursor(&mut self, window: &winit::window::Window, icon: egui::CursorIcon) {
+        if self.cursor_icon == Some(icon) {
+            return;
+        }
+        self.cursor_icon = Some(icon);
+
+        match winit_cursor_icon(icon) {
+            Some(cursor) => {
+                window.set_cursor_visible(true);
+                window.set_cursor(cursor);
+            }
+            None => window.set_cursor_visible(false),
+        }
+    }
+}
+
+/// The winit cursor for `icon`, `None` for [`egui::CursorIcon::None`].
+#[cfg(feature = "winit-input")]
+pub fn winit_cursor_icon(icon: egui::CursorIcon) -> Option<winit::window::CursorIcon> {
+    use egui::CursorIcon;
+    use winit::window::CursorIcon as WinitCursor;
+
+    let cursor = match icon {
+        CursorIcon::None => return None,
+        CursorIcon::Default => WinitCursor::Default,
+        CursorIcon::ContextMenu => WinitCursor::ContextMenu,
+        CursorIcon::Help => WinitCursor::Help,
+        CursorIcon::PointingHand => WinitCursor::Pointer,
+        CursorIcon::Progress => WinitCursor::Progress,
+        CursorIcon::Wait => WinitCursor::Wait,
+        CursorIcon::Cell => WinitCursor::Cell,
+        CursorIcon::Crosshair => WinitCursor::Crosshair,
+        CursorIcon::Text => WinitCursor::Text,
+        CursorIcon::VerticalText => WinitCursor::VerticalText,
+        CursorIcon::Alias => WinitCursor::Alias,
+        CursorIcon::Copy => WinitCursor::Copy,
+        CursorIcon::Move => WinitCursor::Move,
+        CursorIcon::NoDrop => WinitCursor::NoDrop,
+        CursorIcon::NotAllowed => WinitCursor::NotAllowed,
+        CursorIcon::Grab => WinitCursor::Grab,
+        CursorIcon::Grabbing => WinitCursor::Grabbing,
+        CursorIcon::AllScroll => WinitCursor::AllScroll,
+        CursorIcon::ResizeHorizontal => WinitCursor::EwResize,
+        CursorIcon::ResizeNeSw => WinitCursor::NeswResize,
+        CursorIcon::ResizeNwSe => WinitCursor::NwseResize,
+        CursorIcon::ResizeVertical => WinitCursor::NsResize,
+        CursorIcon::ResizeEast => WinitCursor::EResize,
+        CursorIcon::ResizeSouthEast => WinitCursor::SeResize,
+        CursorIcon::ResizeSouth => WinitCursor::SResize,
+        CursorIcon::ResizeSouthWest => WinitCursor::SwResize,
+        CursorIcon::ResizeWest => WinitCursor::WResize,
+        CursorIcon::ResizeNorthWest => WinitCursor::NwResize,
+        CursorIcon::ResizeNorth => WinitCursor::NResize,
+        CursorIcon::ResizeNorthEast => WinitCursor::NeResize,
+        CursorIcon::ResizeColumn => WinitCursor::ColResize,
+        CursorIcon::ResizeRow => WinitCursor::RowResize,
+        CursorIcon::ZoomIn => WinitCursor::ZoomIn,
+        CursorIcon::ZoomOut => WinitCursor::ZoomOut,
+    };
+    Some(cursor)
+}
+
+/// winit's key names follow the values of the web's `KeyboardEvent.key`, like egui's.
+#[cfg(feature = "winit-input")]
+fn winit_key(key: &winit::keyboard::Key) -> Option<Key> {
+    match key {
+        winit::keyboard::Key::Named(named) => Key::from_name(&format!("{named:?}")),
+        winit::keyboard::Key::Character(text) => Key::from_name(text),
+        _ => None,
+    }
+}
+
+/// Physical keys are named after the web's `KeyboardEvent.code`, e.g. `KeyA` or `Digit1`.
+#[cfg(feature = "winit-input")]
+fn winit_physical_key(key: winit::keyboard::PhysicalKey) -> Option<Key> {
+    let winit::keyboard::PhysicalKey::Code(code) = key else {
+        return None;
+    };
+    let name = format!("{code:?}");
+    Key::from_name(name.strip_prefix("Key").unwrap_or(&name))
+}
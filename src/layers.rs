@@ -0,0 +1,197 @@
+//! Render the individual egui layers (background, every window / area, tooltips) separately.
+
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use egui::epaint::ClippedShape;
+use egui::{Context, LayerId, Order};
+use skia_safe::{surfaces, Color, EncodedImageFormat, Picture, Rect, Surface};
+
+use crate::egui_skia::headless_input;
+use crate::{EguiSkia, RasterizeOptions};
+
+/// The shapes painted onto a single egui layer during a frame.
+pub struct LayerShapes {
+    pub layer_id: LayerId,
+    pub shapes: Vec<ClippedShape>,
+}
+
+/// Capture the shapes of all layers of the current frame, in paint order.
+///
+/// Has to be called at the end of the ui closure passed to [`Context::run`], before egui
+/// flattens the layers into a single list of shapes.
+pub fn capture_layers(ctx: &Context) -> Vec<LayerShapes> {
+    let mut layer_ids = ctx.memory(|memory| memory.layer_ids().collect::<Vec<_>>());
+    for layer_id in [LayerId::background(), LayerId::debug()] {
+        if !layer_ids.contains(&layer_id) {
+            layer_ids.push(layer_id);
+        }
+    }
+    // Stable, so areas keep their relative order within an `Order`.
+    layer_ids.sort_by_key(|layer_id| Order::ALL.iter().position(|o| *o == layer_id.order));
+
+    let mut layers = ctx.graphics(|graphics| {
+        layer_ids
+            .into_iter()
+            .filter_map(|layer_id| {
+                let shapes = graphics
+                    .get(layer_id)?
+                    .all_entries()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                (!shapes.is_empty()).then_some(LayerShapes { layer_id, shapes })
+            })
+            .collect::<Vec<_>>()
+    });
+
+    for layer in &mut layers {
+        if let Some(transform) = ctx.layer_transform_to_global(layer.layer_id) {
+            for clipped_shape in &mut layer.shapes {
+                clipped_shape.clip_rect = transform * clipped_shape.clip_rect;
+                clipped_shape.shape.transform(transform);
+            }
+        }
+    }
+
+    layers
+}
+
+/// The draw calls of a single egui layer, see [`record_layers`].
+pub struct LayerPicture {
+    pub layer_id: LayerId,
+    /// The layer in pixels, on a transparent background.
+    pub picture: Picture,
+}
+
+/// Record every layer captured with [`capture_layers`] into its own [`Picture`], in paint
+/// order, instead of painting the frame with [`EguiSkia::paint`].
+///
+/// Embedders can then treat the layers separately, e.g. draw a drop shadow behind windows,
+/// fade tooltips in and out, or draw their own content between two layers:
+///
+/// ```ignore
+/// let mut layers = Vec::new();
+/// egui_skia.run(input, |ctx| {
+///     ui(ctx);
+///     layers = capture_layers(ctx);
+/// });
+/// for layer in record_layers(&mut egui_skia, layers) {
+///     canvas.draw_picture(&layer.picture, None, None);
+/// }
+/// ```
+///
+/// The texture updates of the frame are applied with the first layer.
+pub fn record_layers(backend: &mut EguiSkia, layers: Vec<LayerShapes>) -> Vec<LayerPicture> {
+    let pixels_per_point = backend.egui_ctx.pixels_per_point();
+    let screen_rect = backend.egui_ctx.screen_rect() * pixels_per_point;
+    let bounds = Rect::new(
+        screen_rect.min.x,
+        screen_rect.min.y,
+        screen_rect.max.x,
+        screen_rect.max.y,
+    );
+    let mut textures_delta = std::mem::take(&mut backend.textures_delta);
+
+    let pictures = layers
+        .into_iter()
+        .map(|layer| {
+            let primitives = backend.egui_ctx.tessellate(layer.shapes, pixels_per_point);
+            let picture = backend.painter.paint_to_picture(
+                bounds,
+                pixels_per_point,
+                primitives,
+                std::mem::take(&mut textures_delta),
+            );
+            LayerPicture {
+                layer_id: layer.layer_id,
+                picture,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Without layers, the texture updates still have to be applied.
+    if pictures.is_empty() {
+        backend
+            .painter
+            .paint_to_picture(bounds, pixels_per_point, Vec::new(), textures_delta);
+    }
+    pictures
+}
+
+/// Render every layer of the ui into its own transparent surface of `size`, in paint order.
+pub fn rasterize_layers(
+    size: (i32, i32),
+    mut ui: impl FnMut(&Context),
+    options: Option<RasterizeOptions>,
+) -> Vec<(LayerId, Surface)> {
+    let options = options.unwrap_or_default();
+    let mut backend = EguiSkia::new();
+    let input = headless_input(size);
+
+    backend
+        .egui_ctx
+        .set_pixels_per_point(options.pixels_per_point);
+
+    let mut layers = Vec::new();
+    for _ in 0..options.frames_before_screenshot {
+        backend.run(input.clone(), |ctx| {
+            ui(ctx);
+            layers = capture_layers(ctx);
+        });
+    }
+
+    let mut textures_delta = std::mem::take(&mut backend.textures_delta);
+    let pixels_per_point = backend.egui_ctx.pixels_per_point();
+
+    layers
+        .into_iter()
+        .map(|layer| {
+            let mut surface =
+                surfaces::raster_n32_premul(size).expect("Failed to create surface");
+            surface.canvas().clear(Color::TRANSPARENT);
+
+            let primitives = backend.egui_ctx.tessellate(layer.shapes, pixels_per_point);
+            backend.painter.paint_and_update_textures(
+                surface.canvas(),
+                pixels_per_point,
+                primitives,
+                std::mem::take(&mut textures_delta),
+            );
+
+            (layer.layer_id, surface)
+        })
+        .collect()
+}
+
+/// Render every layer of the ui into its own transparent png in `directory`.
+///
+/// The files are named after their paint order and layer, e.g. `00-Background-<id>.png`.
+/// Returns the paths of the written files, in paint order.
+pub fn export_layers_png(
+    directory: impl AsRef<Path>,
+    size: (i32, i32),
+    ui: impl FnMut(&Context),
+    options: Option<RasterizeOptions>,
+) -> io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(&directory)?;
+
+    rasterize_layers(size, ui, options)
+        .into_iter()
+        .enumerate()
+        .map(|(index, (layer_id, mut surface))| {
+            let data = surface
+                .image_snapshot()
+                .encode_to_data(EncodedImageFormat::PNG)
+                .ok_or_else(|| io::Error::other("Failed to encode image"))?;
+
+            let path = directory.as_ref().join(format!(
+                "{index:02}-{:?}-{:016x}.png",
+                layer_id.order,
+                layer_id.id.value()
+            ));
+            std::fs::File::create(&path)?.write_all(&data)?;
+            Ok(path)
+        })
+        .collect()
+}
@@ -1,12 +1,47 @@
 extern crate core;
 
+mod atlas;
+pub mod backends;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod cursor;
+pub mod damage;
 mod egui_skia;
+mod error;
+mod font_atlas;
+pub mod input;
+pub mod layers;
 mod painter;
+mod profiling;
+pub mod shm;
+pub mod testing;
+pub mod text;
+mod upload;
+pub mod vector;
+pub mod viewport;
+pub mod widgets;
+pub mod yuv;
+
+#[cfg(feature = "clipboard")]
+pub mod clipboard;
+#[cfg(feature = "pdf")]
+pub mod print;
+#[cfg(feature = "remote")]
+pub mod remote;
+#[cfg(feature = "svg")]
+pub mod svg;
 
 #[cfg(feature = "winit")]
 mod egui_skia_winit;
 #[cfg(feature = "winit")]
-pub use egui_skia_winit::EguiSkiaWinit;
+pub use egui_skia_winit::{EguiSkiaWinit, EguiSkiaWinitWindows};
 
 pub use egui_skia::*;
-pub use painter::EguiSkiaPaintCallback;
+pub use error::EguiSkiaError;
+pub use font_atlas::SharedFontAtlas;
+pub use painter::{
+    CallbackError, CallbackOrder, EguiSkiaPaintCallback, EyeView, FrameInfo, HdrOptions,
+    InvalidVertices, PaintCallbackInfo, PaintStats, Painter, PainterOptions, RawFormat,
+    VertexAlpha,
+};
+pub use widgets::SkiaCanvas;
@@ -1,23 +1,387 @@
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
 use std::ops::Deref;
-use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use egui::epaint::ahash::AHashMap;
-#[cfg(feature = "cpu_fix")]
-use egui::epaint::Mesh16;
-use egui::epaint::Primitive;
-use egui::{ClippedPrimitive, ImageData, Pos2, TextureId, TexturesDelta};
-use skia_safe::vertices::VertexMode;
-use skia_safe::{images, scalar, surfaces, BlendMode, Canvas, ClipOp, Color, ConditionallySend, Data, Drawable, Image, ImageInfo, Paint, PictureRecorder, Point, Rect, Sendable, Vertices};
-use skia_safe::canvas::AutoRestoredCanvas;
+use egui::epaint::ahash::{AHashMap, AHashSet, AHasher};
+use egui::epaint::{
+    ClippedShape, ImageDelta, Mesh16, Primitive, TessellationOptions, Tessellator, WHITE_UV,
+};
+use egui::{ClippedPrimitive, Color32, ImageData, Pos2, TextureId, TexturesDelta};
+use skia_safe::vertices::{self, BuilderFlags, VertexMode};
+use skia_safe::{images, surfaces, AlphaType, BlendMode, Canvas, ClipOp, Color, ColorType, ConditionallySend, Data, Drawable, ISize, Image, ImageInfo, Paint, Path, Picture, PictureRecorder, Point, Rect, Sendable, Surface, TileMode, Vertices};
+use skia_safe::canvas::{AutoRestoredCanvas, SaveLayerRec, SrcRectConstraint};
+use skia_safe::{color_filters, ColorSpace, Matrix};
+
+#[cfg(feature = "gpu")]
+use skia_safe::gpu::{
+    Budgeted, DirectContext, FlushInfo, Mipmapped, Protected, SemaphoresSubmitted, SurfaceOrigin,
+};
+#[cfg(feature = "gpu")]
+use skia_safe::TextureCompressionType;
+
+use crate::atlas::{AtlasEntry, TextureAtlas};
+use crate::error::EguiSkiaError;
+use crate::font_atlas::SharedFontAtlas;
+use crate::profiling::profile_scope;
+use crate::text::FontRegistry;
+use crate::upload::UploadWorker;
 
 struct PaintHandle {
     paint: Paint,
     image: Image,
+    sampling_options: skia_safe::SamplingOptions,
+    /// The tile mode requested by the texture's [`egui::TextureOptions::wrap_mode`].
+    wrap_mode: TileMode,
+    /// The surface patches are written into, kept so later patches don't have to draw the
+    /// whole texture into a new surface first.
+    surface: Option<Surface>,
+    /// Set if the texture is packed into the painter's [`TextureAtlas`], in which case `paint`
+    /// samples the atlas page and uvs have to be remapped with [`AtlasEntry::map_uv`].
+    atlas_entry: Option<AtlasEntry>,
+}
+
+impl PaintHandle {
+    /// The memory held by the texture's image and retained surface, in bytes.
+    fn bytes(&self) -> usize {
+        let surface = self.surface.as_ref().map_or(0, |surface| {
+            surface.image_info().compute_min_byte_size()
+        });
+        self.image.image_info().compute_min_byte_size() + surface
+    }
+}
+
+/// The key of cached [`Vertices`]: the hash of the mesh and the painter state they were built
+/// from, with the mesh's size, so meshes only share vertices if a 64 bit hash collides for meshes
+/// of the same size.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct VerticesKey {
+    hash: u64,
+    vertices: usize,
+    indices: usize,
+}
+
+/// The painter state besides the mesh that the vertices depend on.
+#[derive(Clone, Copy, Hash)]
+struct VerticesParams {
+    overlay: bool,
+    atlas: Option<(usize, [i32; 4])>,
+    white_texel: Option<[u32; 4]>,
+}
+
+impl VerticesKey {
+    fn new(mesh: &Mesh16, params: &VerticesParams) -> Self {
+        let mut hasher = AHasher::default();
+        params.hash(&mut hasher);
+        mesh.texture_id.hash(&mut hasher);
+        for vertex in &mesh.vertices {
+            vertex_bits(vertex).hash(&mut hasher);
+        }
+        mesh.indices.hash(&mut hasher);
+        Self {
+            hash: hasher.finish(),
+            vertices: mesh.vertices.len(),
+            indices: mesh.indices.len(),
+        }
+    }
+}
+
+/// The bits of a vertex' position, uv and color, so vertices with NaN positions hash the same
+/// in every frame.
+fn vertex_bits(vertex: &egui::epaint::Vertex) -> [u32; 5] {
+    let [x, y, u, v] = [vertex.pos.x, vertex.pos.y, vertex.uv.x, vertex.uv.y].map(f32::to_bits);
+    [x, y, u, v, u32::from_le_bytes(vertex.color.to_array())]
+}
+
+/// Information about the frame being painted, passed to the paint hooks.
+pub struct FrameInfo {
+    pub pixels_per_point: f32,
+    /// The number of clipped primitives in the frame.
+    pub num_primitives: usize,
+    /// The size of the canvas' base layer in pixels.
+    pub canvas_size: ISize,
+}
+
+type PaintHook = Box<dyn Fn(&Canvas, &FrameInfo) + Send>;
+
+/// What the painter did in a frame, returned by [`Painter::paint_and_update_textures`].
+#[derive(Clone, Debug, Default)]
+pub struct PaintStats {
+    /// The number of meshes painted, after splitting them into 16 bit meshes.
+    pub meshes: usize,
+    pub vertices: usize,
+    pub indices: usize,
+    pub callbacks: usize,
+    /// The number of meshes and callbacks skipped because they are outside of the canvas, or
+    /// of its clip.
+    pub culled: usize,
+    /// The number of textures created or updated.
+    pub textures_set: usize,
+    /// The number of texture updates skipped because they didn't change the texture's data,
+    /// e.g. a font atlas egui sent again after a theme change.
+    pub textures_unchanged: usize,
+    pub textures_freed: usize,
+    /// The number of user textures evicted to stay within the texture memory budget, see
+    /// [`Painter::set_texture_memory_budget`].
+    pub textures_evicted: usize,
+    /// The size of the texture data uploaded, in bytes.
+    pub bytes_uploaded: usize,
+    /// The memory held by all textures after the frame, in bytes.
+    pub texture_memory: usize,
+    /// The time spent creating and updating textures.
+    pub textures_time: Duration,
+    /// The time spent painting the primitives.
+    pub paint_time: Duration,
+}
+
+type PaintModifier = Box<dyn Fn(TextureId, &mut Paint) + Send>;
+
+type TextureReloader = Box<dyn Fn(TextureId) -> Option<Image> + Send>;
+
+/// Maps egui's SDR colors onto an HDR surface, so the ui isn't blindingly bright next to HDR
+/// content.
+#[derive(Clone, Copy, Debug)]
+pub struct HdrOptions {
+    /// The luminance egui's white is displayed at, in nits. 203 nits is the reference white
+    /// recommended by ITU-R BT.2408.
+    pub sdr_white_nits: f32,
+    /// The luminance a linear value of `1.0` represents on the destination surface, in nits.
+    /// This is 80 nits for scRGB (linear F16) surfaces.
+    pub surface_white_nits: f32,
+}
+
+impl Default for HdrOptions {
+    fn default() -> Self {
+        Self {
+            sdr_white_nits: 203.0,
+            surface_white_nits: 80.0,
+        }
+    }
+}
+
+impl HdrOptions {
+    fn layer_paint(&self) -> Paint {
+        let scale = self.sdr_white_nits / self.surface_white_nits;
+        #[rustfmt::skip]
+        let matrix = [
+            scale, 0.0, 0.0, 0.0, 0.0,
+            0.0, scale, 0.0, 0.0, 0.0,
+            0.0, 0.0, scale, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ];
+        let color_filter = color_filters::matrix_row_major(&matrix, color_filters::Clamp::No);
+
+        let mut paint = Paint::default();
+        // Scale the luminance in linear light, not in the surface's transfer function.
+        paint.set_color_filter(
+            color_filter
+                .with_working_color_space(ColorSpace::new_srgb_linear())
+                .unwrap_or(color_filter),
+        );
+        paint
+    }
+}
+
+/// Options of a [`Painter`], see [`Painter::set_options`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PainterOptions {
+    /// Sample all textures but the font texture anisotropically, with up to this many samples.
+    ///
+    /// Improves the quality of images that are drawn scaled down or skewed, e.g. thumbnails in
+    /// a zoomable canvas, at little cost on the gpu. Overrides the texture's filter options.
+    /// Raster surfaces fall back to mipmapped linear filtering. `None` (the default) uses
+    /// egui's [`egui::TextureOptions`].
+    pub max_anisotropy: Option<u32>,
+    /// Blend in sRGB encoded values, like egui_glow and egui_wgpu do, instead of in linear
+    /// light.
+    ///
+    /// Surfaces with an sRGB format (e.g. [`ColorType::SRGBA8888`], as picked for sRGB gl
+    /// framebuffers) blend in linear light, which makes anti-aliased edges, text and
+    /// translucent fills look darker and thinner than in egui's own backends. With this option
+    /// the ui is painted into an 8 bit sRGB layer first, which is then drawn onto the canvas.
+    /// Leave it off for surfaces that already blend in sRGB encoded values, which includes all
+    /// [`ColorType::N32`] surfaces.
+    pub gamma_blending: bool,
+    /// How the alpha of mesh vertex colors is interpreted.
+    pub vertex_alpha: VertexAlpha,
+    /// Remap the coverage of the font texture with this exponent, to match the text weight of
+    /// egui's other backends on surfaces that blend differently.
+    ///
+    /// Values below `1.0` make text heavier, e.g. `0.8` on raster surfaces, where text looks
+    /// thinner than with egui_glow. `None` (the default) keeps egui's coverage. Only applies to
+    /// font texture uploads after the option is set, so set it before the first frame. Painters
+    /// sharing a [`SharedFontAtlas`] have to use the same text gamma.
+    pub text_gamma: Option<f32>,
+    /// Paint the ui at this many times the resolution of the canvas into an offscreen surface,
+    /// which is then scaled down onto the canvas.
+    ///
+    /// Smooths thin strokes and small shapes on raster surfaces, where meshes aren't
+    /// antialiased beyond egui's feathering, at the cost of painting the square of the factor as
+    /// many pixels. Independent of `pixels_per_point`, egui lays out the ui as before. Powers of
+    /// two give the best downscaling. The factor is lowered where the offscreen surface would
+    /// exceed 16384 pixels per side, the largest surface Skia creates on most gpus. `None` or
+    /// `1` (the default) paint directly onto the canvas.
+    pub supersampling: Option<u32>,
+    /// Draw shadows and other blurred rects with Skia's blur in
+    /// [`Painter::paint_native_shapes`], instead of tessellating egui's approximation of the
+    /// blur. Faster and smoother for large blurs.
+    pub native_shadows: bool,
+    /// What happens to mesh vertices with NaN or infinite positions. Triangles using them are
+    /// never drawn.
+    pub invalid_vertices: InvalidVertices,
+    /// Dither textured meshes, vertex color gradients and images.
+    ///
+    /// Hides the banding of large gradient fills on 8 bit raster surfaces and on surfaces
+    /// with fewer bits per channel, like [`ColorType::RGB565`] framebuffers of embedded
+    /// displays. Solid fills aren't affected.
+    pub dither: bool,
+    /// Paint the ui for compositing over external content, e.g. a video or a game scene that
+    /// the ui is blended onto later, see [`EguiSkia::paint_overlay`](crate::EguiSkia::paint_overlay).
+    ///
+    /// The canvas (within its clip) is cleared to transparent before painting, so the result
+    /// is only the ui, with premultiplied alpha. Skia interpolates vertex colors unpremultiplied,
+    /// which darkens egui's feathered edges towards the black of their fully transparent
+    /// outer vertices. That is barely visible on an opaque background, but shows as dark fringes
+    /// around translucent windows once the overlay is composited. In this mode fully transparent
+    /// vertices take the color of the vertices they share a triangle with, so edges fade out in
+    /// the shape's color.
+    pub overlay: bool,
+}
+
+/// How the painter reports mesh vertices with non-finite positions, which usually come from a
+/// tessellation bug or a shape with NaN coordinates, see [`PainterOptions::invalid_vertices`].
+///
+/// Meshes are only checked when their Skia vertices are built, so a mesh that is painted
+/// unchanged in consecutive frames is only reported once.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InvalidVertices {
+    /// Skip the triangles using invalid vertices.
+    #[default]
+    Skip,
+    /// Skip the triangles and log a warning with the mesh's texture and vertex counts.
+    Warn,
+    /// Panic in debug builds, skip the triangles in release builds.
+    DebugAssert,
+}
+
+/// How the color channels of mesh vertex colors relate to their alpha.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VertexAlpha {
+    /// The colors are premultiplied, like all colors egui produces. They are unpremultiplied
+    /// exactly like [`Color32::to_srgba_unmultiplied`] does. Fully transparent vertices stay
+    /// transparent, additive colors (transparent with non-zero color channels) can't be
+    /// represented by Skia's vertex colors and are dropped.
+    #[default]
+    Premultiplied,
+    /// The colors are premultiplied in linear light and then sRGB encoded, which is what
+    /// converting an [`egui::Rgba`] into a [`Color32`] produces. They are unpremultiplied in
+    /// linear light. Fully transparent vertices stay transparent.
+    PremultipliedLinear,
+    /// The colors are already unpremultiplied, e.g. because a custom mesh was built with
+    /// straight alpha. They are passed to Skia unchanged.
+    Straight,
+}
+
+impl VertexAlpha {
+    /// The unpremultiplied sRGBA value of `color`, which is what Skia expects for vertex
+    /// colors and paints.
+    fn unmultiplied(self, color: Color32) -> [u8; 4] {
+        match self {
+            VertexAlpha::Premultiplied => color.to_srgba_unmultiplied(),
+            VertexAlpha::PremultipliedLinear if color.a() == 0 => [0; 4],
+            VertexAlpha::PremultipliedLinear => egui::Rgba::from(color).to_srgba_unmultiplied(),
+            VertexAlpha::Straight => color.to_array(),
+        }
+    }
+}
+
+/// The pixel format of [`Painter::set_texture_from_raw`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawFormat {
+    /// Premultiplied red, green, blue and alpha bytes.
+    Rgba8,
+    /// Premultiplied blue, green, red and alpha bytes, the usual format of screen captures.
+    Bgra8,
+    /// A single coverage byte per pixel. The texture is white with the bytes as alpha, like
+    /// egui's font texture.
+    Alpha8,
+}
+
+/// Where one eye's copy of the ui is painted, see [`Painter::paint_stereo`].
+#[derive(Clone, Copy, Debug)]
+pub struct EyeView {
+    /// The area of the canvas the eye's copy is painted into, in pixels. The ui's origin
+    /// is placed at the top left corner of the target.
+    pub target: Rect,
+    /// An additional per-eye offset in egui points, e.g. to give the ui some depth.
+    pub offset: egui::Vec2,
+}
+
+impl EyeView {
+    /// Split a double-wide canvas of `size` into a left and right half.
+    pub fn side_by_side(size: ISize) -> [EyeView; 2] {
+        let half_width = size.width as f32 / 2.0;
+        let height = size.height as f32;
+        [
+            EyeView {
+                target: Rect::new(0.0, 0.0, half_width, height),
+                offset: egui::Vec2::ZERO,
+            },
+            EyeView {
+                target: Rect::new(half_width, 0.0, half_width * 2.0, height),
+                offset: egui::Vec2::ZERO,
+            },
+        ]
+    }
 }
 
 pub struct Painter {
     paints: AHashMap<TextureId, PaintHandle>,
+    tile_modes: AHashMap<TextureId, TileMode>,
+    blend_modes: AHashMap<TextureId, BlendMode>,
+    atlas: Option<TextureAtlas>,
+    /// The shared font atlas cache and the key of the font image this painter uses from it.
+    shared_font_atlas: Option<(SharedFontAtlas, Option<u64>)>,
+    global_clip: Option<egui::Rect>,
+    transform: Option<Matrix>,
+    upload_budget: Option<usize>,
+    /// Texture deltas held back by the upload budget, grouped by texture.
+    pending_uploads: Vec<(TextureId, Vec<ImageDelta>)>,
+    upload_worker: Option<UploadWorker>,
+    /// A hash of the data of every texture, to skip uploads that don't change it.
+    texture_keys: AHashMap<TextureId, u64>,
+    output_mask: Option<Path>,
+    hdr_options: Option<HdrOptions>,
+    compositing_blend_mode: Option<BlendMode>,
+    options: PainterOptions,
+    pre_paint_hook: Option<PaintHook>,
+    post_paint_hook: Option<PaintHook>,
+    paint_modifier: Option<PaintModifier>,
+    texture_memory_budget: Option<usize>,
+    texture_reloader: Option<TextureReloader>,
+    /// The user textures the texture reloader can restore, see
+    /// [`Painter::set_texture_reloadable`].
+    reloadable_textures: AHashSet<TextureId>,
+    /// The sampling options and wrap modes of evicted user textures, to reload them with.
+    evicted_textures: AHashMap<TextureId, (skia_safe::SamplingOptions, TileMode)>,
+    /// The frame every texture was last painted in.
+    texture_last_used: AHashMap<TextureId, u64>,
+    frame: u64,
+    texture_free_delay: u64,
+    /// Textures egui freed, with the frame they are released after.
+    deferred_frees: Vec<(u64, TextureId)>,
+    /// The paint callbacks that failed in the last frame.
+    callback_errors: Vec<EguiSkiaError>,
+    font_registry: Option<FontRegistry>,
+    stats: PaintStats,
     white_paint_workaround: Paint,
+    /// The vertices of the meshes painted in the current and in the previous frame, keyed by a
+    /// hash of the mesh, together with a copy of the mesh they were built from.
+    vertices_cache: AHashMap<VerticesKey, Vertices>,
+    previous_vertices_cache: AHashMap<VerticesKey, Vertices>,
+    next_user_texture_id: u64,
+    #[cfg(feature = "gpu")]
+    direct_context: Option<DirectContext>,
 }
 
 impl Painter {
@@ -27,49 +391,1465 @@ impl Painter {
 
         Self {
             paints: AHashMap::new(),
+            tile_modes: AHashMap::new(),
+            blend_modes: AHashMap::new(),
+            atlas: None,
+            shared_font_atlas: None,
+            global_clip: None,
+            transform: None,
+            upload_budget: None,
+            pending_uploads: Vec::new(),
+            upload_worker: None,
+            texture_keys: AHashMap::new(),
+            output_mask: None,
+            hdr_options: None,
+            compositing_blend_mode: None,
+            options: PainterOptions::default(),
+            pre_paint_hook: None,
+            post_paint_hook: None,
+            paint_modifier: None,
+            texture_memory_budget: None,
+            texture_reloader: None,
+            reloadable_textures: AHashSet::new(),
+            evicted_textures: AHashMap::new(),
+            texture_last_used: AHashMap::new(),
+            frame: 0,
+            texture_free_delay: 0,
+            deferred_frees: Vec::new(),
+            callback_errors: Vec::new(),
+            font_registry: None,
+            stats: PaintStats::default(),
             white_paint_workaround,
+            vertices_cache: AHashMap::new(),
+            previous_vertices_cache: AHashMap::new(),
+            next_user_texture_id: 0,
+            #[cfg(feature = "gpu")]
+            direct_context: None,
+        }
+    }
+
+    /// Constrain all drawing to `clip`, in egui points.
+    ///
+    /// The clip is intersected with the clip rect of every primitive, so egui never draws
+    /// outside of it, even if it thinks it owns the whole canvas. Useful for split-screen setups.
+    pub fn set_global_clip(&mut self, clip: Option<egui::Rect>) {
+        self.global_clip = clip;
+    }
+
+    /// Transform the whole ui with `transform`, in pixels, e.g. to rotate it for a kiosk display
+    /// mounted in portrait.
+    ///
+    /// Like the rest of the ui, the transform ignores the canvas' matrix. Clip rects are
+    /// transformed with the ui, so they clip correctly for rotations and skews as well. egui
+    /// doesn't know about the transform, so map pointer positions with the inverse of the
+    /// transform before passing them to egui. Passing `None` removes the transform.
+    pub fn set_transform(&mut self, transform: Option<Matrix>) {
+        self.transform = transform;
+    }
+
+    pub fn transform(&self) -> Option<&Matrix> {
+        self.transform.as_ref()
+    }
+
+    /// Draw text with the typefaces of `font_registry` in [`Self::paint_native_shapes`],
+    /// instead of with glyphs from egui's font atlas. Passing `None` goes back to the atlas.
+    pub fn set_font_registry(&mut self, font_registry: Option<FontRegistry>) {
+        self.font_registry = font_registry;
+    }
+
+    pub fn font_registry_mut(&mut self) -> Option<&mut FontRegistry> {
+        self.font_registry.as_mut()
+    }
+
+    /// Limit the texture data uploaded per frame to about `bytes`, spreading large uploads, e.g.
+    /// a gallery loading dozens of photos, over the following frames.
+    ///
+    /// The font texture and textures visible in the current frame are uploaded first. At least
+    /// one texture is uploaded per frame, even if it exceeds the budget. Meshes using textures
+    /// that weren't uploaded yet are skipped, so keep repainting while
+    /// [`Self::has_pending_uploads`] returns `true`. Passing `None` uploads everything again.
+    pub fn set_texture_upload_budget(&mut self, bytes: Option<usize>) {
+        self.upload_budget = bytes;
+    }
+
+    /// Convert new textures into Skia's pixel format on a worker thread, so loading large
+    /// images doesn't stall the frame.
+    ///
+    /// Textures keep showing their previous image, or aren't drawn if they are new, until the
+    /// conversion finished, so keep repainting while [`Self::has_pending_uploads`] returns
+    /// `true`. The font texture is always uploaded immediately. Returns `false` if the worker
+    /// thread couldn't be spawned.
+    pub fn set_async_uploads(&mut self, async_uploads: bool) -> bool {
+        if !async_uploads {
+            self.upload_worker = None;
+        } else if self.upload_worker.is_none() {
+            self.upload_worker = UploadWorker::new();
+        }
+        self.upload_worker.is_some() == async_uploads
+    }
+
+    /// Whether texture uploads were deferred to later frames by the upload budget, or are
+    /// still being converted, see [`Self::set_async_uploads`].
+    pub fn has_pending_uploads(&self) -> bool {
+        !self.pending_uploads.is_empty()
+            || self
+                .upload_worker
+                .as_ref()
+                .is_some_and(UploadWorker::is_busy)
+    }
+
+    /// Change the options of the painter. They apply to textures uploaded afterwards.
+    pub fn set_options(&mut self, options: PainterOptions) {
+        self.options = options;
+        self.vertices_cache.clear();
+        self.previous_vertices_cache.clear();
+    }
+
+    pub fn options(&self) -> &PainterOptions {
+        &self.options
+    }
+
+    /// Switch [`PainterOptions::overlay`] and return its previous value. Unlike
+    /// [`Self::set_options`] this keeps the vertex caches, which are keyed on the flag.
+    pub(crate) fn set_overlay(&mut self, overlay: bool) -> bool {
+        std::mem::replace(&mut self.options.overlay, overlay)
+    }
+
+    /// Clip all drawing to `mask`, a path in egui points, e.g. a circle for a round dashboard
+    /// or a window outline with a notch. Unlike [`Self::set_global_clip`], the mask can have
+    /// any shape; its edges are antialiased.
+    pub fn set_output_mask(&mut self, mask: Option<Path>) {
+        self.output_mask = mask;
+    }
+
+    /// Map egui's colors to the given reference white when painting onto an HDR (e.g. F16 or
+    /// PQ) surface. Only set this when painting onto HDR surfaces.
+    pub fn set_hdr_options(&mut self, hdr_options: Option<HdrOptions>) {
+        self.hdr_options = hdr_options;
+    }
+
+    /// Composite the whole ui onto the canvas with `blend_mode`, e.g. [`BlendMode::DstOut`] to
+    /// use the ui as a mask that cuts holes into a game scene, or [`BlendMode::Plus`] to add it
+    /// as a glowing HUD.
+    ///
+    /// The ui is painted into a layer first, with egui's usual blending, and the layer is then
+    /// blended onto the canvas' contents. Passing `None` paints directly onto the canvas again,
+    /// like [`BlendMode::SrcOver`]. Per-texture blend modes, see
+    /// [`Self::set_texture_blend_mode`], apply within the layer.
+    pub fn set_compositing_blend_mode(&mut self, blend_mode: Option<BlendMode>) {
+        self.compositing_blend_mode = blend_mode;
+    }
+
+    pub fn compositing_blend_mode(&self) -> Option<BlendMode> {
+        self.compositing_blend_mode
+    }
+
+    /// Flush the gpu work recorded by the previous paints to the backend api.
+    ///
+    /// The painter never flushes or submits on its own, so hosts sharing a [`DirectContext`]
+    /// with other renderers control exactly when egui's work is issued relative to theirs.
+    #[cfg(feature = "gpu")]
+    pub fn flush(&self, context: &mut DirectContext) -> SemaphoresSubmitted {
+        context.flush(&FlushInfo::default())
+    }
+
+    /// Like [`Self::flush`], but with a caller provided [`FlushInfo`].
+    ///
+    /// Use this to pass backend semaphores that get signaled, or a finished proc that is called,
+    /// once the gpu work of the egui frame completes, e.g. when another api samples the result.
+    /// Check the returned [`SemaphoresSubmitted`]: if the semaphores weren't submitted, the
+    /// caller must not wait on them.
+    #[cfg(feature = "gpu")]
+    pub fn flush_with_info(
+        &self,
+        context: &mut DirectContext,
+        info: &FlushInfo,
+    ) -> SemaphoresSubmitted {
+        context.flush(info)
+    }
+
+    /// Flush, submit and block until the gpu finished all work, for hosts that can't use
+    /// semaphores.
+    #[cfg(feature = "gpu")]
+    pub fn flush_and_wait(&self, context: &mut DirectContext) {
+        context.flush_submit_and_sync_cpu();
+    }
+
+    /// Submit the flushed work to the gpu. Returns `false` if submitting failed.
+    ///
+    /// See [`Self::flush`].
+    #[cfg(feature = "gpu")]
+    pub fn submit(&self, context: &mut DirectContext) -> bool {
+        context.submit(None)
+    }
+
+    /// Upload textures to the gpu of `context`, and apply texture patches there, instead of
+    /// re-compositing the whole texture on the cpu for every patch, e.g. for the font atlas.
+    ///
+    /// Set this before painting the first frame, textures that already exist stay on the cpu.
+    /// Textures aren't packed into the texture atlas while a context is set.
+    #[cfg(feature = "gpu")]
+    pub fn set_direct_context(&mut self, context: Option<DirectContext>) {
+        self.direct_context = context;
+    }
+
+    /// Whether textures live on the gpu, see [`Self::set_direct_context`].
+    fn has_direct_context(&self) -> bool {
+        #[cfg(feature = "gpu")]
+        let has_direct_context = self.direct_context.is_some();
+        #[cfg(not(feature = "gpu"))]
+        let has_direct_context = false;
+        has_direct_context
+    }
+
+    /// Move `image` to the gpu, if the painter has a direct context.
+    fn upload_image(&mut self, image: Image) -> Image {
+        #[cfg(feature = "gpu")]
+        if let Some(context) = &mut self.direct_context {
+            if let Some(texture) = skia_safe::gpu::images::texture_from_image(
+                context,
+                &image,
+                Mipmapped::No,
+                Budgeted::Yes,
+            ) {
+                return texture;
+            }
+        }
+        image
+    }
+
+    /// A surface to composite texture patches onto, on the gpu if the painter has a direct
+    /// context.
+    fn texture_surface(&mut self, info: &ImageInfo) -> Option<Surface> {
+        #[cfg(feature = "gpu")]
+        if let Some(context) = &mut self.direct_context {
+            if let Some(surface) = skia_safe::gpu::surfaces::render_target(
+                context,
+                Budgeted::Yes,
+                info,
+                None,
+                SurfaceOrigin::TopLeft,
+                None,
+                false,
+                None,
+            ) {
+                return Some(surface);
+            }
+        }
+        surfaces::raster(info, None, None)
+    }
+
+    /// Set a hook that is called before the first primitive of every frame is painted, e.g. to
+    /// clear the background.
+    ///
+    /// The hook draws with the canvas' matrix as passed to [`Self::paint_and_update_textures`],
+    /// i.e. in pixels unless the host transformed the canvas.
+    pub fn set_pre_paint_hook(&mut self, hook: impl Fn(&Canvas, &FrameInfo) + Send + 'static) {
+        self.pre_paint_hook = Some(Box::new(hook));
+    }
+
+    /// Set a hook that is called after the last primitive of every frame is painted, e.g. to
+    /// stamp a watermark or draw debug overlays on top of the ui.
+    ///
+    /// See [`Self::set_pre_paint_hook`] for the coordinate system.
+    pub fn set_post_paint_hook(&mut self, hook: impl Fn(&Canvas, &FrameInfo) + Send + 'static) {
+        self.post_paint_hook = Some(Box::new(hook));
+    }
+
+    /// Remove the hooks set with [`Self::set_pre_paint_hook`] and [`Self::set_post_paint_hook`].
+    pub fn clear_paint_hooks(&mut self) {
+        self.pre_paint_hook = None;
+        self.post_paint_hook = None;
+    }
+
+    /// Keep the memory held by textures below about `bytes`, by evicting the least recently
+    /// painted user textures, e.g. thumbnails scrolled out of view, after a frame.
+    ///
+    /// Only textures marked with [`Self::set_texture_reloadable`] are evicted, while a reloader
+    /// is set with [`Self::set_texture_reloader`], and only if they weren't painted in the
+    /// frame. egui's own textures are never evicted. Passing `None` removes the budget. See
+    /// [`Self::texture_memory`].
+    pub fn set_texture_memory_budget(&mut self, bytes: Option<usize>) {
+        self.texture_memory_budget = bytes;
+    }
+
+    /// Set the function that restores an evicted user texture when it is painted again, e.g.
+    /// by decoding its file again. It returns the texture's image, or `None` if it can't be
+    /// restored right now. Meshes using the texture aren't drawn then, and the reload is tried
+    /// again the next time the texture is painted.
+    pub fn set_texture_reloader(
+        &mut self,
+        reloader: impl Fn(TextureId) -> Option<Image> + Send + 'static,
+    ) {
+        self.texture_reloader = Some(Box::new(reloader));
+    }
+
+    /// Allow evicting the user texture `tex_id` to stay within the memory budget, because the
+    /// texture reloader can restore it. Textures aren't reloadable unless marked.
+    pub fn set_texture_reloadable(&mut self, tex_id: TextureId, reloadable: bool) {
+        if reloadable && matches!(tex_id, TextureId::User(_)) {
+            self.reloadable_textures.insert(tex_id);
+        } else {
+            self.reloadable_textures.remove(&tex_id);
+        }
+    }
+
+    /// Remove the reloader set with [`Self::set_texture_reloader`], which stops evictions.
+    pub fn clear_texture_reloader(&mut self) {
+        self.texture_reloader = None;
+    }
+
+    /// Release the textures egui frees only after `frames` more frames were painted, instead of
+    /// right after the frame that frees them.
+    ///
+    /// Backends that queue several frames on the gpu, e.g. with mailbox presentation or a
+    /// render thread, may still sample a texture of an earlier frame when egui frees it. Set
+    /// this to the depth of the queue to keep such textures alive until those frames are done.
+    /// Defaults to `0`.
+    pub fn set_texture_free_delay(&mut self, frames: u64) {
+        self.texture_free_delay = frames;
+    }
+
+    /// The memory held by all textures, in bytes.
+    pub fn texture_memory(&self) -> usize {
+        self.paints.values().map(PaintHandle::bytes).sum()
+    }
+
+    /// The memory held by the texture `tex_id`, in bytes.
+    pub fn texture_bytes(&self, tex_id: TextureId) -> Option<usize> {
+        self.paints.get(&tex_id).map(PaintHandle::bytes)
+    }
+
+    /// Set a function that can adjust the [`Paint`] of a texture whenever the painter builds it,
+    /// e.g. to change the filter quality, add a color filter or enable dithering per texture.
+    ///
+    /// The paint comes with the texture's shader set. Paints are built when a texture is
+    /// uploaded, its tile mode changes or its atlas page is updated, so set the modifier before
+    /// uploading the textures it should apply to.
+    pub fn set_paint_modifier(
+        &mut self,
+        paint_modifier: impl Fn(TextureId, &mut Paint) + Send + 'static,
+    ) {
+        self.paint_modifier = Some(Box::new(paint_modifier));
+    }
+
+    /// Remove the modifier set with [`Self::set_paint_modifier`], for textures uploaded from now
+    /// on.
+    pub fn clear_paint_modifier(&mut self) {
+        self.paint_modifier = None;
+    }
+
+    /// Pack user textures whose width and height are at most `max_texture_side` pixels into a
+    /// shared atlas managed by the painter. This reduces shader switches and per-image overhead
+    /// in UIs that show many small icons.
+    ///
+    /// Only textures using [`TileMode::Clamp`] are packed. Atlas pages are sampled without
+    /// mipmaps, see [`TextureOptions::minification`](egui::TextureOptions::minification).
+    /// Changing `max_texture_side` rebuilds the atlas: packed textures that still fit are
+    /// packed again, the others get their own image. Passing `None` unpacks all textures.
+    pub fn set_texture_atlas(&mut self, max_texture_side: Option<usize>) {
+        let new_atlas = max_texture_side.map(TextureAtlas::new);
+        let unchanged = match (&self.atlas, &new_atlas) {
+            (Some(atlas), Some(new_atlas)) => {
+                atlas.max_texture_side() == new_atlas.max_texture_side()
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            return;
+        }
+
+        let packed = self
+            .paints
+            .iter()
+            .filter(|(_, handle)| handle.atlas_entry.is_some())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for tex_id in &packed {
+            self.unpack_texture(*tex_id);
+        }
+        self.atlas = new_atlas;
+
+        if let Some(atlas) = &mut self.atlas {
+            for tex_id in packed {
+                if let Some(handle) = self.paints.get_mut(&tex_id) {
+                    // The paint is rebuilt from the page in `update_atlas_paints`.
+                    handle.atlas_entry = atlas.insert(&handle.image);
+                }
+            }
+        }
+    }
+
+    /// Share the font atlas image with other painters using the same [`SharedFontAtlas`].
+    ///
+    /// Takes effect with the next full upload of the font texture, e.g. when painting the first
+    /// frame of a new context.
+    pub fn set_shared_font_atlas(&mut self, shared_font_atlas: Option<SharedFontAtlas>) {
+        self.release_shared_font_image();
+        self.shared_font_atlas = shared_font_atlas.map(|shared| (shared, None));
+    }
+
+    fn release_shared_font_image(&mut self) {
+        if let Some((shared, key)) = &mut self.shared_font_atlas {
+            if let Some(key) = key.take() {
+                shared.release(key);
+            }
+        }
+    }
+
+    /// Set the [`TileMode`] used when a mesh samples `tex_id` outside of the `[0, 1]` uv range.
+    ///
+    /// Defaults to the wrap mode of the texture's [`egui::TextureOptions`]. Use this to override
+    /// it, e.g. with [`TileMode::Decal`], which egui has no wrap mode for. Passing `None`
+    /// restores the default.
+    pub fn set_texture_tile_mode(&mut self, tex_id: TextureId, tile_mode: Option<TileMode>) {
+        match tile_mode {
+            Some(tile_mode) => {
+                self.tile_modes.insert(tex_id, tile_mode);
+            }
+            None => {
+                self.tile_modes.remove(&tex_id);
+            }
+        }
+
+        self.unpack_texture(tex_id);
+        if let Some(handle) = self.paints.get_mut(&tex_id) {
+            if let Some(paint) = Self::texture_paint(
+                &handle.image,
+                handle.sampling_options,
+                tile_mode.unwrap_or(handle.wrap_mode),
+            ) {
+                handle.paint = Self::modified_paint(&self.paint_modifier, tex_id, paint);
+            }
+        }
+    }
+
+    /// Upload a texture in a gpu compressed format and register it as an egui texture, which
+    /// needs a fraction of the memory of an uncompressed texture.
+    ///
+    /// `data` holds the compressed blocks of the base level. Skia supports ETC2 (RGB8) and BC1;
+    /// other formats like BC7 aren't supported by Skia. Returns `None` if the backend can't
+    /// create the texture.
+    ///
+    /// The painter hands out [`TextureId::User`] ids counting up from `0`, so don't use user ids
+    /// for anything else. Free the texture with [`Self::free_registered_texture`].
+    #[cfg(feature = "gpu")]
+    pub fn register_compressed_texture(
+        &mut self,
+        context: &mut DirectContext,
+        data: &[u8],
+        size: ISize,
+        compression: TextureCompressionType,
+    ) -> Option<TextureId> {
+        let image = skia_safe::gpu::images::texture_from_compressed_texture_data(
+            context,
+            Data::new_copy(data),
+            size,
+            compression,
+            Mipmapped::No,
+            Protected::No,
+        )?;
+
+        self.register_image_handle(
+            image,
+            skia_safe::SamplingOptions::new(
+                skia_safe::FilterMode::Linear,
+                skia_safe::MipmapMode::None,
+            ),
+            TileMode::Clamp,
+        )
+    }
+
+    /// Register an existing Skia image, e.g. a decoded photo or the texture of a gpu surface,
+    /// as an egui texture, without copying its pixels into an [`egui::ColorImage`].
+    ///
+    /// Show it with [`egui::Image`] and the returned id. The image is sampled and wrapped as
+    /// `options` ask for. Gpu images can only be drawn onto canvases of the same gpu context.
+    /// Returns `None` if Skia can't create a shader for the image. See
+    /// [`Self::register_compressed_texture`] for the ids, and free the texture with
+    /// [`Self::free_registered_texture`].
+    pub fn register_image(
+        &mut self,
+        image: Image,
+        options: egui::TextureOptions,
+    ) -> Option<TextureId> {
+        // The id isn't known yet, but is never the font texture's.
+        let sampling_options = self.sampling_options(TextureId::User(0), &options);
+        self.register_image_handle(image, sampling_options, Self::wrap_tile_mode(options.wrap_mode))
+    }
+
+    /// Show a decoded video frame as a texture, converting it from YUV to RGB with a shader
+    /// instead of on the cpu.
+    ///
+    /// Pass `None` as `tex_id` for the first frame, and the returned id for the following
+    /// frames, which replace the texture's image. With a direct context, see
+    /// [`Self::set_direct_context`], only the planes are uploaded, as a Skia YUVA image that
+    /// the gpu converts. Otherwise Skia's raster pipeline converts them with a shader, into a
+    /// texture that is reused as long as the frame size stays the same. Returns `None` if the
+    /// planes are smaller than their size and strides ask for, or Skia can't create the
+    /// texture, in which case the previous frame stays.
+    pub fn set_yuv_texture(
+        &mut self,
+        tex_id: Option<TextureId>,
+        planes: &crate::yuv::YuvPlanes,
+        options: egui::TextureOptions,
+    ) -> Option<TextureId> {
+        #[cfg(feature = "gpu")]
+        if let Some(context) = &mut self.direct_context {
+            if let Some(image) = crate::yuv::yuva_texture(context, planes) {
+                return self.set_user_image(tex_id, image, &options, None);
+            }
+        }
+
+        let shader = crate::yuv::yuv_shader(planes, |image| self.upload_image(image))?;
+        let size = ISize::new(planes.width, planes.height);
+
+        let previous_surface = tex_id
+            .and_then(|tex_id| self.paints.get_mut(&tex_id))
+            .and_then(|handle| {
+                let same_size = handle.surface.as_ref().is_some_and(|surface| {
+                    surface.width() == size.width && surface.height() == size.height
+                });
+                handle.surface.take().filter(|_| same_size)
+            });
+        let mut surface = match previous_surface {
+            Some(surface) => surface,
+            None => self.texture_surface(&ImageInfo::new_n32_premul(
+                size,
+                Some(ColorSpace::new_srgb()),
+            ))?,
+        };
+        let mut paint = Paint::default();
+        paint.set_shader(shader);
+        paint.set_blend_mode(BlendMode::Src);
+        surface.canvas().draw_paint(&paint);
+        let image = surface.image_snapshot();
+
+        self.set_user_image(tex_id, image, &options, Some(surface))
+    }
+
+    /// Create or replace a texture from pixels in memory, e.g. frames of a screen capture,
+    /// without converting them to an [`egui::ColorImage`] first.
+    ///
+    /// `size` is `[width, height]` in pixels. `pixels` holds `height` rows of `stride` bytes
+    /// each, of which the first `width` pixels are used, so padded rows can be passed as they
+    /// are; the last row may end right after its pixels. Pass `None` as `tex_id` to create a
+    /// texture, and the returned id to replace its image later. The pixels are copied once,
+    /// and uploaded if the painter has a direct context. Returns `None` if `pixels` is too
+    /// short or `stride` too small for a row, in which case the previous image stays.
+    pub fn set_texture_from_raw(
+        &mut self,
+        tex_id: Option<TextureId>,
+        pixels: &[u8],
+        size: [usize; 2],
+        stride: usize,
+        format: RawFormat,
+        options: egui::TextureOptions,
+    ) -> Option<TextureId> {
+        let color_type = match format {
+            RawFormat::Rgba8 => ColorType::RGBA8888,
+            RawFormat::Bgra8 => ColorType::BGRA8888,
+            RawFormat::Alpha8 => ColorType::Alpha8,
+        };
+        let info = ImageInfo::new(
+            ISize::new(size[0].try_into().ok()?, size[1].try_into().ok()?),
+            color_type,
+            AlphaType::Premul,
+            Some(ColorSpace::new_srgb()),
+        );
+        let row_len = size[0].checked_mul(info.bytes_per_pixel())?;
+        if stride < row_len {
+            return None;
+        }
+        let len = match size[1].checked_sub(1) {
+            Some(rows) => stride.checked_mul(rows)?.checked_add(row_len)?,
+            None => 0,
+        };
+        let pixels = pixels.get(..len)?;
+        let image = images::raster_from_data(&info, Data::new_copy(pixels), stride)?;
+
+        let image = self.upload_image(image);
+        self.set_user_image(tex_id, image, &options, None)
+    }
+
+    /// Store `image` as the texture `tex_id`, or under a new [`TextureId::User`] id if `tex_id`
+    /// is `None`.
+    fn set_user_image(
+        &mut self,
+        tex_id: Option<TextureId>,
+        image: Image,
+        options: &egui::TextureOptions,
+        surface: Option<Surface>,
+    ) -> Option<TextureId> {
+        let tex_id = tex_id.unwrap_or_else(|| {
+            self.next_user_texture_id += 1;
+            TextureId::User(self.next_user_texture_id - 1)
+        });
+        let sampling_options = self.sampling_options(tex_id, options);
+        let wrap_mode = Self::wrap_tile_mode(options.wrap_mode);
+        let paint = Self::modified_paint(
+            &self.paint_modifier,
+            tex_id,
+            Self::texture_paint(&image, sampling_options, wrap_mode)?,
+        );
+        self.evicted_textures.remove(&tex_id);
+        self.remove_paint_handle(tex_id);
+        self.paints.insert(
+            tex_id,
+            PaintHandle {
+                paint,
+                image,
+                sampling_options,
+                wrap_mode,
+                surface,
+                atlas_entry: None,
+            },
+        );
+        Some(tex_id)
+    }
+
+    /// Free a texture registered with the painter.
+    pub fn free_registered_texture(&mut self, tex_id: TextureId) {
+        self.free_texture(tex_id);
+    }
+
+    /// Store an image under a new [`TextureId::User`] id. Registered images are never packed
+    /// into the atlas.
+    fn register_image_handle(
+        &mut self,
+        image: Image,
+        sampling_options: skia_safe::SamplingOptions,
+        wrap_mode: TileMode,
+    ) -> Option<TextureId> {
+        let paint = Self::texture_paint(&image, sampling_options, wrap_mode)?;
+        let tex_id = TextureId::User(self.next_user_texture_id);
+        self.next_user_texture_id += 1;
+        let paint = Self::modified_paint(&self.paint_modifier, tex_id, paint);
+
+        self.paints.insert(
+            tex_id,
+            PaintHandle {
+                paint,
+                image,
+                sampling_options,
+                wrap_mode,
+                surface: None,
+                atlas_entry: None,
+            },
+        );
+        Some(tex_id)
+    }
+
+    /// The image of the texture `tex_id`, e.g. to inspect the font atlas (the texture
+    /// [`TextureId::default`]) in developer tools.
+    ///
+    /// This is the texture's own image, also if it is packed into the texture atlas.
+    pub fn image(&self, tex_id: TextureId) -> Option<&Image> {
+        self.paints.get(&tex_id).map(|handle| &handle.image)
+    }
+
+    /// The ids and sizes in pixels of all textures the painter holds, in no particular order.
+    pub fn textures(&self) -> impl Iterator<Item = (TextureId, ISize)> + '_ {
+        self.paints
+            .iter()
+            .map(|(tex_id, handle)| (*tex_id, handle.image.dimensions()))
+    }
+
+    /// Set the [`BlendMode`] meshes using `tex_id` are composited onto the canvas with, e.g.
+    /// [`BlendMode::Plus`] for glow, particle or heatmap effects.
+    ///
+    /// Defaults to [`BlendMode::SrcOver`]. Passing `None` restores the default. Use a dedicated
+    /// texture (e.g. a white 1x1 image) to give untextured meshes a blend mode.
+    pub fn set_texture_blend_mode(&mut self, tex_id: TextureId, blend_mode: Option<BlendMode>) {
+        match blend_mode {
+            Some(blend_mode) => {
+                self.blend_modes.insert(tex_id, blend_mode);
+            }
+            None => {
+                self.blend_modes.remove(&tex_id);
+            }
+        }
+    }
+
+    /// Move a texture out of the atlas and back into its own image.
+    fn unpack_texture(&mut self, tex_id: TextureId) {
+        let Some(handle) = self.paints.get_mut(&tex_id) else {
+            return;
+        };
+        if let Some(entry) = handle.atlas_entry.take() {
+            if let Some(paint) =
+                Self::texture_paint(&handle.image, handle.sampling_options, TileMode::Clamp)
+            {
+                handle.paint = Self::modified_paint(&self.paint_modifier, tex_id, paint);
+            }
+            if let Some(atlas) = &mut self.atlas {
+                atlas.remove(entry);
+            }
+        }
+    }
+
+    /// Rebuild the paints of packed textures whose atlas page changed.
+    fn update_atlas_paints(&mut self) {
+        let Some(atlas) = &mut self.atlas else {
+            return;
+        };
+
+        let dirty_pages = atlas.snapshot_dirty_pages();
+        if dirty_pages.is_empty() {
+            return;
+        }
+
+        for (tex_id, handle) in self.paints.iter_mut() {
+            let Some(entry) = handle.atlas_entry else {
+                continue;
+            };
+            if dirty_pages.contains(&entry.page) {
+                if let Some(paint) = atlas.page_image(entry.page).and_then(|page_image| {
+                    let sampling_options = crate::atlas::page_sampling(handle.sampling_options);
+                    Self::texture_paint(page_image, sampling_options, TileMode::Clamp)
+                }) {
+                    handle.paint = Self::modified_paint(&self.paint_modifier, *tex_id, paint);
+                }
+            }
+        }
+    }
+
+    /// Draw the kinds of primitives the painter produces (textured, colored and clipped
+    /// meshes, shapes, images and layers) to an offscreen surface compatible with `canvas`.
+    ///
+    /// On gpu canvases this makes Skia compile its pipelines at startup instead of during the
+    /// first frames the user interacts with. The work is flushed and submitted right away.
+    pub fn warmup(&mut self, canvas: &Canvas) {
+        let info = ImageInfo::new_n32_premul((64, 64), None);
+        let Some(mut surface) = canvas.new_surface(&info, None) else {
+            return;
+        };
+        let offscreen = surface.canvas();
+
+        let texture = {
+            let pixels = (0..16u32)
+                .flat_map(|i| [(i * 16) as u8, 255 - (i * 16) as u8, 128, 255])
+                .collect::<Vec<_>>();
+            images::raster_from_data(
+                &ImageInfo::new_n32_premul((4, 4), None),
+                Data::new_copy(&pixels),
+                16,
+            )
+        };
+        let Some(texture) = texture else {
+            return;
+        };
+        let sampling_options = skia_safe::SamplingOptions::new(
+            skia_safe::FilterMode::Linear,
+            skia_safe::MipmapMode::None,
+        );
+        let Some(texture_paint) = Self::texture_paint(&texture, sampling_options, TileMode::Clamp)
+        else {
+            return;
+        };
+
+        let positions = [
+            Point::new(4.0, 4.0),
+            Point::new(60.0, 4.0),
+            Point::new(4.0, 60.0),
+            Point::new(60.0, 60.0),
+        ];
+        let uvs = [
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 0.0),
+            Point::new(0.0, 1.0),
+            Point::new(1.0, 1.0),
+        ];
+        let colors = [Color::WHITE, Color::RED, Color::GREEN, Color::from_argb(128, 0, 0, 255)];
+        let vertices = Vertices::new_copy(
+            VertexMode::Triangles,
+            &positions,
+            &uvs,
+            &colors,
+            Some(&[0, 1, 2, 2, 1, 3][..]),
+        );
+
+        match &self.hdr_options {
+            Some(hdr_options) => {
+                offscreen.save_layer(&SaveLayerRec::default().paint(&hdr_options.layer_paint()));
+            }
+            None => {
+                offscreen.save_layer(&SaveLayerRec::default());
+            }
+        }
+
+        // Textured and clipped meshes.
+        offscreen.draw_vertices(&vertices, BlendMode::Modulate, &texture_paint);
+        offscreen.save();
+        offscreen.clip_rect(Rect::new(8.5, 8.5, 40.5, 40.5), ClipOp::default(), true);
+        offscreen.draw_vertices(&vertices, BlendMode::Modulate, &self.white_paint_workaround);
+        offscreen.restore();
+
+        // The solid color and image fast paths.
+        let mut solid_paint = Paint::default();
+        solid_paint.set_anti_alias(true);
+        solid_paint.set_color(Color::from_argb(200, 30, 30, 30));
+        offscreen.draw_rect(Rect::new(2.0, 2.0, 30.0, 20.0), &solid_paint);
+        offscreen.draw_path(
+            &Path::polygon(&positions[..3], true, None, None),
+            &solid_paint,
+        );
+        offscreen.draw_image_rect_with_sampling_options(
+            &texture,
+            None,
+            Rect::new(32.0, 32.0, 48.0, 48.0),
+            sampling_options,
+            &Paint::default(),
+        );
+
+        // Paint callbacks are drawn as drawables.
+        let mut recorder = PictureRecorder::new();
+        let recording = recorder.begin_recording(Rect::new(0.0, 0.0, 16.0, 16.0), false);
+        recording.draw_circle((8.0, 8.0), 6.0, &solid_paint);
+        if let Some(mut drawable) = recorder.finish_recording_as_drawable() {
+            drawable.draw(offscreen, None);
+        }
+
+        offscreen.restore();
+
+        #[cfg(feature = "gpu")]
+        if let Some(mut context) = surface.direct_context() {
+            context.flush_and_submit();
+        }
+    }
+
+    /// Apply `textures_delta` and paint `primitives` onto `canvas`.
+    ///
+    /// Textures and callbacks that fail are skipped, use
+    /// [`Self::try_paint_and_update_textures`] to find out about them. Returns what was painted
+    /// and how long it took.
+    pub fn paint_and_update_textures(
+        &mut self,
+        canvas: &Canvas,
+        dpi: f32,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> PaintStats {
+        let _ = self.try_paint_and_update_textures(canvas, dpi, primitives, textures_delta);
+        self.stats.clone()
+    }
+
+    /// Like [`Self::paint_and_update_textures`], but returns the first error that occurred.
+    ///
+    /// The rest of the frame is still painted, so the host can log the error and carry on.
+    pub fn try_paint_and_update_textures(
+        &mut self,
+        canvas: &Canvas,
+        dpi: f32,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Result<(), EguiSkiaError> {
+        profile_scope!("egui_skia::paint_and_update_textures");
+        let textures = self.update_textures(&textures_delta, &primitives);
+        let frame_info = Self::frame_info(canvas, dpi, &primitives);
+        self.begin_frame(canvas, &frame_info);
+        let painted = self.paint_primitives(canvas, Point::default(), dpi, primitives);
+        self.end_frame(canvas, &frame_info, &textures_delta);
+        textures.and(painted)
+    }
+
+    /// The [`PaintStats`] of the last painted frame.
+    pub fn stats(&self) -> &PaintStats {
+        &self.stats
+    }
+
+    /// The errors of all paint callbacks that failed in the last painted frame, see
+    /// [`EguiSkiaPaintCallback::try_new`].
+    pub fn callback_errors(&self) -> &[EguiSkiaError] {
+        &self.callback_errors
+    }
+
+    /// Record a frame into a [`Picture`] instead of drawing it onto a canvas.
+    ///
+    /// `bounds` is the cull rect of the picture in pixels, usually the size of the screen. The
+    /// picture can be replayed any number of times, e.g. as part of a retained scene. It keeps
+    /// references to the images it draws, so it stays valid after the textures are freed.
+    pub fn paint_to_picture(
+        &mut self,
+        bounds: Rect,
+        dpi: f32,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Picture {
+        let mut recorder = PictureRecorder::new();
+        let canvas = recorder.begin_recording(bounds, false);
+        self.paint_and_update_textures(canvas, dpi, primitives, textures_delta);
+        recorder
+            .finish_recording_as_picture(None)
+            .expect("Recording was started")
+    }
+
+    /// Record a frame like [`Self::paint_to_picture`] and write it to `path` as a `.skp` file,
+    /// with the images it draws embedded.
+    ///
+    /// The file can be opened in Skia's debugger (<https://debugger.skia.org>) or `viewer`, to
+    /// inspect every draw call, clip and blend mode of the frame.
+    pub fn paint_to_skp(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        bounds: Rect,
+        dpi: f32,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> std::io::Result<()> {
+        let picture = self.paint_to_picture(bounds, dpi, primitives, textures_delta);
+        std::fs::write(path, picture.serialize().as_bytes())
+    }
+
+    /// Paint the same frame twice, once per eye, e.g. to feed a VR/AR compositor's quad layer.
+    ///
+    /// The primitives are only tessellated once by the caller. Each copy is clipped to its
+    /// [`EyeView::target`]. Both copies are one frame, e.g. the hooks run once and textures are
+    /// freed like after [`Self::paint_and_update_textures`]. Returns the first error that
+    /// occurred, like [`Self::try_paint_and_update_textures`].
+    pub fn paint_stereo(
+        &mut self,
+        canvas: &Canvas,
+        dpi: f32,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+        eyes: [EyeView; 2],
+    ) -> Result<(), EguiSkiaError> {
+        let textures = self.update_textures(&textures_delta, &primitives);
+        let frame_info = Self::frame_info(canvas, dpi, &primitives);
+        self.begin_frame(canvas, &frame_info);
+
+        let mut painted = Ok(());
+        let [left, right] = eyes;
+        for (eye, primitives) in [(left, primitives.clone()), (right, primitives)] {
+            let save_count = canvas.save();
+            canvas.reset_matrix();
+            canvas.clip_rect(eye.target, ClipOp::default(), true);
+            let offset = Point::new(
+                eye.target.left + eye.offset.x * dpi,
+                eye.target.top + eye.offset.y * dpi,
+            );
+            painted = painted.and(self.paint_primitives(canvas, offset, dpi, primitives));
+            canvas.restore_to_count(save_count);
+        }
+
+        self.end_frame(canvas, &frame_info, &textures_delta);
+        textures.and(painted)
+    }
+
+    /// Paint a frame into a vector pdf document written to `writer`.
+    ///
+    /// `page_size` is in pdf points (1/72 inch), and one egui point becomes one pdf point, so
+    /// tessellate the primitives with a `pixels_per_point` of 1. Content that is taller than a
+    /// page continues on the next pages.
+    ///
+    /// All pages are one frame: the pre-paint hook runs on the first page and the post-paint hook
+    /// on the last one. The whole document is written even if an error occurred, the first error
+    /// is returned like by [`Self::try_paint_and_update_textures`].
+    #[cfg(feature = "pdf")]
+    pub fn paint_to_pdf(
+        &mut self,
+        writer: &mut impl std::io::Write,
+        page_size: skia_safe::Size,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Result<(), EguiSkiaError> {
+        let content_bottom = primitives
+            .iter()
+            .map(|primitive| {
+                let bounds = match &primitive.primitive {
+                    Primitive::Mesh(mesh) => mesh.calc_bounds(),
+                    Primitive::Callback(callback) => callback.rect,
+                };
+                bounds.intersect(primitive.clip_rect).max.y
+            })
+            .fold(0.0, f32::max);
+        let pages = (content_bottom / page_size.height).ceil().max(1.0) as usize;
+
+        let textures = self.update_textures(&textures_delta, &primitives);
+
+        let mut painted = Ok(());
+        let mut document = skia_safe::pdf::new_document(writer, None);
+        for page in 0..pages {
+            let mut page_document = document.begin_page(page_size, None);
+            let canvas = page_document.canvas();
+            let frame_info = Self::frame_info(canvas, 1.0, &primitives);
+            if page == 0 {
+                self.begin_frame(canvas, &frame_info);
+            }
+            let offset = Point::new(0.0, -(page as f32) * page_size.height);
+            painted = painted.and(self.paint_primitives(canvas, offset, 1.0, primitives.clone()));
+            if page + 1 == pages {
+                self.end_frame(canvas, &frame_info, &textures_delta);
+            }
+            document = page_document.end_page();
+        }
+        document.close();
+
+        textures.and(painted)
+    }
+
+    /// Paint a frame into an svg document of `size` egui points and return it.
+    ///
+    /// Like with [`Self::paint_to_pdf`], one egui point becomes one svg unit. Meshes are
+    /// exported as polygons and textures as embedded images. Fails with the first error that
+    /// occurred, see [`Self::try_paint_and_update_textures`].
+    #[cfg(feature = "svg")]
+    pub fn paint_to_svg(
+        &mut self,
+        size: egui::Vec2,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Result<String, EguiSkiaError> {
+        let textures = self.update_textures(&textures_delta, &primitives);
+
+        let canvas = skia_safe::svg::Canvas::new(Rect::from_wh(size.x, size.y), None);
+        let frame_info = Self::frame_info(&canvas, 1.0, &primitives);
+        self.begin_frame(&canvas, &frame_info);
+        let painted = self.paint_primitives(&canvas, Point::default(), 1.0, primitives);
+        self.end_frame(&canvas, &frame_info, &textures_delta);
+        let data = canvas.end();
+
+        textures.and(painted)?;
+        Ok(String::from_utf8_lossy(data.as_bytes()).into_owned())
+    }
+
+    /// Paint egui's shapes before tessellation, drawing rects, circles, ellipses, lines, paths
+    /// and curves with native Skia geometry and antialiasing, and text with Skia's font
+    /// rendering if a [`FontRegistry`] is set.
+    ///
+    /// The output is resolution independent, e.g. when the canvas is scaled or recorded into a
+    /// picture or pdf. Shapes without a native equivalent, like meshes, are tessellated with the
+    /// default [`TessellationOptions`] and painted as usual, in the order of `shapes`. Pass the
+    /// shapes of [`egui::FullOutput`] instead of tessellating them.
+    pub fn paint_native_shapes(
+        &mut self,
+        canvas: &Canvas,
+        dpi: f32,
+        shapes: Vec<ClippedShape>,
+        textures_delta: TexturesDelta,
+    ) -> PaintStats {
+        let font_tex_size = self.font_tex_size(&textures_delta);
+        let mut tessellator =
+            Tessellator::new(dpi, TessellationOptions::default(), font_tex_size, Vec::new());
+
+        let mut primitives = Vec::new();
+        for ClippedShape { clip_rect, shape } in shapes {
+            let native = match &shape {
+                egui::Shape::Text(_) => self.font_registry.is_some(),
+                egui::Shape::Rect(rect) if rect.blur_width > 0.0 => {
+                    self.options.native_shadows && rect.brush.is_none()
+                }
+                shape => crate::vector::is_native(shape),
+            };
+            if native {
+                // Drawn by `paint_primitives` in the order of the other primitives.
+                primitives.push(ClippedPrimitive {
+                    clip_rect,
+                    primitive: Primitive::Callback(egui::PaintCallback {
+                        rect: clip_rect,
+                        callback: std::sync::Arc::new(NativeShape(shape)),
+                    }),
+                });
+            } else {
+                tessellator
+                    .tessellate_clipped_shape(ClippedShape { clip_rect, shape }, &mut primitives);
+            }
+        }
+
+        self.paint_and_update_textures(canvas, dpi, primitives, textures_delta)
+    }
+
+    /// Tessellate egui's shapes with `tessellation_options` and paint them, instead of
+    /// tessellating them with [`egui::Context::tessellate`] first.
+    ///
+    /// Gives one place to tune the tessellation per surface, e.g. a wider feathering on raster
+    /// surfaces, or no feathering on multisampled gpu surfaces, which antialias on their own.
+    /// Pass the shapes of [`egui::FullOutput`].
+    pub fn paint_shapes(
+        &mut self,
+        canvas: &Canvas,
+        dpi: f32,
+        shapes: Vec<ClippedShape>,
+        tessellation_options: TessellationOptions,
+        textures_delta: TexturesDelta,
+    ) -> PaintStats {
+        let font_tex_size = self.font_tex_size(&textures_delta);
+        let primitives = Tessellator::new(dpi, tessellation_options, font_tex_size, Vec::new())
+            .tessellate_shapes(shapes);
+
+        self.paint_and_update_textures(canvas, dpi, primitives, textures_delta)
+    }
+
+    /// The size of the font texture after applying `textures_delta`, for the tessellator.
+    fn font_tex_size(&self, textures_delta: &TexturesDelta) -> [usize; 2] {
+        textures_delta
+            .set
+            .iter()
+            .rev()
+            .find(|(id, delta)| *id == TextureId::default() && delta.pos.is_none())
+            .map(|(_, delta)| delta.image.size())
+            .or_else(|| {
+                self.image(TextureId::default()).map(|image| {
+                    [image.width() as usize, image.height() as usize]
+                })
+            })
+            .unwrap_or([1, 1])
+    }
+
+    fn update_textures(
+        &mut self,
+        textures_delta: &TexturesDelta,
+        primitives: &[ClippedPrimitive],
+    ) -> Result<(), EguiSkiaError> {
+        // Every frame starts with its texture updates.
+        self.stats = PaintStats::default();
+        let start = Instant::now();
+
+        // Group the deltas by texture, keeping their order.
+        let mut grouped: Vec<(TextureId, Vec<&egui::epaint::ImageDelta>)> = Vec::new();
+        let mut indices = AHashMap::new();
+        for (id, image_delta) in &textures_delta.set {
+            let index = *indices.entry(*id).or_insert_with(|| {
+                grouped.push((*id, Vec::new()));
+                grouped.len() - 1
+            });
+            grouped[index].1.push(image_delta);
+        }
+
+        let mut result = self.install_converted_textures();
+        if self.upload_budget.is_none() && self.pending_uploads.is_empty() {
+            for (id, deltas) in grouped {
+                // Keep uploading the other textures, but report the first error.
+                result = result.and(self.set_or_submit_texture(id, &deltas));
+            }
+        } else {
+            result = self.upload_within_budget(grouped, primitives);
+        }
+        self.update_atlas_paints();
+        self.stats.textures_time = start.elapsed();
+        result
+    }
+
+    /// Queue the deltas behind earlier deferred ones and upload as many as the budget allows.
+    fn upload_within_budget(
+        &mut self,
+        grouped: Vec<(TextureId, Vec<&ImageDelta>)>,
+        primitives: &[ClippedPrimitive],
+    ) -> Result<(), EguiSkiaError> {
+        for (id, deltas) in grouped {
+            let deltas = deltas.into_iter().cloned();
+            match self.pending_uploads.iter_mut().find(|(pending, _)| *pending == id) {
+                Some((_, pending)) => pending.extend(deltas),
+                None => self.pending_uploads.push((id, deltas.collect())),
+            }
+        }
+
+        let visible = primitives
+            .iter()
+            .filter_map(|primitive| match &primitive.primitive {
+                Primitive::Mesh(mesh) => Some(mesh.texture_id),
+                Primitive::Callback(_) => None,
+            })
+            .collect::<AHashSet<_>>();
+        // Stable, so textures keep their upload order within a priority.
+        self.pending_uploads.sort_by_key(|(id, _)| {
+            if *id == TextureId::default() {
+                0
+            } else if visible.contains(id) {
+                1
+            } else {
+                2
+            }
+        });
+
+        let budget = self.upload_budget.unwrap_or(usize::MAX);
+        let mut uploaded = 0;
+        let mut result = Ok(());
+        while let Some((_, deltas)) = self.pending_uploads.first() {
+            let size = deltas
+                .iter()
+                .map(|delta| delta.image.width() * delta.image.height() * 4)
+                .sum::<usize>();
+            if uploaded > 0 && uploaded + size > budget {
+                break;
+            }
+
+            let (id, deltas) = self.pending_uploads.remove(0);
+            result = result.and(
+                self.set_or_submit_texture(id, &deltas.iter().collect::<Vec<_>>()),
+            );
+            uploaded += size;
+        }
+        result
+    }
+
+    /// Hand the deltas of a texture to the upload worker if they replace it, or apply them
+    /// right away. Deltas that leave the texture's data as it is are skipped.
+    fn set_or_submit_texture(
+        &mut self,
+        tex_id: TextureId,
+        deltas: &[&ImageDelta],
+    ) -> Result<(), EguiSkiaError> {
+        let previous_key = self.texture_keys.get(&tex_id).copied();
+        let key = SharedFontAtlas::key(previous_key, deltas);
+        let exists = self.paints.contains_key(&tex_id)
+            || self
+                .upload_worker
+                .as_ref()
+                .is_some_and(|worker| worker.is_converting(tex_id));
+        if key.is_some() && key == previous_key && exists {
+            self.stats.textures_unchanged += 1;
+            return Ok(());
+        }
+        match key {
+            Some(key) => self.texture_keys.insert(tex_id, key),
+            None => self.texture_keys.remove(&tex_id),
+        };
+
+        let result = self.submit_texture(tex_id, deltas);
+        if result.is_err() {
+            self.texture_keys.remove(&tex_id);
+        }
+        result
+    }
+
+    fn submit_texture(
+        &mut self,
+        tex_id: TextureId,
+        deltas: &[&ImageDelta],
+    ) -> Result<(), EguiSkiaError> {
+        let Some(worker) = &mut self.upload_worker else {
+            return self.set_texture(tex_id, deltas);
+        };
+        if tex_id == TextureId::default() {
+            return self.set_texture(tex_id, deltas);
+        }
+
+        match deltas.iter().rposition(|delta| delta.pos.is_none()) {
+            Some(index) => {
+                let deltas = deltas[index..].iter().map(|delta| (*delta).clone());
+                worker.submit(tex_id, deltas.collect());
+                Ok(())
+            }
+            None => {
+                let patches = deltas.iter().map(|delta| (*delta).clone()).collect();
+                match worker.defer_patches(tex_id, patches) {
+                    Some(_) => self.set_texture(tex_id, deltas),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Replace the textures the upload worker finished converting.
+    fn install_converted_textures(&mut self) -> Result<(), EguiSkiaError> {
+        let Some(worker) = &mut self.upload_worker else {
+            return Ok(());
+        };
+        let mut result = Ok(());
+        for converted in worker.finished() {
+            let tex_id = converted.tex_id;
+            let Some(image) = Self::pixels_image(converted.size, converted.pixels) else {
+                result = result.and(Err(EguiSkiaError::UnsupportedImage(tex_id)));
+                continue;
+            };
+            self.stats.textures_set += 1;
+            self.stats.bytes_uploaded += converted.size[0] * converted.size[1] * 4;
+
+            let image = self.upload_image(image);
+            result = result.and(self.insert_texture(tex_id, image, &converted.options, None));
+            if !converted.patches.is_empty() {
+                let patches = converted.patches.iter().collect::<Vec<_>>();
+                result = result.and(self.set_texture(tex_id, &patches));
+            }
+        }
+        result
+    }
+
+    fn free_textures(&mut self, textures_delta: &TexturesDelta) {
+        let release_after = self.frame + self.texture_free_delay;
+        self.deferred_frees
+            .extend(textures_delta.free.iter().map(|id| (release_after, *id)));
+
+        let frame = self.frame;
+        let (free, deferred): (Vec<_>, Vec<_>) = std::mem::take(&mut self.deferred_frees)
+            .into_iter()
+            .partition(|(release_after, _)| *release_after <= frame);
+        self.deferred_frees = deferred;
+
+        free.iter().for_each(|(_, id)| {
+            self.free_texture(*id);
+        });
+        self.stats.textures_freed += free.len();
+    }
+
+    fn frame_info(canvas: &Canvas, dpi: f32, primitives: &[ClippedPrimitive]) -> FrameInfo {
+        FrameInfo {
+            pixels_per_point: dpi,
+            num_primitives: primitives.len(),
+            canvas_size: canvas.base_layer_size(),
+        }
+    }
+
+    /// Start a frame: advance the frame counter, rotate the vertex cache and run the pre-paint
+    /// hook. Frames painted several times, e.g. once per eye, are started only once.
+    fn begin_frame(&mut self, canvas: &Canvas, frame_info: &FrameInfo) {
+        self.callback_errors.clear();
+        self.frame += 1;
+
+        // Vertices that weren't used in the previous frame are dropped.
+        self.previous_vertices_cache = std::mem::take(&mut self.vertices_cache);
+
+        if self.options.overlay {
+            canvas.clear(Color::TRANSPARENT);
+        }
+        if let Some(hook) = &self.pre_paint_hook {
+            hook(canvas, frame_info);
+        }
+    }
+
+    /// Finish a frame started with [`Self::begin_frame`]: run the post-paint hook and free the
+    /// textures of `textures_delta`.
+    fn end_frame(
+        &mut self,
+        canvas: &Canvas,
+        frame_info: &FrameInfo,
+        textures_delta: &TexturesDelta,
+    ) {
+        if let Some(hook) = &self.post_paint_hook {
+            hook(canvas, frame_info);
+        }
+        self.free_textures(textures_delta);
+        self.evict_textures();
+    }
+
+    /// Paint `primitives` onto `canvas`, with the ui's origin at `offset` pixels from the top
+    /// left corner of the canvas. Call it between [`Self::begin_frame`] and
+    /// [`Self::end_frame`].
+    fn paint_primitives(
+        &mut self,
+        canvas: &Canvas,
+        offset: Point,
+        dpi: f32,
+        primitives: Vec<ClippedPrimitive>,
+    ) -> Result<(), EguiSkiaError> {
+        let mut result = Ok(());
+        let start = Instant::now();
+        let viewport_size_px = canvas.base_layer_size();
+
+        // See `PainterOptions::gamma_blending` and `PainterOptions::supersampling`.
+        let supersampling = self.options.supersampling.filter(|factor| *factor > 1);
+        let mut offscreen = if self.options.gamma_blending || supersampling.is_some() {
+            Self::offscreen_surface(
+                canvas,
+                supersampling.unwrap_or(1),
+                self.options.gamma_blending,
+            )
+        } else {
+            None
+        };
+        let target = match &mut offscreen {
+            Some((surface, _)) => surface.canvas(),
+            None => canvas,
+        };
+
+        let save_count = target.save_count();
+        // Offscreen surfaces are blended onto the canvas as a whole, see below.
+        let compositing_blend_mode = self.compositing_blend_mode.filter(|_| offscreen.is_none());
+        match (&self.hdr_options, compositing_blend_mode) {
+            (None, None) => {
+                target.save();
+            }
+            (hdr_options, blend_mode) => {
+                let mut paint = hdr_options
+                    .as_ref()
+                    .map(HdrOptions::layer_paint)
+                    .unwrap_or_default();
+                if let Some(blend_mode) = blend_mode {
+                    paint.set_blend_mode(blend_mode);
+                }
+                target.save_layer(&SaveLayerRec::default().paint(&paint));
+            }
+        }
+        // The ui is painted in pixels of the canvas, regardless of the canvas' matrix.
+        let scale = offscreen.as_ref().map_or(1.0, |(_, factor)| *factor as f32);
+        target.set_matrix(skia_safe::M44::new_identity().set_scale(scale, scale, 1.0));
+        target.translate(offset);
+        if let Some(transform) = &self.transform {
+            target.concat(transform);
+        }
+        target.scale((dpi, dpi));
+        if let Some(mask) = &self.output_mask {
+            target.clip_path(mask, ClipOp::Intersect, true);
         }
-    }
 
-    pub fn paint_and_update_textures(
-        &mut self,
-        canvas: &Canvas,
-        dpi: f32,
-        primitives: Vec<ClippedPrimitive>,
-        textures_delta: TexturesDelta,
-    ) {
-        textures_delta.set.iter().for_each(|(id, image_delta)| {
-            self.set_texture(*id, image_delta);
-        });
+        // The part of the canvas that can be drawn to, in points. Primitives outside of it,
+        // e.g. of a large virtual ui painted into a small tile, are skipped before converting
+        // their vertices.
+        let visible = target
+            .local_clip_bounds()
+            .map(|bounds| {
+                egui::Rect::from_min_max(
+                    Pos2::new(bounds.left, bounds.top),
+                    Pos2::new(bounds.right, bounds.bottom),
+                )
+            })
+            .unwrap_or(egui::Rect::NOTHING);
 
         for primitive in primitives {
+            let clip_rect = match self.global_clip {
+                Some(global_clip) => primitive.clip_rect.intersect(global_clip),
+                None => primitive.clip_rect,
+            };
+            if !clip_rect.is_positive() {
+                continue;
+            }
+            if !clip_rect.intersects(visible) {
+                self.stats.culled += 1;
+                continue;
+            }
+
             let skclip_rect = Rect::new(
-                primitive.clip_rect.min.x,
-                primitive.clip_rect.min.y,
-                primitive.clip_rect.max.x,
-                primitive.clip_rect.max.y,
+                clip_rect.min.x,
+                clip_rect.min.y,
+                clip_rect.max.x,
+                clip_rect.max.y,
             );
 
             match primitive.primitive {
                 Primitive::Mesh(mesh) => {
-                    canvas.set_matrix(skia_safe::M44::new_identity().set_scale(dpi, dpi, 1.0));
-                    let arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
-
-                    #[cfg(feature = "cpu_fix")]
-                    let meshes = mesh
-                        .split_to_u16()
-                        .into_iter()
-                        .flat_map(|mesh| self.split_texture_meshes(mesh))
-                        .collect::<Vec<Mesh16>>();
-                    #[cfg(not(feature = "cpu_fix"))]
+                    let arc = skia_safe::AutoCanvasRestore::guard(target, true);
+
                     let meshes = mesh.split_to_u16();
 
                     for mesh in &meshes {
+                        self.stats.meshes += 1;
+                        self.stats.vertices += mesh.vertices.len();
+                        self.stats.indices += mesh.indices.len();
                         self.paint_mesh(&arc, &skclip_rect, mesh);
                     }
                 }
                 Primitive::Callback(data) => {
-                    let callback: Arc<EguiSkiaPaintCallback> = data.callback.downcast().unwrap();
+                    if let Some(NativeShape(shape)) = data.callback.downcast_ref::<NativeShape>() {
+                        let arc = skia_safe::AutoCanvasRestore::guard(target, true);
+                        arc.clip_rect(skclip_rect, ClipOp::default(), true);
+                        match (shape, &self.font_registry) {
+                            (egui::Shape::Text(text), Some(fonts)) => {
+                                crate::text::draw_text(&arc, text, fonts, dpi);
+                            }
+                            (shape, _) => crate::vector::draw_shape(&arc, shape, dpi),
+                        }
+                        continue;
+                    }
+
+                    let Ok(callback) = data.callback.downcast::<EguiSkiaPaintCallback>() else {
+                        self.callback_errors.push(EguiSkiaError::BadCallback);
+                        result = result.and(Err(EguiSkiaError::BadCallback));
+                        continue;
+                    };
+                    self.stats.callbacks += 1;
                     let rect = data.rect;
 
                     let skia_rect = Rect::new(
@@ -78,222 +1858,850 @@ impl Painter {
                         rect.max.x * dpi,
                         rect.max.y * dpi,
                     );
+                    let info = PaintCallbackInfo {
+                        rect,
+                        rect_px: skia_rect,
+                        clip_rect_px: Rect::new(
+                            skclip_rect.left * dpi,
+                            skclip_rect.top * dpi,
+                            skclip_rect.right * dpi,
+                            skclip_rect.bottom * dpi,
+                        ),
+                        pixels_per_point: dpi,
+                        viewport_size_px,
+                    };
 
-                    let mut drawable: Drawable = callback.callback.deref()(skia_rect).0.into_inner();
-                    let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
-
+                    let mut arc = skia_safe::AutoCanvasRestore::guard(target, true);
                     arc.clip_rect(skclip_rect, ClipOp::default(), true);
-                    arc.translate((rect.min.x, rect.min.y));
-                    drawable.draw(&mut arc, None);
+
+                    let painted = match &callback.callback {
+                        CallbackKind::Recorded(record) => record.deref()(&info).map(|drawable| {
+                            let mut drawable: Drawable = drawable.0.into_inner();
+                            arc.translate((rect.min.x, rect.min.y));
+                            drawable.draw(&mut arc, None);
+                        }),
+                        CallbackKind::Immediate(draw) => {
+                            arc.scale((1.0 / dpi, 1.0 / dpi));
+                            draw(&arc, &info)
+                        }
+                    };
+                    if let Err(err) = painted {
+                        let err = EguiSkiaError::CallbackFailed(err.into());
+                        self.callback_errors.push(err.clone());
+                        result = result.and(Err(err));
+                    }
                 }
             }
         }
 
-        textures_delta.free.iter().for_each(|id| {
-            self.free_texture(*id);
-        });
-    }
+        target.restore_to_count(save_count);
 
-    fn set_texture(&mut self, tex_id: TextureId, image_delta: &egui::epaint::ImageDelta) {
-        let delta_image = match &image_delta.image {
-            ImageData::Color(color_image) => {
-                images::raster_from_data(
-                    &ImageInfo::new_n32_premul(
-                        skia_safe::ISize::new(
-                            color_image.width() as i32,
-                            color_image.height() as i32,
+        if let Some((mut surface, factor)) = offscreen {
+            let image = surface.image_snapshot();
+            let mut paint = Paint::default();
+            if let Some(blend_mode) = self.compositing_blend_mode {
+                paint.set_blend_mode(blend_mode);
+            }
+            canvas.save();
+            canvas.reset_matrix();
+            match factor {
+                2.. => {
+                    // Mipmapped sampling averages all pixels of the larger image.
+                    canvas.scale((1.0 / factor as f32, 1.0 / factor as f32));
+                    canvas.draw_image_with_sampling_options(
+                        &image,
+                        (0, 0),
+                        skia_safe::SamplingOptions::new(
+                            skia_safe::FilterMode::Linear,
+                            skia_safe::MipmapMode::Linear,
                         ),
-                        None,
-                    ),
-                    Data::new_copy(
-                        color_image
-                            .pixels
-                            .iter()
-                            .flat_map(|p| p.to_array())
-                            .collect::<Vec<_>>()
-                            .as_slice(),
-                    ),
-                    color_image.width() * 4,
-                )
-                    .unwrap()
+                        Some(&paint),
+                    );
+                }
+                _ => {
+                    canvas.draw_image(&image, (0, 0), Some(&paint));
+                }
             }
+            canvas.restore();
+        }
+
+        self.stats.paint_time += start.elapsed();
+        result
+    }
+
+    /// An offscreen surface covering the device space of `canvas` at `scale` times its
+    /// resolution, with its clip. With `gamma_blending` it blends in sRGB encoded values like
+    /// egui's backends do, otherwise it has the format of `canvas`.
+    ///
+    /// `scale` is lowered to keep the surface within [`MAX_OFFSCREEN_SIDE`], the scale that is
+    /// used is returned with the surface.
+    fn offscreen_surface(
+        canvas: &Canvas,
+        scale: u32,
+        gamma_blending: bool,
+    ) -> Option<(Surface, u32)> {
+        let size = canvas.base_layer_size();
+        let max_scale = MAX_OFFSCREEN_SIDE / size.width.max(size.height).max(1);
+        let scale = scale.min(max_scale.max(1) as u32);
+        let size = ISize::new(
+            size.width.checked_mul(scale as i32)?,
+            size.height.checked_mul(scale as i32)?,
+        );
+        let info = if gamma_blending {
+            ImageInfo::new(
+                size,
+                ColorType::RGBA8888,
+                AlphaType::Premul,
+                Some(ColorSpace::new_srgb()),
+            )
+        } else {
+            canvas.image_info().with_dimensions(size)
         };
+        let mut surface = canvas.new_surface(&info, None)?;
+        let offscreen = surface.canvas();
+        offscreen.clear(Color::TRANSPARENT);
+        offscreen.scale((scale as f32, scale as f32));
+        if let Some(clip) = canvas.device_clip_bounds() {
+            offscreen.clip_irect(clip, ClipOp::Intersect);
+        }
+        Some((surface, scale))
+    }
 
-        let image = match image_delta.pos {
-            None => delta_image,
-            Some(pos) => {
-                let old_image = self.paints.remove(&tex_id).unwrap().image;
+    /// Apply all deltas a texture received this frame.
+    ///
+    /// Patches are composited onto a single surface that is only snapshotted once, instead of
+    /// once per patch. The previous texture stays in place until the new one was built, so a
+    /// failing delta leaves it untouched.
+    fn set_texture(
+        &mut self,
+        tex_id: TextureId,
+        deltas: &[&egui::epaint::ImageDelta],
+    ) -> Result<(), EguiSkiaError> {
+        profile_scope!("egui_skia::set_texture");
+        let Some(image_delta) = deltas.last() else {
+            return Ok(());
+        };
+        self.stats.textures_set += 1;
+        self.stats.bytes_uploaded += deltas
+            .iter()
+            .map(|delta| delta.image.width() * delta.image.height() * 4)
+            .sum::<usize>();
 
-                let mut surface = surfaces::raster_n32_premul(skia_safe::ISize::new(
-                    old_image.width(),
-                    old_image.height(),
-                ))
-                    .unwrap();
+        let shared_font_key = match &self.shared_font_atlas {
+            Some((_, key)) if tex_id == TextureId::default() => SharedFontAtlas::key(*key, deltas),
+            _ => None,
+        };
+        let shared_font_image = shared_font_key.and_then(|key| {
+            let (shared, _) = self.shared_font_atlas.as_ref()?;
+            shared.acquire(key)
+        });
 
-                let canvas = surface.canvas();
-                canvas.draw_image(&old_image, Point::new(0.0, 0.0), None);
+        let text_gamma = self
+            .options
+            .text_gamma
+            .filter(|_| tex_id == TextureId::default());
 
-                canvas.clip_rect(
-                    Rect::new(
-                        pos[0] as scalar,
-                        pos[1] as scalar,
-                        (pos[0] as i32 + delta_image.width()) as scalar,
-                        (pos[1] as i32 + delta_image.height()) as scalar,
-                    ),
-                    ClipOp::default(),
-                    false,
-                );
+        // Everything before the last full upload is overwritten by it.
+        let (base_image, patches) = match deltas.iter().rposition(|delta| delta.pos.is_none()) {
+            Some(index) => {
+                let image = Self::delta_image(&deltas[index].image, text_gamma)
+                    .ok_or(EguiSkiaError::UnsupportedImage(tex_id))?;
+                (Some(image), &deltas[index + 1..])
+            }
+            None if self.paints.contains_key(&tex_id) => (None, deltas),
+            None => return Err(EguiSkiaError::UnknownTexture(tex_id)),
+        };
+        let patches = patches
+            .iter()
+            .map(|patch| {
+                let pos = patch.pos.unwrap();
+                Self::delta_image(&patch.image, text_gamma)
+                    .map(|image| (image, (pos[0] as i32, pos[1] as i32)))
+                    .ok_or(EguiSkiaError::UnsupportedImage(tex_id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
-                canvas.clear(Color::TRANSPARENT);
-                canvas.draw_image(&delta_image, Point::new(pos[0] as f32, pos[1] as f32), None);
+        let mut retained_surface = None;
+        let image = if let Some(image) = shared_font_image.clone() {
+            image
+        } else if let (Some(base_image), true) = (&base_image, patches.is_empty()) {
+            self.upload_image(base_image.clone())
+        } else {
+            let retained = match &base_image {
+                Some(_) => None,
+                None => self.paints.get_mut(&tex_id).and_then(|handle| handle.surface.take()),
+            };
+            let mut surface = match retained {
+                Some(surface) => surface,
+                None => {
+                    let base_image = match &base_image {
+                        Some(image) => image.clone(),
+                        None => self.paints[&tex_id].image.clone(),
+                    };
+                    // Keep the texture's color type, so deep-color textures stay deep.
+                    let mut surface = self
+                        .texture_surface(base_image.image_info())
+                        .ok_or(EguiSkiaError::SurfaceCreationFailed(tex_id))?;
+                    surface
+                        .canvas()
+                        .draw_image(&base_image, Point::new(0.0, 0.0), None);
+                    surface
+                }
+            };
 
-                surface.image_snapshot()
+            // A retained surface is still shared with the texture's previous image, so the first
+            // write copies all of its pixels once.
+            for (delta_image, pos) in &patches {
+                if let Some(pixmap) = delta_image.peek_pixels() {
+                    surface.write_pixels_from_pixmap(&pixmap, *pos);
+                }
             }
+
+            let image = surface.image_snapshot();
+            retained_surface = Some(surface);
+            image
         };
 
-        let local_matrix = skia_safe::Matrix::scale((
-            1.0 / image.width() as f32,
-            1.0 / image.height() as f32,
-        ));
+        if tex_id == TextureId::default() {
+            self.release_shared_font_image();
+            if let (Some(key), Some((shared, current_key))) =
+                (shared_font_key, &mut self.shared_font_atlas)
+            {
+                if shared_font_image.is_none() {
+                    shared.insert(key, &image);
+                }
+                *current_key = Some(key);
+            }
+        }
 
-        #[cfg(feature = "cpu_fix")]
-        let sampling_options = skia_safe::SamplingOptions::new(
-            skia_safe::FilterMode::Nearest,
-            skia_safe::MipmapMode::None,
+        self.insert_texture(tex_id, image, &image_delta.options, retained_surface)
+    }
+
+    /// Make `image`, already uploaded, the texture `tex_id`, packing it into the atlas if
+    /// possible.
+    fn insert_texture(
+        &mut self,
+        tex_id: TextureId,
+        image: Image,
+        options: &egui::TextureOptions,
+        retained_surface: Option<Surface>,
+    ) -> Result<(), EguiSkiaError> {
+        let sampling_options = self.sampling_options(tex_id, options);
+        let wrap_mode = Self::wrap_tile_mode(options.wrap_mode);
+        let tile_mode = self.tile_modes.get(&tex_id).copied().unwrap_or(wrap_mode);
+
+        let packable = tile_mode == TileMode::Clamp
+            && tex_id != TextureId::default()
+            && !self.has_direct_context();
+        let atlas_entry = match &mut self.atlas {
+            Some(atlas) if packable => atlas.insert(&image),
+            _ => None,
+        };
+
+        // Packed textures get their paint once the atlas page has been snapshotted, see
+        // `update_atlas_paints`.
+        let paint = match atlas_entry {
+            Some(_) => Paint::default(),
+            None => Self::modified_paint(
+                &self.paint_modifier,
+                tex_id,
+                Self::texture_paint(&image, sampling_options, tile_mode)
+                    .ok_or(EguiSkiaError::ShaderCreationFailed(tex_id))?,
+            ),
+        };
+
+        // Replaces the previous texture only now that the new one is complete.
+        self.remove_paint_handle(tex_id);
+        self.paints.insert(
+            tex_id,
+            PaintHandle {
+                paint,
+                image,
+                sampling_options,
+                wrap_mode,
+                surface: retained_surface,
+                atlas_entry,
+            },
         );
+        Ok(())
+    }
+
+    /// How a texture with `options` is sampled.
+    fn sampling_options(
+        &self,
+        tex_id: TextureId,
+        options: &egui::TextureOptions,
+    ) -> skia_safe::SamplingOptions {
+        #[cfg(feature = "cpu_fix")]
+        let sampling_options = {
+            let _ = options;
+            skia_safe::SamplingOptions::new(
+                skia_safe::FilterMode::Nearest,
+                skia_safe::MipmapMode::None,
+            )
+        };
         #[cfg(not(feature = "cpu_fix"))]
         let sampling_options = {
             use egui::TextureFilter;
-            let filter_mode = match image_delta.options.magnification {
+            let filter_mode = match options.magnification {
                 TextureFilter::Nearest => skia_safe::FilterMode::Nearest,
                 TextureFilter::Linear => skia_safe::FilterMode::Linear,
             };
-            let mm_mode = match image_delta.options.minification {
+            let mm_mode = match options.minification {
                 TextureFilter::Nearest => skia_safe::MipmapMode::Nearest,
                 TextureFilter::Linear => skia_safe::MipmapMode::Linear,
             };
             skia_safe::SamplingOptions::new(filter_mode, mm_mode)
         };
+        match self.options.max_anisotropy {
+            Some(max_anisotropy) if tex_id != TextureId::default() => {
+                skia_safe::SamplingOptions::from_aniso(max_anisotropy as i32)
+            }
+            _ => sampling_options,
+        }
+    }
+
+    fn wrap_tile_mode(wrap_mode: egui::TextureWrapMode) -> TileMode {
+        match wrap_mode {
+            egui::TextureWrapMode::ClampToEdge => TileMode::Clamp,
+            egui::TextureWrapMode::Repeat => TileMode::Repeat,
+            egui::TextureWrapMode::MirroredRepeat => TileMode::Mirror,
+        }
+    }
+
+    /// The image of a delta, with the coverage of its pixels remapped with `text_gamma`.
+    fn delta_image(image_data: &ImageData, text_gamma: Option<f32>) -> Option<Image> {
+        Self::pixels_image(image_data.size(), delta_pixels(image_data, text_gamma))
+    }
+
+    /// An image of `size` pixels from the premultiplied RGBA `pixels` of a delta.
+    fn pixels_image(size: [usize; 2], pixels: Vec<u8>) -> Option<Image> {
+        images::raster_from_data(
+            // Tagged as sRGB, so Skia converts textures into the color space of
+            // color-managed surfaces, e.g. Display P3, like it does with vertex colors.
+            &ImageInfo::new_n32_premul(
+                skia_safe::ISize::new(size[0] as i32, size[1] as i32),
+                Some(ColorSpace::new_srgb()),
+            ),
+            Data::new_copy(&pixels),
+            size[0] * 4,
+        )
+    }
+
+    fn remove_paint_handle(&mut self, tex_id: TextureId) -> Option<PaintHandle> {
+        let handle = self.paints.remove(&tex_id)?;
+        if let (Some(entry), Some(atlas)) = (handle.atlas_entry, &mut self.atlas) {
+            atlas.remove(entry);
+        }
+        Some(handle)
+    }
+
+    fn texture_paint(
+        image: &Image,
+        sampling_options: skia_safe::SamplingOptions,
+        tile_mode: TileMode,
+    ) -> Option<Paint> {
+        let local_matrix = skia_safe::Matrix::scale((
+            1.0 / image.width() as f32,
+            1.0 / image.height() as f32,
+        ));
 
-        let tile_mode = skia_safe::TileMode::Clamp;
-        let shader = image
-            .to_shader((tile_mode, tile_mode), sampling_options, &local_matrix)
-            .unwrap();
+        let shader = image.to_shader((tile_mode, tile_mode), sampling_options, &local_matrix)?;
 
         let mut paint = Paint::default();
         paint.set_shader(shader);
         paint.set_color(Color::WHITE);
+        Some(paint)
+    }
 
-        self.paints.insert(tex_id, PaintHandle { paint, image });
+    fn modified_paint(
+        paint_modifier: &Option<PaintModifier>,
+        tex_id: TextureId,
+        mut paint: Paint,
+    ) -> Paint {
+        if let Some(paint_modifier) = paint_modifier {
+            paint_modifier(tex_id, &mut paint);
+        }
+        paint
     }
 
     fn free_texture(&mut self, tex_id: TextureId) {
-        self.paints.remove(&tex_id);
+        self.pending_uploads.retain(|(id, _)| *id != tex_id);
+        self.texture_keys.remove(&tex_id);
+        self.evicted_textures.remove(&tex_id);
+        self.reloadable_textures.remove(&tex_id);
+        self.texture_last_used.remove(&tex_id);
+        if let Some(worker) = &mut self.upload_worker {
+            worker.cancel(tex_id);
+        }
+        if tex_id == TextureId::default() {
+            self.release_shared_font_image();
+        }
+        self.remove_paint_handle(tex_id);
+        self.tile_modes.remove(&tex_id);
+        self.blend_modes.remove(&tex_id);
+    }
+
+    /// Evict the least recently painted user textures until all textures fit into the memory
+    /// budget, see [`Self::set_texture_memory_budget`].
+    fn evict_textures(&mut self) {
+        let mut memory = self.texture_memory();
+        self.stats.texture_memory = memory;
+        let Some(budget) = self.texture_memory_budget else {
+            return;
+        };
+        if memory <= budget || self.texture_reloader.is_none() {
+            return;
+        }
+
+        let mut candidates = self
+            .reloadable_textures
+            .iter()
+            .filter(|tex_id| self.paints.contains_key(tex_id))
+            .map(|tex_id| (*tex_id, self.texture_last_used.get(tex_id).copied().unwrap_or(0)))
+            .filter(|(_, last_used)| *last_used != self.frame)
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(_, last_used)| *last_used);
+
+        for (tex_id, _) in candidates {
+            if memory <= budget {
+                break;
+            }
+            let Some(handle) = self.remove_paint_handle(tex_id) else {
+                continue;
+            };
+            memory -= handle.bytes();
+            self.evicted_textures
+                .insert(tex_id, (handle.sampling_options, handle.wrap_mode));
+            self.stats.textures_evicted += 1;
+        }
+        self.stats.texture_memory = memory;
+    }
+
+    /// Restore an evicted texture with the texture reloader.
+    fn reload_texture(&mut self, tex_id: TextureId) {
+        let Some((sampling_options, wrap_mode)) = self.evicted_textures.get(&tex_id).copied()
+        else {
+            return;
+        };
+        let Some(image) = self.texture_reloader.as_ref().and_then(|reload| reload(tex_id)) else {
+            return;
+        };
+        let image = self.upload_image(image);
+        let tile_mode = self.tile_modes.get(&tex_id).copied().unwrap_or(wrap_mode);
+        let Some(paint) = Self::texture_paint(&image, sampling_options, tile_mode) else {
+            return;
+        };
+
+        self.evicted_textures.remove(&tex_id);
+        self.paints.insert(
+            tex_id,
+            PaintHandle {
+                paint: Self::modified_paint(&self.paint_modifier, tex_id, paint),
+                image,
+                sampling_options,
+                wrap_mode,
+                surface: None,
+                atlas_entry: None,
+            },
+        );
     }
 
     fn paint_mesh(
-        &self,
+        &mut self,
         arc: &AutoRestoredCanvas,
         skclip_rect: &Rect,
         mesh: &egui::epaint::Mesh16,
     ) {
+        profile_scope!("egui_skia::paint_mesh");
+        arc.clip_rect(*skclip_rect, ClipOp::default(), true);
+
+        self.texture_last_used.insert(mesh.texture_id, self.frame);
+        if !self.paints.contains_key(&mesh.texture_id) {
+            self.reload_texture(mesh.texture_id);
+        }
+
+        let blend_mode = self.blend_modes.get(&mesh.texture_id).copied();
+        if blend_mode.is_none()
+            && (self.paint_solid_mesh(arc, mesh) || self.paint_image_quad(arc, mesh))
+        {
+            return;
+        }
+        // The texture may still be waiting for its upload, see `set_texture_upload_budget`.
+        if !self.paints.contains_key(&mesh.texture_id) {
+            return;
+        }
+
         let texture_id = mesh.texture_id;
+        let atlas_entry = self
+            .paints
+            .get(&texture_id)
+            .and_then(|handle| handle.atlas_entry);
+
+        let vertices = self.mesh_vertices(mesh, atlas_entry);
+        let paint = &self.paints[&texture_id].paint;
+
+        // `Modulate` combines the vertex colors with the texture, the paint's blend mode
+        // composites the result onto the canvas.
+        if blend_mode.is_none() && !self.options.dither {
+            arc.draw_vertices(&vertices, BlendMode::Modulate, paint);
+            return;
+        }
+        let mut paint = paint.clone();
+        if let Some(blend_mode) = blend_mode {
+            paint.set_blend_mode(blend_mode);
+        }
+        paint.set_dither(self.options.dither);
+        arc.draw_vertices(&vertices, BlendMode::Modulate, &paint);
+    }
 
-        let mut pos = Vec::with_capacity(mesh.vertices.len());
-        let mut texs = Vec::with_capacity(mesh.vertices.len());
-        let mut colors = Vec::with_capacity(mesh.vertices.len());
+    /// The Skia vertices of `mesh`, reused from the previous frame if an identical mesh was
+    /// painted then. Static content like panels and labels then skips the conversion.
+    fn mesh_vertices(&mut self, mesh: &Mesh16, atlas_entry: Option<AtlasEntry>) -> Vertices {
+        #[cfg(feature = "cpu_fix")]
+        let white_texel = self.white_texel(mesh);
+
+        let params = VerticesParams {
+            overlay: self.options.overlay,
+            atlas: atlas_entry.map(|entry| {
+                let rect = entry.rect;
+                (entry.page, [rect.left, rect.top, rect.right, rect.bottom])
+            }),
+            #[cfg(feature = "cpu_fix")]
+            white_texel: white_texel.map(|texel| texel.map(f32::to_bits)),
+            #[cfg(not(feature = "cpu_fix"))]
+            white_texel: None,
+        };
+
+        let key = VerticesKey::new(mesh, &params);
+        if let Some(vertices) = self.vertices_cache.get(&key) {
+            return vertices.clone();
+        }
+        if let Some(vertices) = self.previous_vertices_cache.remove(&key) {
+            self.vertices_cache.insert(key, vertices.clone());
+            return vertices;
+        }
+
+        let indices = self.valid_indices(mesh);
 
-        mesh.vertices.iter().for_each(|v| {
-            let fixed_pos = if v.pos.x.is_nan() || v.pos.y.is_nan() {
-                Pos2::new(0.0, 0.0)
+        // Write straight into the memory of the Skia vertices, instead of building temporary
+        // vectors that are copied.
+        let mut builder = vertices::Builder::new(
+            VertexMode::Triangles,
+            mesh.vertices.len(),
+            indices.len(),
+            BuilderFlags::HAS_TEX_COORDS | BuilderFlags::HAS_COLORS,
+        );
+        for (position, v) in builder.positions().iter_mut().zip(&mesh.vertices) {
+            // Invalid vertices aren't used by any triangle.
+            *position = if v.pos.is_finite() {
+                Point::new(v.pos.x, v.pos.y)
             } else {
-                v.pos
+                Point::new(0.0, 0.0)
             };
+        }
+        if let Some(tex_coords) = builder.tex_coords() {
+            for (tex_coord, v) in tex_coords.iter_mut().zip(&mesh.vertices) {
+                let uv = Point::new(v.uv.x, v.uv.y);
+                *tex_coord = match &atlas_entry {
+                    Some(entry) => entry.map_uv(uv),
+                    None => uv,
+                };
+                #[cfg(feature = "cpu_fix")]
+                if let Some([x, y, scale_x, scale_y]) = white_texel.filter(|_| v.uv == WHITE_UV) {
+                    *tex_coord = Point::new(x + v.pos.x * scale_x, y + v.pos.y * scale_y);
+                }
+            }
+        }
+        if let Some(colors) = builder.colors() {
+            let unmultiplied = |color| self.options.vertex_alpha.unmultiplied(color);
+            let edge_colors = self
+                .options
+                .overlay
+                .then(|| transparent_vertex_colors(mesh))
+                .unwrap_or_default();
+            for (index, (color, v)) in colors.iter_mut().zip(&mesh.vertices).enumerate() {
+                let [mut r, mut g, mut b, a] = unmultiplied(v.color);
+                if let Some(Some(edge_color)) = edge_colors.get(index) {
+                    [r, g, b, _] = unmultiplied(*edge_color);
+                }
+                *color = Color::from_argb(a, r, g, b);
+            }
+        }
+        if let Some(builder_indices) = builder.indices() {
+            builder_indices.copy_from_slice(&indices);
+        }
+        let vertices = builder.detach();
+        self.vertices_cache.insert(key, vertices.clone());
+        vertices
+    }
 
-            pos.push(Point::new(fixed_pos.x, fixed_pos.y));
-            texs.push(Point::new(v.uv.x, v.uv.y));
-
-            let c = v.color;
-            let c = Color::from_argb(c.a(), c.r(), c.g(), c.b());
-            let mut cf = skia_safe::Color4f::from(c);
-            cf.r /= cf.a;
-            cf.g /= cf.a;
-            cf.b /= cf.a;
-            colors.push(Color::from_argb(
-                c.a(),
-                (cf.r * 255.0) as u8,
-                (cf.g * 255.0) as u8,
-                (cf.b * 255.0) as u8,
-            ));
-        });
+    /// The indices of the triangles of `mesh` whose vertices all have finite positions,
+    /// reporting the others as configured by [`PainterOptions::invalid_vertices`].
+    fn valid_indices<'a>(&self, mesh: &'a Mesh16) -> Cow<'a, [u16]> {
+        let invalid = mesh.vertices.iter().filter(|v| !v.pos.is_finite()).count();
+        if invalid == 0 {
+            return Cow::Borrowed(&mesh.indices);
+        }
 
-        let vertices = Vertices::new_copy(
-            VertexMode::Triangles,
-            &pos,
-            &texs,
-            &colors,
-            Some(
-                mesh.indices
-                    .iter()
-                    .map(|index| *index as u16)
-                    .collect::<Vec<u16>>()
-                    .as_slice(),
-            ),
+        let message = format!(
+            "{invalid} of the {} vertices of a mesh with texture {:?} have non-finite positions, \
+             skipping their triangles",
+            mesh.vertices.len(),
+            mesh.texture_id,
         );
+        match self.options.invalid_vertices {
+            InvalidVertices::Skip => {}
+            InvalidVertices::Warn => log::warn!("{message}"),
+            InvalidVertices::DebugAssert => debug_assert!(false, "{message}"),
+        }
 
-        arc.clip_rect(*skclip_rect, ClipOp::default(), true);
+        Cow::Owned(
+            mesh.indices
+                .chunks_exact(3)
+                .filter(|triangle| {
+                    triangle
+                        .iter()
+                        .all(|index| mesh.vertices[*index as usize].pos.is_finite())
+                })
+                .flatten()
+                .copied()
+                .collect(),
+        )
+    }
 
-        #[cfg(feature = "cpu_fix")]
-        let use_white_workaround = !texs
-            .first()
-            .map(|point| point.x != 0.0 || point.y != 0.0)
-            .unwrap_or(false);
-        #[cfg(not(feature = "cpu_fix"))]
-        let use_white_workaround = false;
+    /// Draw untextured meshes of a single color that are a rectangle or a convex fill, e.g.
+    /// panel backgrounds and separators, with `draw_rect` / `draw_path` instead of
+    /// `draw_vertices`. This is faster and gives cleaner edges on raster surfaces.
+    ///
+    /// Returns `false` if the mesh doesn't qualify.
+    fn paint_solid_mesh(&self, canvas: &Canvas, mesh: &Mesh16) -> bool {
+        if mesh.texture_id != TextureId::default() || mesh.vertices.len() > FAST_PATH_MAX_VERTICES
+        {
+            return false;
+        }
+        let Some(first) = mesh.vertices.first() else {
+            return false;
+        };
+        let is_solid = mesh
+            .vertices
+            .iter()
+            .all(|v| v.color == first.color && v.uv == WHITE_UV && v.pos.is_finite());
+        if !is_solid {
+            return false;
+        }
 
-        let paint = if use_white_workaround {
-            &self.white_paint_workaround
-        } else {
-            &self.paints[&texture_id].paint
+        let [r, g, b, a] = self.options.vertex_alpha.unmultiplied(first.color);
+        let mut paint = Paint::default();
+        paint.set_anti_alias(true);
+        paint.set_color(Color::from_argb(a, r, g, b));
+
+        if let Some(rect) = axis_aligned_quad(mesh) {
+            canvas.draw_rect(
+                Rect::new(rect.min.x, rect.min.y, rect.max.x, rect.max.y),
+                &paint,
+            );
+            return true;
+        }
+
+        match convex_outline(mesh) {
+            Some(outline) => {
+                canvas.draw_path(&Path::polygon(&outline, true, None, None), &paint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Draw a single axis-aligned quad showing (part of) a texture in its original colors, the
+    /// typical [`egui::Image`], with `draw_image_rect`. This filters better than sampling the
+    /// texture through a shader and needs less setup per frame.
+    ///
+    /// Returns `false` if the mesh doesn't qualify. Meshes don't while a paint modifier is set,
+    /// since `draw_image_rect` would ignore the shader of the modified paint.
+    fn paint_image_quad(&self, canvas: &Canvas, mesh: &Mesh16) -> bool {
+        if mesh.texture_id == TextureId::default()
+            || self.paint_modifier.is_some()
+            || self.tile_modes.contains_key(&mesh.texture_id)
+            || !mesh.vertices.iter().all(|v| v.color == Color32::WHITE)
+        {
+            return false;
+        }
+        let Some(handle) = self.paints.get(&mesh.texture_id) else {
+            return false;
+        };
+        let Some(rect) = axis_aligned_quad(mesh) else {
+            return false;
+        };
+
+        let uv_at = |pos: Pos2| mesh.vertices.iter().find(|v| v.pos == pos).map(|v| v.uv);
+        let (Some(uv_min), Some(uv_max)) = (uv_at(rect.min), uv_at(rect.max)) else {
+            return false;
         };
+        // The uvs have to map the quad onto the texture without rotating or flipping it, and
+        // without leaving the texture.
+        let uvs_match = mesh.vertices.iter().all(|vertex| {
+            let u = if vertex.pos.x == rect.min.x { uv_min.x } else { uv_max.x };
+            let v = if vertex.pos.y == rect.min.y { uv_min.y } else { uv_max.y };
+            vertex.uv == Pos2::new(u, v)
+        });
+        let in_texture = uv_min.x >= 0.0
+            && uv_min.y >= 0.0
+            && uv_max.x <= 1.0
+            && uv_max.y <= 1.0
+            && uv_min.x < uv_max.x
+            && uv_min.y < uv_max.y;
+        if !uvs_match || !in_texture {
+            return false;
+        }
 
-        arc.draw_vertices(&vertices, BlendMode::Modulate, paint);
+        let (width, height) = (handle.image.width() as f32, handle.image.height() as f32);
+        let src = Rect::new(
+            uv_min.x * width,
+            uv_min.y * height,
+            uv_max.x * width,
+            uv_max.y * height,
+        );
+        let mut paint = Paint::default();
+        paint.set_dither(self.options.dither);
+        canvas.draw_image_rect_with_sampling_options(
+            &handle.image,
+            Some((&src, SrcRectConstraint::Fast)),
+            Rect::new(rect.min.x, rect.min.y, rect.max.x, rect.max.y),
+            handle.sampling_options,
+            &paint,
+        );
+        true
     }
 
+    /// How the vertices of `mesh` that use the font texture's white pixel are spread over
+    /// that pixel, as `[x, y, scale_x, scale_y]`: their uv is `(x, y) + pos * (scale_x, scale_y)`.
+    ///
+    /// Skia's raster backend maps every triangle's texture coordinates onto its positions with
+    /// an inverted matrix, which doesn't exist if all three vertices have the same uv, so solid
+    /// triangles would be drawn with garbage. Spreading the uvs linearly with the positions
+    /// keeps them inside the white pixel, which is sampled with nearest filtering, while every
+    /// triangle gets an invertible mapping.
     #[cfg(feature = "cpu_fix")]
-    fn split_texture_meshes(&self, mesh: egui::epaint::Mesh16) -> Vec<egui::epaint::Mesh16> {
-        let mut is_zero = None;
-        let mut meshes = Vec::new();
-        meshes.push(egui::epaint::Mesh16 {
-            indices: vec![],
-            vertices: vec![],
-            texture_id: mesh.texture_id,
-        });
+    fn white_texel(&self, mesh: &Mesh16) -> Option<[f32; 4]> {
+        if mesh.texture_id != TextureId::default() {
+            return None;
+        }
+        let image = &self.paints.get(&mesh.texture_id)?.image;
+        let bounds =
+            egui::Rect::from_points(&mesh.vertices.iter().map(|v| v.pos).collect::<Vec<_>>());
+        if !bounds.is_finite() {
+            return None;
+        }
 
-        for index in mesh.indices.iter() {
-            let vertex = mesh.vertices.get(*index as usize).unwrap();
-            let is_current_zero = vertex.uv.x == 0.0 && vertex.uv.y == 0.0;
+        // The white pixel spans `[0, 1 / size)`; use the middle 80% of it.
+        let (width, height) = (image.width() as f32, image.height() as f32);
+        let scale_x = 0.8 / (width * bounds.width().max(1.0));
+        let scale_y = 0.8 / (height * bounds.height().max(1.0));
+        Some([
+            WHITE_UV.x + 0.1 / width - bounds.min.x * scale_x,
+            WHITE_UV.y + 0.1 / height - bounds.min.y * scale_y,
+            scale_x,
+            scale_y,
+        ])
+    }
+}
 
-            if is_current_zero != is_zero.unwrap_or(is_current_zero) {
-                meshes.push(egui::epaint::Mesh16 {
-                    indices: vec![],
-                    vertices: vec![],
-                    texture_id: mesh.texture_id,
-                });
-                is_zero = Some(is_current_zero);
+/// The color of a visible vertex sharing a triangle with every fully transparent vertex of
+/// `mesh`, see [`PainterOptions::overlay`].
+fn transparent_vertex_colors(mesh: &Mesh16) -> Vec<Option<Color32>> {
+    let mut colors = vec![None; mesh.vertices.len()];
+    for triangle in mesh.indices.chunks_exact(3) {
+        let vertex_color = |index: &u16| mesh.vertices[*index as usize].color;
+        let Some(visible) = triangle.iter().map(vertex_color).find(|color| color.a() > 0) else {
+            continue;
+        };
+        for index in triangle {
+            let color = &mut colors[*index as usize];
+            if color.is_none() && vertex_color(index).a() == 0 {
+                *color = Some(visible);
             }
+        }
+    }
+    colors
+}
 
-            if is_zero.is_none() {
-                is_zero = Some(is_current_zero);
-            }
+/// Meshes with more vertices than this are always drawn as vertices.
+const FAST_PATH_MAX_VERTICES: usize = 64;
 
-            let last = meshes.last_mut().unwrap();
-            last.vertices.push(vertex.clone());
-            last.indices.push(last.indices.len() as u16);
-        }
+/// The largest side of the offscreen surfaces of [`PainterOptions::supersampling`], in pixels.
+const MAX_OFFSCREEN_SIDE: i32 = 16384;
+
+/// The summed area of all triangles of `mesh`.
+fn triangles_area(mesh: &Mesh16) -> f32 {
+    mesh.indices
+        .chunks_exact(3)
+        .map(|triangle| {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]]
+                .map(|index| mesh.vertices[index as usize].pos);
+            ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)).abs() / 2.0
+        })
+        .sum()
+}
+
+/// Whether the triangles of a mesh cover `area` exactly, without gaps or overlaps.
+fn covers_area(mesh: &Mesh16, area: f32) -> bool {
+    area > 0.0 && (triangles_area(mesh) - area).abs() <= area * 1e-3
+}
+
+/// The rect covered by `mesh`, if it is a quad of two triangles with axis-aligned edges.
+fn axis_aligned_quad(mesh: &Mesh16) -> Option<egui::Rect> {
+    if mesh.vertices.len() != 4 || mesh.indices.len() != 6 {
+        return None;
+    }
+
+    let rect = egui::Rect::from_points(&mesh.vertices.iter().map(|v| v.pos).collect::<Vec<_>>());
+    let at_corners = mesh.vertices.iter().all(|v| {
+        (v.pos.x == rect.min.x || v.pos.x == rect.max.x)
+            && (v.pos.y == rect.min.y || v.pos.y == rect.max.y)
+    });
 
-        meshes
+    (at_corners && covers_area(mesh, rect.area())).then_some(rect)
+}
+
+/// The outline of `mesh`, if its triangles exactly fill their convex hull.
+fn convex_outline(mesh: &Mesh16) -> Option<Vec<Point>> {
+    let mut points = mesh.vertices.iter().map(|v| v.pos).collect::<Vec<_>>();
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return None;
+    }
+
+    // Andrew's monotone chain.
+    let cross = |o: Pos2, a: Pos2, b: Pos2| (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x);
+    let mut hull: Vec<Pos2> = Vec::with_capacity(points.len() + 1);
+    for &point in &points {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(point);
+    }
+    let lower_len = hull.len() + 1;
+    for &point in points.iter().rev().skip(1) {
+        while hull.len() >= lower_len
+            && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0
+        {
+            hull.pop();
+        }
+        hull.push(point);
     }
+    hull.pop();
+
+    let hull_area = hull
+        .iter()
+        .zip(hull.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum::<f32>()
+        .abs()
+        / 2.0;
+
+    covers_area(mesh, hull_area).then(|| hull.iter().map(|p| Point::new(p.x, p.y)).collect())
 }
 
 impl Default for Painter {
@@ -302,28 +2710,223 @@ impl Default for Painter {
     }
 }
 
+impl Drop for Painter {
+    fn drop(&mut self) {
+        self.release_shared_font_image();
+    }
+}
+
+/// When a paint callback is composited relative to the other shapes of its layer, e.g. the
+/// window it is shown in.
+///
+/// The shapes are reordered by [`CallbackOrder::apply`] at the end of a pass, while egui still
+/// keeps them per layer. [`EguiSkia::run`](crate::EguiSkia::run), [`render_ui_onto_surface`]
+/// and the winit integrations do that themselves.
+///
+/// [`render_ui_onto_surface`]: crate::render_ui_onto_surface
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CallbackOrder {
+    /// Paint the callback where egui placed it.
+    #[default]
+    InPlace,
+    /// Paint the callback before the other shapes of its layer, e.g. for 3D or video content
+    /// behind a window's widgets.
+    BelowUi,
+    /// Paint the callback after the other shapes of its layer.
+    AboveUi,
+}
+
+impl CallbackOrder {
+    /// Move the paint callbacks of every layer of `ctx` below or above the other shapes of
+    /// their layer, as set with [`EguiSkiaPaintCallback::with_order`].
+    ///
+    /// Call this at the end of the ui closure passed to [`egui::Context::run`] when painting
+    /// with a [`Painter`] directly.
+    pub fn apply(ctx: &egui::Context) {
+        let mut layer_ids = ctx.memory(|memory| memory.layer_ids().collect::<Vec<_>>());
+        if !layer_ids.contains(&egui::LayerId::background()) {
+            layer_ids.push(egui::LayerId::background());
+        }
+
+        ctx.graphics_mut(|graphics| {
+            for layer_id in layer_ids {
+                let Some(paint_list) = graphics.get_mut(layer_id) else {
+                    continue;
+                };
+                let mut shapes = paint_list.all_entries().cloned().collect::<Vec<_>>();
+                if shapes
+                    .iter()
+                    .all(|shape| Self::of(&shape.shape) == CallbackOrder::InPlace)
+                {
+                    continue;
+                }
+                // Stable, so everything without an explicit order keeps egui's order.
+                shapes.sort_by_key(|shape| Self::of(&shape.shape).rank());
+                for (index, shape) in shapes.into_iter().enumerate() {
+                    paint_list.set(egui::layers::ShapeIdx(index), shape.clip_rect, shape.shape);
+                }
+            }
+        });
+    }
+
+    fn of(shape: &egui::Shape) -> Self {
+        match shape {
+            egui::Shape::Callback(callback) => callback
+                .callback
+                .downcast_ref::<EguiSkiaPaintCallback>()
+                .map(|callback| callback.order)
+                .unwrap_or_default(),
+            _ => CallbackOrder::InPlace,
+        }
+    }
+
+    fn rank(self) -> u8 {
+        match self {
+            CallbackOrder::BelowUi => 0,
+            CallbackOrder::InPlace => 1,
+            CallbackOrder::AboveUi => 2,
+        }
+    }
+}
+
+/// Where and how a paint callback is painted, like [`egui::PaintCallbackInfo`] with pixel
+/// coordinates in Skia types.
+#[derive(Clone, Copy, Debug)]
+pub struct PaintCallbackInfo {
+    /// The rect of the callback in points, as given to [`egui::PaintCallback`].
+    pub rect: egui::Rect,
+    /// The rect of the callback in pixels.
+    pub rect_px: Rect,
+    /// The clip rect of the callback in pixels.
+    pub clip_rect_px: Rect,
+    pub pixels_per_point: f32,
+    /// The size of the canvas being painted in pixels.
+    pub viewport_size_px: ISize,
+}
+
+/// The error a fallible paint callback fails with.
+pub type CallbackError = Box<dyn std::error::Error + Send + Sync>;
+
+enum CallbackKind {
+    Recorded(
+        Box<dyn Fn(&PaintCallbackInfo) -> Result<SyncSendableDrawable, CallbackError> + Send + Sync>,
+    ),
+    Immediate(Box<dyn Fn(&Canvas, &PaintCallbackInfo) -> Result<(), CallbackError> + Send + Sync>),
+}
+
 pub struct EguiSkiaPaintCallback {
-    callback: Box<dyn Fn(Rect) -> SyncSendableDrawable + Send + Sync>,
+    callback: CallbackKind,
+    order: CallbackOrder,
 }
 
 impl EguiSkiaPaintCallback {
     pub fn new<F: Fn(&Canvas) + Send + Sync + 'static>(callback: F) -> EguiSkiaPaintCallback {
+        Self::with_info(move |canvas, _| callback(canvas))
+    }
+
+    /// Like [`Self::new`], with the [`PaintCallbackInfo`] of the callback, e.g. to lay out its
+    /// drawing for the current `pixels_per_point`.
+    pub fn with_info<F: Fn(&Canvas, &PaintCallbackInfo) + Send + Sync + 'static>(
+        callback: F,
+    ) -> EguiSkiaPaintCallback {
+        Self::try_new(move |canvas, info| {
+            callback(canvas, info);
+            Ok(())
+        })
+    }
+
+    /// Like [`Self::with_info`], for callbacks that can fail, e.g. ones loaded from plugins.
+    ///
+    /// A failed callback isn't drawn, the rest of the frame is. The error is returned by
+    /// [`Painter::try_paint_and_update_textures`] and listed in [`Painter::callback_errors`].
+    pub fn try_new<
+        F: Fn(&Canvas, &PaintCallbackInfo) -> Result<(), CallbackError> + Send + Sync + 'static,
+    >(
+        callback: F,
+    ) -> EguiSkiaPaintCallback {
         EguiSkiaPaintCallback {
-            callback: Box::new(move |rect| {
+            callback: CallbackKind::Recorded(Box::new(move |info| {
                 let mut pr = PictureRecorder::new();
-                let canvas = pr.begin_recording(rect, false);
-                callback(canvas);
-                SyncSendableDrawable(
-                    pr.finish_recording_as_drawable()
-                        .unwrap()
-                        .wrap_send()
-                        .unwrap(),
-                )
-            }),
+                let canvas = pr.begin_recording(info.rect_px, false);
+                callback(canvas, info)?;
+                let drawable = pr
+                    .finish_recording_as_drawable()
+                    .ok_or("the recording of the callback is empty")?;
+                let drawable = drawable
+                    .wrap_send()
+                    .map_err(|_| "the recording of the callback can't be sent")?;
+                Ok(SyncSendableDrawable(drawable))
+            })),
+            order: CallbackOrder::InPlace,
+        }
+    }
+
+    /// A callback that draws directly onto the canvas being painted, instead of being recorded
+    /// into a drawable first.
+    ///
+    /// The callback gets the canvas, clipped to the callback's clip rect and transformed to
+    /// pixels, and its [`PaintCallbackInfo`]. Use it for drawing that needs the live canvas,
+    /// e.g. the gpu context of its surface with `canvas.direct_context()`, or to avoid the copy
+    /// of the recording.
+    pub fn immediate<F: Fn(&Canvas, &PaintCallbackInfo) + Send + Sync + 'static>(
+        callback: F,
+    ) -> EguiSkiaPaintCallback {
+        Self::try_immediate(move |canvas, info| {
+            callback(canvas, info);
+            Ok(())
+        })
+    }
+
+    /// Like [`Self::immediate`], for callbacks that can fail, see [`Self::try_new`]. Whatever a
+    /// failed callback drew before returning the error stays on the canvas.
+    pub fn try_immediate<
+        F: Fn(&Canvas, &PaintCallbackInfo) -> Result<(), CallbackError> + Send + Sync + 'static,
+    >(
+        callback: F,
+    ) -> EguiSkiaPaintCallback {
+        EguiSkiaPaintCallback {
+            callback: CallbackKind::Immediate(Box::new(callback)),
+            order: CallbackOrder::InPlace,
+        }
+    }
+
+    /// Composite the callback below or above the other shapes of its layer instead of where
+    /// egui placed it, see [`CallbackOrder`].
+    pub fn with_order(mut self, order: CallbackOrder) -> Self {
+        self.order = order;
+        self
+    }
+}
+
+/// The premultiplied RGBA pixels of a delta, with their coverage remapped with `text_gamma`.
+pub(crate) fn delta_pixels(image_data: &ImageData, text_gamma: Option<f32>) -> Vec<u8> {
+    // egui dropped `ImageData::Font`: the font atlas arrives as a color image of
+    // premultiplied white with egui's text gamma already applied.
+    match image_data {
+        ImageData::Color(color_image) => {
+            let alpha_lut = text_gamma.map(|gamma| {
+                std::array::from_fn::<u8, 256, _>(|a| {
+                    ((a as f32 / 255.0).powf(gamma) * 255.0).round() as u8
+                })
+            });
+            let pixel = |p: &Color32| match &alpha_lut {
+                // Scale all channels, so colored glyphs like emojis stay premultiplied.
+                Some(lut) if p.a() > 0 => {
+                    let scale = lut[p.a() as usize] as f32 / p.a() as f32;
+                    p.to_array()
+                        .map(|c| (c as f32 * scale).round().min(255.0) as u8)
+                }
+                _ => p.to_array(),
+            };
+            color_image.pixels.iter().flat_map(pixel).collect()
         }
     }
 }
 
+/// A shape of [`Painter::paint_native_shapes`] that is drawn with Skia geometry, passed through
+/// as a paint callback to keep its place among the tessellated primitives.
+struct NativeShape(egui::Shape);
+
 struct SyncSendableDrawable(pub Sendable<Drawable>);
 
 unsafe impl Sync for SyncSendableDrawable {}
@@ -0,0 +1,196 @@
+//! Print egui content through the platform print pipeline.
+//!
+//! The ui is rendered into a vector pdf with Skia's pdf backend, which is then handed to the
+//! system spooler: `lp` on macOS and Linux (CUPS), and the shell's print verb on Windows, which
+//! routes the document through the default XPS/GDI print driver.
+
+use std::io;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use egui::Context;
+use skia_safe::{pdf, ClipOp, PictureRecorder, Rect, Size};
+
+use crate::{draw_onto_canvas, RasterizeOptions};
+
+/// Page margins in pdf points (1/72 inch).
+#[derive(Clone, Copy, Debug)]
+pub struct PageMargins {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl PageMargins {
+    pub fn same(margin: f32) -> Self {
+        Self {
+            left: margin,
+            top: margin,
+            right: margin,
+            bottom: margin,
+        }
+    }
+}
+
+impl Default for PageMargins {
+    /// Half an inch on every side.
+    fn default() -> Self {
+        Self::same(36.0)
+    }
+}
+
+pub struct PrintOptions {
+    /// The page size in pdf points (1/72 inch). Defaults to A4.
+    pub page_size: Size,
+    pub margins: PageMargins,
+    /// The name of the print job, shown in the printer queue.
+    pub job_name: String,
+    /// The printer to use. Uses the system default printer if `None`.
+    pub printer: Option<String>,
+    pub rasterize_options: RasterizeOptions,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            page_size: Size::new(595.0, 842.0),
+            margins: PageMargins::default(),
+            job_name: "egui".to_owned(),
+            printer: None,
+            rasterize_options: RasterizeOptions::default(),
+        }
+    }
+}
+
+/// Render `ui` into a single page pdf document, laid out in the printable area of the page.
+pub fn render_pdf(ui: impl FnMut(&Context), options: &PrintOptions) -> Vec<u8> {
+    let PrintOptions {
+        page_size,
+        margins,
+        rasterize_options,
+        ..
+    } = options;
+
+    let content_rect = Rect::new(
+        margins.left,
+        margins.top,
+        page_size.width - margins.right,
+        page_size.height - margins.bottom,
+    );
+
+    // The painter ignores the matrix of the canvas it paints onto, so the ui is recorded first
+    // and the recording is placed in the printable area.
+    let mut recorder = PictureRecorder::new();
+    let canvas = recorder.begin_recording(Rect::from_size(content_rect.size()), false);
+    draw_onto_canvas(
+        canvas,
+        (content_rect.width() as i32, content_rect.height() as i32),
+        ui,
+        Some(rasterize_options.clone()),
+    );
+    let picture = recorder.finish_recording_as_picture(None);
+
+    let mut pdf_data = Vec::new();
+    let document = pdf::new_document(&mut pdf_data, None);
+    let mut page = document.begin_page(*page_size, None);
+    if let Some(picture) = picture {
+        let canvas = page.canvas();
+        canvas.clip_rect(content_rect, ClipOp::Intersect, true);
+        canvas.translate((content_rect.left, content_rect.top));
+        canvas.draw_picture(&picture, None, None);
+    }
+    page.end_page().close();
+
+    pdf_data
+}
+
+/// Render `ui` and send it to the printer.
+pub fn print(ui: impl FnMut(&Context), options: &PrintOptions) -> io::Result<()> {
+    let pdf_data = render_pdf(ui, options);
+    spool(&pdf_data, options)
+}
+
+#[cfg(unix)]
+fn spool(pdf_data: &[u8], options: &PrintOptions) -> io::Result<()> {
+    let mut command = Command::new("lp");
+    command.arg("-t").arg(&options.job_name);
+    if let Some(printer) = &options.printer {
+        command.arg("-d").arg(printer);
+    }
+
+    let mut child = command
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(pdf_data)?;
+
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("lp exited with {status}")))
+    }
+}
+
+#[cfg(windows)]
+fn spool(pdf_data: &[u8], options: &PrintOptions) -> io::Result<()> {
+    if options
+        .printer
+        .as_ref()
+        .is_some_and(|printer| printer.contains('"'))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "printer names can't contain quotes",
+        ));
+    }
+
+    let file_name = format!(
+        "{}-{}.pdf",
+        options
+            .job_name
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_"),
+        std::process::id()
+    );
+    let path = std::env::temp_dir().join(file_name);
+    std::fs::File::create(&path)?.write_all(pdf_data)?;
+
+    // The path and printer are passed in environment variables, so they are never parsed as
+    // part of the script.
+    let mut command = Command::new("powershell");
+    command.env("EGUI_SKIA_PRINT_FILE", &path);
+    let script = match &options.printer {
+        Some(printer) => {
+            command.env("EGUI_SKIA_PRINTER", printer);
+            "Start-Process -FilePath $env:EGUI_SKIA_PRINT_FILE -Verb PrintTo \
+             -ArgumentList ('\"' + $env:EGUI_SKIA_PRINTER + '\"') -Wait"
+        }
+        None => "Start-Process -FilePath $env:EGUI_SKIA_PRINT_FILE -Verb Print -Wait",
+    };
+
+    let status = command
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .stdout(Stdio::null())
+        .status()?;
+    let _ = std::fs::remove_file(&path);
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("printing failed with {status}")))
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn spool(_pdf_data: &[u8], _options: &PrintOptions) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "printing is not supported on this platform",
+    ))
+}
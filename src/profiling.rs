@@ -0,0 +1,14 @@
+//! Profiling scopes for the `puffin` and `tracing` features.
+
+/// Profile the rest of the enclosing scope as `$name` with puffin and/or a tracing span,
+/// depending on the enabled features. Compiles to nothing without them.
+macro_rules! profile_scope {
+    ($name:expr) => {
+        #[cfg(feature = "puffin")]
+        puffin::profile_scope!($name);
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!($name).entered();
+    };
+}
+
+pub(crate) use profile_scope;
@@ -0,0 +1,226 @@
+//! Run a headless ui that is controlled remotely: input events go in, encoded frames come out.
+//!
+//! Useful for remote debugging, automated exploratory testing and thin clients. Commands can
+//! be applied directly with [`RemoteSession::apply`], e.g. when received over a channel, or
+//! sent over a socket with the line based text protocol implemented by [`serve`]:
+//!
+//! ```text
+//! size <width> <height>            resize the ui, in pixels
+//! move <x> <y>                     move the pointer, in points
+//! down <x> <y> [primary|secondary|middle]
+//! up <x> <y> [primary|secondary|middle]
+//! scroll <dx> <dy>                 in points
+//! key <name> down|up               e.g. `key Enter down`
+//! text <text>                      the rest of the line
+//! frame                            render a frame
+//! ```
+//!
+//! Every command is answered with `ok`, or `error <message>`. `frame` is answered with
+//! `frame <length>` followed by `length` bytes of png data. Sizes are limited to
+//! [`MAX_SIZE`] pixels per side.
+
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+
+use egui::{Context, Key, Modifiers, MouseWheelUnit, PointerButton, Pos2, Vec2};
+use skia_safe::{surfaces, Color, EncodedImageFormat};
+
+use crate::egui_skia::headless_input;
+use crate::EguiSkia;
+
+/// The largest width and height of a remote frame, in pixels.
+pub const MAX_SIZE: i32 = 16384;
+
+/// A command of the remote protocol.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Resize(i32, i32),
+    PointerMoved(Pos2),
+    PointerButton {
+        pos: Pos2,
+        button: PointerButton,
+        pressed: bool,
+    },
+    Scroll(Vec2),
+    Key {
+        key: Key,
+        pressed: bool,
+    },
+    Text(String),
+    Frame,
+}
+
+impl Command {
+    /// Parse a line of the text protocol.
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let mut args = rest.split_whitespace();
+        let mut number = |what: &str| -> Result<f32, String> {
+            args.next()
+                .ok_or_else(|| format!("missing {what}"))?
+                .parse()
+                .map_err(|_| format!("invalid {what}"))
+        };
+
+        let command = match name {
+            "size" => {
+                let mut size = |what: &str| -> Result<i32, String> {
+                    let value = number(what)?;
+                    if (1.0..=MAX_SIZE as f32).contains(&value) {
+                        Ok(value as i32)
+                    } else {
+                        Err(format!("{what} must be between 1 and {MAX_SIZE}"))
+                    }
+                };
+                Command::Resize(size("width")?, size("height")?)
+            }
+            "move" => Command::PointerMoved(Pos2::new(number("x")?, number("y")?)),
+            "down" | "up" => {
+                let pos = Pos2::new(number("x")?, number("y")?);
+                let button = match args.next().unwrap_or("primary") {
+                    "primary" => PointerButton::Primary,
+                    "secondary" => PointerButton::Secondary,
+                    "middle" => PointerButton::Middle,
+                    other => return Err(format!("unknown button {other}")),
+                };
+                Command::PointerButton {
+                    pos,
+                    button,
+                    pressed: name == "down",
+                }
+            }
+            "scroll" => Command::Scroll(Vec2::new(number("dx")?, number("dy")?)),
+            "key" => {
+                let name = args.next().ok_or("missing key")?;
+                let key = Key::from_name(name).ok_or_else(|| format!("unknown key {name}"))?;
+                let pressed = match args.next() {
+                    Some("down") => true,
+                    Some("up") => false,
+                    _ => return Err("expected down or up".to_owned()),
+                };
+                Command::Key { key, pressed }
+            }
+            "text" => Command::Text(rest.to_owned()),
+            "frame" => Command::Frame,
+            other => return Err(format!("unknown command {other}")),
+        };
+        Ok(command)
+    }
+}
+
+/// A headless ui and the input collected for its next frame.
+pub struct RemoteSession {
+    pub egui_skia: EguiSkia,
+    size: (i32, i32),
+    events: Vec<egui::Event>,
+}
+
+impl RemoteSession {
+    /// A session rendering frames of `size` pixels.
+    pub fn new(size: (i32, i32)) -> Self {
+        Self {
+            egui_skia: EguiSkia::new(),
+            size,
+            events: Vec::new(),
+        }
+    }
+
+    /// Apply `command`. Returns the png encoded frame for [`Command::Frame`].
+    pub fn apply(
+        &mut self,
+        command: Command,
+        ui: impl FnMut(&Context),
+    ) -> Result<Option<Vec<u8>>, String> {
+        let modifiers = Modifiers::default();
+        let event = match command {
+            Command::Resize(width, height) => {
+                self.size = (width.clamp(1, MAX_SIZE), height.clamp(1, MAX_SIZE));
+                return Ok(None);
+            }
+            Command::PointerMoved(pos) => egui::Event::PointerMoved(pos),
+            Command::PointerButton {
+                pos,
+                button,
+                pressed,
+            } => egui::Event::PointerButton {
+                pos,
+                button,
+                pressed,
+                modifiers,
+            },
+            Command::Scroll(delta) => egui::Event::MouseWheel {
+                unit: MouseWheelUnit::Point,
+                delta,
+                modifiers,
+            },
+            Command::Key { key, pressed } => egui::Event::Key {
+                key,
+                physical_key: None,
+                pressed,
+                repeat: false,
+                modifiers,
+            },
+            Command::Text(text) => egui::Event::Text(text),
+            Command::Frame => return self.render_frame(ui).map(Some),
+        };
+        self.events.push(event);
+        Ok(None)
+    }
+
+    /// Run a frame with the collected input and return it png encoded.
+    ///
+    /// Fails if no surface of the session's size can be allocated, or the frame can't be
+    /// encoded. The ui still ran, so the next frame continues from this one.
+    pub fn render_frame(&mut self, ui: impl FnMut(&Context)) -> Result<Vec<u8>, String> {
+        let mut input = headless_input(self.size);
+        input.events = std::mem::take(&mut self.events);
+        self.egui_skia.run(input, ui);
+
+        let mut surface = surfaces::raster_n32_premul(self.size).ok_or_else(|| {
+            format!(
+                "failed to create a surface of {}x{} pixels",
+                self.size.0, self.size.1
+            )
+        })?;
+        surface.canvas().clear(Color::TRANSPARENT);
+        self.egui_skia.paint(surface.canvas());
+
+        surface
+            .image_snapshot()
+            .encode_to_data(EncodedImageFormat::PNG)
+            .map(|data| data.as_bytes().to_vec())
+            .ok_or_else(|| "failed to encode the frame".to_owned())
+    }
+}
+
+/// Serve `ui` over the text protocol to one client after another.
+///
+/// Every client gets a fresh [`RemoteSession`] of 1024x768 pixels. A client disconnecting
+/// doesn't stop the server.
+pub fn serve(listener: TcpListener, mut ui: impl FnMut(&Context)) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let _ = serve_client(stream?, &mut ui);
+    }
+    Ok(())
+}
+
+fn serve_client(stream: TcpStream, mut ui: impl FnMut(&Context)) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut session = RemoteSession::new((1024, 768));
+
+    for line in BufReader::new(stream).lines() {
+        let result = Command::parse(&line?).and_then(|command| session.apply(command, &mut ui));
+        match result {
+            Ok(Some(frame)) => {
+                writeln!(writer, "frame {}", frame.len())?;
+                writer.write_all(&frame)?;
+            }
+            Ok(None) => writeln!(writer, "ok")?,
+            Err(error) => writeln!(writer, "error {error}")?,
+        }
+        writer.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+//! Paint into caller provided memory, e.g. a `wl_shm` pool or a `memfd` shared with another
+//! process, so a compositor or parent process can display the ui without copying frames.
+
+use skia_safe::{surfaces, AlphaType, Borrows, ColorType, IRect, ImageInfo, Surface};
+
+use crate::damage::DamageTracker;
+use crate::EguiSkia;
+
+/// A raster surface backed by shared memory.
+///
+/// The buffer keeps its contents between frames, so only the damaged area is repainted.
+/// Reuse the same `ShmSurface` for the same buffer. With several buffers that are handed out in
+/// turns, call [`Self::invalidate`] whenever the previous frame wasn't painted into this buffer.
+pub struct ShmSurface<'a> {
+    surface: Borrows<'a, Surface>,
+    damage: DamageTracker,
+}
+
+impl<'a> ShmSurface<'a> {
+    /// Wrap `pixels`, which holds `size.1` rows of `stride` bytes in `color_type` with
+    /// premultiplied alpha. Wayland's `ARGB8888` is [`ColorType::BGRA8888`] on little endian
+    /// machines.
+    ///
+    /// Returns `None` if the buffer is too small for the layout.
+    pub fn new(
+        pixels: &'a mut [u8],
+        size: (i32, i32),
+        stride: usize,
+        color_type: ColorType,
+    ) -> Option<Self> {
+        let info = ImageInfo::new(size, color_type, AlphaType::Premul, None);
+        let surface = surfaces::wrap_pixels(&info, pixels, stride, None)?;
+
+        Some(Self {
+            surface,
+            damage: DamageTracker::new(),
+        })
+    }
+
+    /// Repaint the whole buffer with the next frame.
+    pub fn invalidate(&mut self) {
+        self.damage.invalidate();
+    }
+
+    /// Paint the results of the last [`EguiSkia::run`] into the buffer.
+    ///
+    /// Returns the damaged area in pixels, to be reported to the compositor (e.g. with
+    /// `wl_surface.damage_buffer`), or `None` if the buffer didn't change.
+    pub fn paint(&mut self, egui_skia: &mut EguiSkia) -> Option<IRect> {
+        egui_skia.paint_damaged(self.surface.canvas(), &mut self.damage)
+    }
+}
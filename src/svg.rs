@@ -0,0 +1,115 @@
+//! Show svg files in egui with Skia's svg module, either as vector drawing or as a texture
+//! rasterized at the displayed size, so they stay sharp at any scale.
+
+use std::sync::Arc;
+
+use egui::{ColorImage, Response, Sense, TextureHandle, TextureOptions, Ui, Vec2};
+use skia_safe::svg::{Dom, LoadError};
+use skia_safe::{
+    surfaces, AlphaType, Color, ColorType, FontMgr, ImageInfo, Picture, PictureRecorder, Rect,
+};
+
+use crate::EguiSkiaPaintCallback;
+
+/// A parsed svg document.
+///
+/// The document is scaled to the size it is shown at, following its `viewBox` and
+/// `preserveAspectRatio`. Drawings and textures are cached for the last size.
+pub struct SvgDocument {
+    dom: Dom,
+    picture: Option<(Vec2, Picture)>,
+    texture: Option<([usize; 2], TextureHandle)>,
+}
+
+impl SvgDocument {
+    /// Parse an svg file. Text in the document is drawn with the system's fonts.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoadError> {
+        Ok(Self {
+            dom: Dom::from_bytes(bytes, FontMgr::default())?,
+            picture: None,
+            texture: None,
+        })
+    }
+
+    /// The document drawn into a box of `size`, recorded as a picture.
+    pub fn picture(&mut self, size: Vec2) -> Picture {
+        match &self.picture {
+            Some((picture_size, picture)) if *picture_size == size => picture.clone(),
+            _ => {
+                self.dom.set_container_size((size.x, size.y));
+                let mut recorder = PictureRecorder::new();
+                let canvas = recorder.begin_recording(Rect::from_wh(size.x, size.y), false);
+                self.dom.render(canvas);
+                let picture = recorder
+                    .finish_recording_as_picture(None)
+                    .expect("Recording was started");
+                self.picture = Some((size, picture.clone()));
+                picture
+            }
+        }
+    }
+
+    /// Rasterize the document at `size` pixels, e.g. to load it with
+    /// [`egui::Context::load_texture`].
+    pub fn rasterize(&mut self, size: [usize; 2]) -> Option<ColorImage> {
+        let picture = self.picture(Vec2::new(size[0] as f32, size[1] as f32));
+        let info = ImageInfo::new(
+            (size[0] as i32, size[1] as i32),
+            ColorType::RGBA8888,
+            AlphaType::Premul,
+            None,
+        );
+        let mut surface = surfaces::raster(&info, None, None)?;
+        surface.canvas().clear(Color::TRANSPARENT);
+        surface.canvas().draw_picture(&picture, None, None);
+
+        let row_bytes = info.min_row_bytes();
+        let mut pixels = vec![0; row_bytes * size[1]];
+        surface
+            .read_pixels(&info, &mut pixels, row_bytes, (0, 0))
+            .then(|| ColorImage::from_rgba_premultiplied(size, &pixels))
+    }
+
+    /// Show the document in a widget of `size`, drawn with Skia at the resolution of the
+    /// screen through a paint callback.
+    pub fn show(&mut self, ui: &mut Ui, size: Vec2) -> Response {
+        let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+        if ui.is_rect_visible(rect) {
+            let picture = self.picture(size);
+            ui.painter().add(egui::PaintCallback {
+                rect,
+                callback: Arc::new(EguiSkiaPaintCallback::new(move |canvas| {
+                    canvas.draw_picture(&picture, None, None);
+                })),
+            });
+        }
+        response
+    }
+
+    /// Like [`Self::show`], but draws a texture that is rasterized whenever the size of the
+    /// widget in pixels changes.
+    ///
+    /// Cheaper to paint than [`Self::show`] for complex documents, and works with any egui
+    /// backend, not only with egui_skia's painter.
+    pub fn show_texture(&mut self, ui: &mut Ui, size: Vec2) -> Response {
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        let size_px = [
+            (size.x * pixels_per_point).round().max(1.0) as usize,
+            (size.y * pixels_per_point).round().max(1.0) as usize,
+        ];
+
+        if self.texture.as_ref().map(|(size, _)| *size) != Some(size_px) {
+            if let Some(image) = self.rasterize(size_px) {
+                let texture = ui
+                    .ctx()
+                    .load_texture("egui_skia svg", image, TextureOptions::LINEAR);
+                self.texture = Some((size_px, texture));
+            }
+        }
+
+        match &self.texture {
+            Some((_, texture)) => ui.add(egui::Image::new((texture.id(), size))),
+            None => ui.allocate_exact_size(size, Sense::hover()).1,
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Golden image tests for uis rendered with egui_skia.
+//!
+//! [`assert_ui_snapshot`] renders a ui headlessly and compares it with a reference png, by
+//! default in `tests/snapshots` of the crate under test. Set the `UPDATE_SNAPSHOTS` environment
+//! variable to write the current renderings as the new references.
+//!
+//! On a mismatch the current rendering is written next to the reference as `<name>.new.png`,
+//! together with `<name>.diff.png`, which highlights the differing pixels in red.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use egui::Context;
+use skia_safe::{
+    images, AlphaType, CachingHint, ColorType, Data, EncodedImageFormat, Image, ImageInfo,
+};
+
+use crate::{rasterize, RasterizeOptions};
+
+#[derive(Clone)]
+pub struct SnapshotOptions {
+    /// The directory of the reference images. Defaults to `tests/snapshots` in the directory
+    /// of the crate under test.
+    pub directory: PathBuf,
+    /// The largest difference of a color channel of a pixel that still counts as equal.
+    pub tolerance: u8,
+    /// The number of differing pixels that is still accepted.
+    pub max_failed_pixels: usize,
+    pub rasterize_options: RasterizeOptions,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default();
+        Self {
+            directory: Path::new(&manifest_dir).join("tests").join("snapshots"),
+            tolerance: 2,
+            max_failed_pixels: 0,
+            rasterize_options: RasterizeOptions::default(),
+        }
+    }
+}
+
+/// Render `ui` at `size` pixels and compare it with the reference image `<name>.png`.
+///
+/// # Panics
+///
+/// If the reference is missing, has another size, or more pixels than allowed differ.
+pub fn assert_ui_snapshot(name: &str, size: (i32, i32), ui: impl FnMut(&Context)) {
+    assert_ui_snapshot_with(name, size, ui, &SnapshotOptions::default());
+}
+
+/// Like [`assert_ui_snapshot`], with the tolerance and location of the references set in
+/// `options`.
+pub fn assert_ui_snapshot_with(
+    name: &str,
+    size: (i32, i32),
+    ui: impl FnMut(&Context),
+    options: &SnapshotOptions,
+) {
+    let image = rasterize(size, ui, Some(options.rasterize_options.clone())).image_snapshot();
+
+    let reference_path = options.directory.join(format!("{name}.png"));
+    let new_path = options.directory.join(format!("{name}.new.png"));
+    let diff_path = options.directory.join(format!("{name}.diff.png"));
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        write_png(&reference_path, &image);
+        let _ = std::fs::remove_file(&new_path);
+        let _ = std::fs::remove_file(&diff_path);
+        return;
+    }
+
+    let Some(reference) = std::fs::read(&reference_path)
+        .ok()
+        .and_then(|data| images::deferred_from_encoded_data(Data::new_copy(&data), None))
+    else {
+        write_png(&new_path, &image);
+        panic!(
+            "Missing snapshot {}, the rendering was written to {}. Run with UPDATE_SNAPSHOTS=1 \
+             to accept it.",
+            reference_path.display(),
+            new_path.display()
+        );
+    };
+
+    if reference.dimensions() != image.dimensions() {
+        write_png(&new_path, &image);
+        panic!(
+            "Snapshot {name} is {:?}, but the ui was rendered at {:?}",
+            reference.dimensions(),
+            image.dimensions()
+        );
+    }
+
+    let actual = unpremul_pixels(&image);
+    let expected = unpremul_pixels(&reference);
+
+    let mut failed_pixels = 0;
+    let diff: Vec<u8> = actual
+        .chunks_exact(4)
+        .zip(expected.chunks_exact(4))
+        .flat_map(|(actual, expected)| {
+            let differs = actual
+                .iter()
+                .zip(expected)
+                .any(|(a, e)| a.abs_diff(*e) > options.tolerance);
+            if differs {
+                failed_pixels += 1;
+                [255, 0, 0, 255]
+            } else {
+                // Fade matching pixels, so the differences stand out.
+                [expected[0] / 4, expected[1] / 4, expected[2] / 4, 255]
+            }
+        })
+        .collect();
+
+    if failed_pixels > options.max_failed_pixels {
+        write_png(&new_path, &image);
+        let info = rgba_info(image.width(), image.height());
+        let row_bytes = info.min_row_bytes();
+        if let Some(diff_image) = images::raster_from_data(&info, Data::new_copy(&diff), row_bytes)
+        {
+            write_png(&diff_path, &diff_image);
+        }
+        panic!(
+            "Snapshot {name}: {failed_pixels} pixels differ by more than {}, see {} and {}",
+            options.tolerance,
+            new_path.display(),
+            diff_path.display()
+        );
+    }
+
+    let _ = std::fs::remove_file(&new_path);
+    let _ = std::fs::remove_file(&diff_path);
+}
+
+fn rgba_info(width: i32, height: i32) -> ImageInfo {
+    ImageInfo::new(
+        (width, height),
+        ColorType::RGBA8888,
+        AlphaType::Unpremul,
+        None,
+    )
+}
+
+fn unpremul_pixels(image: &Image) -> Vec<u8> {
+    let info = rgba_info(image.width(), image.height());
+    let row_bytes = info.min_row_bytes();
+    let mut pixels = vec![0; row_bytes * info.height() as usize];
+
+    image.read_pixels(&info, &mut pixels, row_bytes, (0, 0), CachingHint::Allow);
+    pixels
+}
+
+fn write_png(path: &Path, image: &Image) {
+    let data = image
+        .encode_to_data(EncodedImageFormat::PNG)
+        .expect("Failed to encode image");
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory).expect("Failed to create the snapshot directory");
+    }
+    std::fs::File::create(path)
+        .and_then(|mut file| file.write_all(&data))
+        .unwrap_or_else(|err| panic!("Failed to write {}: {err}", path.display()));
+}
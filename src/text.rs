@@ -0,0 +1,212 @@
+//! Draw egui text with Skia's own font rendering instead of glyphs from egui's font atlas.
+//!
+//! Text is drawn as positioned glyphs laid out by egui, so it keeps egui's layout, but the
+//! glyph shapes come from Skia typefaces. That gives crisp text at any zoom level, hinting on
+//! raster surfaces and real text in pdf and svg exports. See
+//! [`Painter::set_font_registry`](crate::Painter::set_font_registry).
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use egui::epaint::TextShape;
+use egui::{Color32, FontFamily};
+use skia_safe::{Canvas, Font, FontMgr, FontStyle, Point, Rect, TextBlob, Typeface};
+
+use crate::vector::{fill_paint, stroke_paint};
+
+/// Maps egui's [`FontFamily`]s to Skia typefaces.
+///
+/// Proportional text uses the system's default typeface and monospace text the system's
+/// monospace typeface, unless other typefaces are set. Glyphs that none of the typefaces of a
+/// family contain, e.g. emojis or CJK characters, are looked up in the system's fonts.
+pub struct FontRegistry {
+    font_mgr: FontMgr,
+    families: HashMap<FontFamily, Vec<Typeface>>,
+    /// The typefaces characters were looked up for, including characters no typeface has.
+    lookups: RefCell<HashMap<(FontFamily, char), Option<Typeface>>>,
+}
+
+impl FontRegistry {
+    pub fn new() -> Self {
+        let font_mgr = FontMgr::default();
+        let mut families = HashMap::new();
+        if let Some(typeface) = font_mgr.legacy_make_typeface(None, FontStyle::normal()) {
+            families.insert(FontFamily::Proportional, vec![typeface]);
+        }
+        if let Some(typeface) = font_mgr.match_family_style("monospace", FontStyle::normal()) {
+            families.insert(FontFamily::Monospace, vec![typeface]);
+        }
+        Self {
+            font_mgr,
+            families,
+            lookups: RefCell::default(),
+        }
+    }
+
+    /// Use `typefaces` for `family`, in order of preference.
+    pub fn set_family(&mut self, family: FontFamily, typefaces: Vec<Typeface>) {
+        self.families.insert(family, typefaces);
+        self.lookups.get_mut().clear();
+    }
+
+    /// Load a font file, e.g. the same ttf that is passed to egui's
+    /// [`FontDefinitions`](egui::FontDefinitions), and add it to `family` with the lowest
+    /// preference. Returns `false` if the data isn't a font Skia can read.
+    pub fn add_font_data(&mut self, family: FontFamily, data: &[u8]) -> bool {
+        let Some(typeface) = self.font_mgr.new_from_data(data, None) else {
+            return false;
+        };
+        self.families.entry(family).or_default().push(typeface);
+        self.lookups.get_mut().clear();
+        true
+    }
+
+    /// The typeface `chr` of `family` is drawn with.
+    ///
+    /// Lookups are cached, so the system's fonts are only searched once per character.
+    pub fn typeface(&self, family: &FontFamily, chr: char) -> Option<Typeface> {
+        let key = (family.clone(), chr);
+        if let Some(typeface) = self.lookups.borrow().get(&key) {
+            return typeface.clone();
+        }
+        let typeface = self.find_typeface(family, chr);
+        self.lookups.borrow_mut().insert(key, typeface.clone());
+        typeface
+    }
+
+    fn find_typeface(&self, family: &FontFamily, chr: char) -> Option<Typeface> {
+        let typefaces = self
+            .families
+            .get(family)
+            .or_else(|| self.families.get(&FontFamily::Proportional))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        typefaces
+            .iter()
+            .find(|typeface| typeface.unichar_to_glyph(chr as i32) != 0)
+            .cloned()
+            .or_else(|| {
+                self.font_mgr
+                    .match_family_style_character("", FontStyle::normal(), &[], chr as i32)
+            })
+            .or_else(|| typefaces.first().cloned())
+    }
+}
+
+impl Default for FontRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Draw `text` onto `canvas` in points, with the glyph positions egui laid out and the glyph
+/// shapes of the typefaces in `fonts`.
+///
+/// Backgrounds, underlines and strikethroughs of the text's sections are drawn as well.
+pub fn draw_text(canvas: &Canvas, text: &TextShape, fonts: &FontRegistry, pixels_per_point: f32) {
+    if text.galley.is_empty() || text.opacity_factor <= 0.0 {
+        return;
+    }
+
+    canvas.save();
+    canvas.translate((text.pos.x, text.pos.y));
+    if text.angle != 0.0 {
+        canvas.rotate(text.angle.to_degrees(), None);
+    }
+
+    let sections = &text.galley.job.sections;
+    let color = |color: Color32| {
+        let color = text.override_text_color.unwrap_or(color);
+        let color = if color == Color32::PLACEHOLDER {
+            text.fallback_color
+        } else {
+            color
+        };
+        color.gamma_multiply(text.opacity_factor)
+    };
+
+    for row in &text.galley.rows {
+        // Runs of glyphs with the same section and typeface, drawn as one blob each.
+        let mut start = 0;
+        while start < row.glyphs.len() {
+            let first = &row.glyphs[start];
+            let section = first.section_index as usize;
+            let format = &sections[section].format;
+            let typeface = fonts.typeface(&format.font_id.family, first.chr);
+            let typeface_id = typeface.as_ref().map(Typeface::unique_id);
+
+            let mut end = start + 1;
+            while end < row.glyphs.len() {
+                let glyph = &row.glyphs[end];
+                let glyph_typeface = fonts.typeface(&format.font_id.family, glyph.chr);
+                if glyph.section_index as usize != section
+                    || glyph_typeface.as_ref().map(Typeface::unique_id) != typeface_id
+                {
+                    break;
+                }
+                end += 1;
+            }
+            let glyphs = &row.glyphs[start..end];
+            start = end;
+
+            let first = &glyphs[0];
+            let last = &glyphs[glyphs.len() - 1];
+            let left = row.pos.x + first.pos.x;
+            let right = row.pos.x + last.pos.x + last.advance_width;
+            let baseline = row.pos.y + first.pos.y;
+            let top = baseline - first.font_ascent;
+            let bottom = top + first.line_height;
+
+            if format.background != Color32::TRANSPARENT {
+                canvas.draw_rect(
+                    Rect::new(left, top, right, bottom),
+                    &fill_paint(color(format.background)),
+                );
+            }
+
+            let Some(typeface) = typeface else {
+                continue;
+            };
+            let mut font = Font::from_typeface(typeface, format.font_id.size);
+            font.set_subpixel(true);
+            if format.italics {
+                font.set_skew_x(-0.25);
+            }
+
+            let string = glyphs.iter().map(|glyph| glyph.chr).collect::<String>();
+            let positions = glyphs
+                .iter()
+                .map(|glyph| Point::new(row.pos.x + glyph.pos.x, row.pos.y + glyph.pos.y))
+                .collect::<Vec<_>>();
+            if let Some(blob) = TextBlob::from_pos_text(string, &positions, &font) {
+                canvas.draw_text_blob(&blob, (0, 0), &fill_paint(color(format.color)));
+            }
+
+            for (stroke, y) in [
+                (format.underline, bottom),
+                (format.strikethrough, (top + bottom) / 2.0),
+            ] {
+                if !stroke.is_empty() {
+                    let stroke = egui::Stroke::new(stroke.width, color(stroke.color));
+                    canvas.draw_line(
+                        (left, y),
+                        (right, y),
+                        &stroke_paint(stroke, pixels_per_point),
+                    );
+                }
+            }
+        }
+    }
+
+    if !text.underline.is_empty() {
+        let rect = text.galley.rect;
+        canvas.draw_line(
+            (rect.min.x, rect.max.y),
+            (rect.max.x, rect.max.y),
+            &stroke_paint(text.underline, pixels_per_point),
+        );
+    }
+
+    canvas.restore();
+}
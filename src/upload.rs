@@ -0,0 +1,172 @@
+//! Converting texture deltas into pixels on a worker thread, see
+//! [`Painter::set_async_uploads`](crate::Painter::set_async_uploads).
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use egui::epaint::ahash::AHashMap;
+use egui::epaint::ImageDelta;
+use egui::{TextureId, TextureOptions};
+
+use crate::painter::delta_pixels;
+
+struct Job {
+    job: u64,
+    tex_id: TextureId,
+    /// A full upload followed by patches.
+    deltas: Vec<ImageDelta>,
+}
+
+/// The pixels of a texture after applying the deltas of a job.
+pub(crate) struct Converted {
+    pub tex_id: TextureId,
+    pub size: [usize; 2],
+    pub pixels: Vec<u8>,
+    pub options: TextureOptions,
+    /// Patches that arrived while the job was being converted, to apply on top.
+    pub patches: Vec<ImageDelta>,
+}
+
+pub(crate) struct UploadWorker {
+    jobs: Sender<Job>,
+    results: Receiver<(u64, Converted)>,
+    next_job: u64,
+    /// The latest job of every texture that is being converted. Results of older jobs are
+    /// dropped.
+    in_flight: AHashMap<TextureId, u64>,
+    deferred_patches: AHashMap<TextureId, Vec<ImageDelta>>,
+}
+
+impl UploadWorker {
+    pub fn new() -> Option<Self> {
+        let (jobs, job_receiver) = channel::<Job>();
+        let (result_sender, results) = channel();
+
+        std::thread::Builder::new()
+            .name("egui_skia uploads".to_owned())
+            .spawn(move || {
+                // Ends once the worker, and with it the sender, is dropped.
+                for Job {
+                    job,
+                    tex_id,
+                    deltas,
+                } in job_receiver
+                {
+                    let converted = convert(tex_id, deltas);
+                    if result_sender.send((job, converted)).is_err() {
+                        break;
+                    }
+                }
+            })
+            .ok()?;
+
+        Some(Self {
+            jobs,
+            results,
+            next_job: 0,
+            in_flight: AHashMap::new(),
+            deferred_patches: AHashMap::new(),
+        })
+    }
+
+    /// Convert `deltas`, which start with a full upload, on the worker thread.
+    pub fn submit(&mut self, tex_id: TextureId, deltas: Vec<ImageDelta>) {
+        let job = self.next_job;
+        self.next_job += 1;
+        self.deferred_patches.remove(&tex_id);
+        if self
+            .jobs
+            .send(Job {
+                job,
+                tex_id,
+                deltas,
+            })
+            .is_ok()
+        {
+            self.in_flight.insert(tex_id, job);
+        }
+    }
+
+    /// Hold back `patches` of a texture that is being converted, until the conversion is
+    /// done. Returns them if the texture isn't being converted.
+    pub fn defer_patches(
+        &mut self,
+        tex_id: TextureId,
+        patches: Vec<ImageDelta>,
+    ) -> Option<Vec<ImageDelta>> {
+        if !self.in_flight.contains_key(&tex_id) {
+            return Some(patches);
+        }
+        self.deferred_patches
+            .entry(tex_id)
+            .or_default()
+            .extend(patches);
+        None
+    }
+
+    /// Drop the conversion of a freed texture.
+    pub fn cancel(&mut self, tex_id: TextureId) {
+        self.in_flight.remove(&tex_id);
+        self.deferred_patches.remove(&tex_id);
+    }
+
+    pub fn is_converting(&self, tex_id: TextureId) -> bool {
+        self.in_flight.contains_key(&tex_id)
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.in_flight.is_empty()
+    }
+
+    /// The textures whose conversion finished since the last call.
+    pub fn finished(&mut self) -> Vec<Converted> {
+        let mut finished = Vec::new();
+        while let Ok((job, mut converted)) = self.results.try_recv() {
+            if self.in_flight.get(&converted.tex_id) != Some(&job) {
+                continue;
+            }
+            self.in_flight.remove(&converted.tex_id);
+            converted.patches = self
+                .deferred_patches
+                .remove(&converted.tex_id)
+                .unwrap_or_default();
+            finished.push(converted);
+        }
+        finished
+    }
+}
+
+fn convert(tex_id: TextureId, deltas: Vec<ImageDelta>) -> Converted {
+    let mut deltas = deltas.into_iter();
+    let full = deltas.next().expect("jobs start with a full upload");
+    let size = full.image.size();
+    let mut pixels = delta_pixels(&full.image, None);
+    let mut options = full.options;
+
+    for patch in deltas {
+        let pos = patch.pos.unwrap_or([0, 0]);
+        let patch_pixels = delta_pixels(&patch.image, None);
+        let row_bytes = patch.image.width() * 4;
+        // Pixels of a patch reaching past the texture are dropped, like in the synchronous
+        // upload, instead of wrapping into the next row.
+        let row_len = row_bytes.min(size[0].saturating_sub(pos[0]) * 4);
+        for (row, source) in patch_pixels.chunks_exact(row_bytes.max(1)).enumerate() {
+            if pos[1] + row >= size[1] {
+                break;
+            }
+            let start = ((pos[1] + row) * size[0] + pos[0]) * 4;
+            let source = &source[..row_len];
+            if let Some(target) = pixels.get_mut(start..start + source.len()) {
+                target.copy_from_slice(source);
+            }
+        }
+        options = patch.options;
+    }
+
+    Converted {
+        tex_id,
+        size,
+        pixels,
+        options,
+        patches: Vec::new(),
+    }
+}
@@ -0,0 +1,241 @@
+//! Helpers for drawing egui geometry with native Skia primitives instead of tessellated meshes.
+
+use egui::epaint::{ColorMode, PathStroke, RectShape};
+use egui::{Shape, StrokeKind};
+use skia_safe::{
+    image_filters, BlurStyle, Canvas, Color, ImageFilter, MaskFilter, Paint, PaintStyle, Path,
+    Point, RRect, Rect, Vector,
+};
+
+/// The paint for drawing `stroke` as a Skia path stroke.
+///
+/// Strokes that are at most one physical pixel wide become hairlines (stroke width `0`), which
+/// Skia renders exactly one device pixel wide under any canvas scaling and in exports, instead
+/// of a scaled strip that blurs. Wider strokes keep their width in points.
+pub fn stroke_paint(stroke: egui::Stroke, pixels_per_point: f32) -> Paint {
+    let [r, g, b, a] = stroke.color.to_srgba_unmultiplied();
+
+    let mut paint = Paint::default();
+    paint.set_anti_alias(true);
+    paint.set_style(PaintStyle::Stroke);
+    paint.set_color(Color::from_argb(a, r, g, b));
+
+    if stroke.width * pixels_per_point <= 1.0 {
+        paint.set_stroke_width(0.0);
+        // Hairlines can't get thinner, fade them instead.
+        paint.set_alpha_f(paint.alpha_f() * (stroke.width * pixels_per_point).max(0.0));
+    } else {
+        paint.set_stroke_width(stroke.width);
+    }
+
+    paint
+}
+
+/// The paint for filling with `color`.
+pub fn fill_paint(color: egui::Color32) -> Paint {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+
+    let mut paint = Paint::default();
+    paint.set_anti_alias(true);
+    paint.set_color(Color::from_argb(a, r, g, b));
+    paint
+}
+
+/// Whether [`draw_shape`] can draw `shape` with native Skia geometry.
+///
+/// Text, meshes, textured or blurred rects, paths with uv dependent stroke colors or inside or
+/// outside strokes, and paint callbacks have to be tessellated instead.
+pub fn is_native(shape: &Shape) -> bool {
+    match shape {
+        Shape::Noop | Shape::Circle(_) | Shape::Ellipse(_) | Shape::LineSegment { .. } => true,
+        Shape::Vec(shapes) => shapes.iter().all(is_native),
+        Shape::Rect(rect) => rect.brush.is_none() && rect.blur_width <= 0.0,
+        Shape::Path(path) => is_solid(&path.stroke),
+        Shape::QuadraticBezier(bezier) => is_solid(&bezier.stroke),
+        Shape::CubicBezier(bezier) => is_solid(&bezier.stroke),
+        Shape::Text(_) | Shape::Mesh(_) | Shape::Callback(_) => false,
+    }
+}
+
+fn is_solid(stroke: &PathStroke) -> bool {
+    // Skia only strokes centered on the outline.
+    matches!(stroke.color, ColorMode::Solid(_)) && stroke.kind == StrokeKind::Middle
+}
+
+/// Draw `shape` onto `canvas` with native Skia geometry and antialiasing, in points.
+///
+/// Unlike tessellated meshes, the result is resolution independent, e.g. in pdf and svg
+/// exports. Blurred rects, e.g. egui's [`Shadow`](egui::Shadow)s, are drawn with Skia's
+/// analytic blur. Other shapes that aren't [`is_native`] are skipped.
+pub fn draw_shape(canvas: &Canvas, shape: &Shape, pixels_per_point: f32) {
+    match shape {
+        Shape::Noop | Shape::Text(_) | Shape::Mesh(_) | Shape::Callback(_) => {}
+        Shape::Vec(shapes) => {
+            for shape in shapes {
+                draw_shape(canvas, shape, pixels_per_point);
+            }
+        }
+        Shape::Circle(circle) => {
+            let center = point(circle.center);
+            if circle.fill != egui::Color32::TRANSPARENT {
+                canvas.draw_circle(center, circle.radius, &fill_paint(circle.fill));
+            }
+            if !circle.stroke.is_empty() {
+                let paint = stroke_paint(circle.stroke, pixels_per_point);
+                canvas.draw_circle(center, circle.radius, &paint);
+            }
+        }
+        Shape::Ellipse(ellipse) => {
+            let oval = Rect::from_point_and_size(
+                point(ellipse.center - ellipse.radius),
+                (ellipse.radius.x * 2.0, ellipse.radius.y * 2.0),
+            );
+            if ellipse.fill != egui::Color32::TRANSPARENT {
+                canvas.draw_oval(oval, &fill_paint(ellipse.fill));
+            }
+            if !ellipse.stroke.is_empty() {
+                canvas.draw_oval(oval, &stroke_paint(ellipse.stroke, pixels_per_point));
+            }
+        }
+        Shape::LineSegment { points, stroke } => {
+            if !stroke.is_empty() {
+                let paint = stroke_paint(*stroke, pixels_per_point);
+                canvas.draw_line(point(points[0]), point(points[1]), &paint);
+            }
+        }
+        Shape::Rect(rect_shape) => draw_rect(canvas, rect_shape, pixels_per_point),
+        Shape::Path(path) => {
+            let mut skia_path = Path::new();
+            skia_path.add_poly(
+                &path.points.iter().copied().map(point).collect::<Vec<_>>(),
+                path.closed,
+            );
+            fill_and_stroke(
+                canvas,
+                &skia_path,
+                path.fill,
+                &path.stroke,
+                pixels_per_point,
+            );
+        }
+        Shape::QuadraticBezier(bezier) => {
+            let [start, control, end] = bezier.points.map(point);
+            let mut path = Path::new();
+            path.move_to(start).quad_to(control, end);
+            if bezier.closed {
+                path.close();
+            }
+            fill_and_stroke(canvas, &path, bezier.fill, &bezier.stroke, pixels_per_point);
+        }
+        Shape::CubicBezier(bezier) => {
+            let [start, control1, control2, end] = bezier.points.map(point);
+            let mut path = Path::new();
+            path.move_to(start).cubic_to(control1, control2, end);
+            if bezier.closed {
+                path.close();
+            }
+            fill_and_stroke(canvas, &path, bezier.fill, &bezier.stroke, pixels_per_point);
+        }
+    }
+}
+
+fn draw_rect(canvas: &Canvas, rect_shape: &RectShape, pixels_per_point: f32) {
+    let rect = rect_shape.rect;
+    let corner_radius = rect_shape.corner_radius;
+    let rrect = |rect: egui::Rect, grow: f32| {
+        let radius = |r: u8| {
+            let r = (r as f32 + grow).max(0.0);
+            Vector::new(r, r)
+        };
+        RRect::new_rect_radii(
+            Rect::new(rect.min.x, rect.min.y, rect.max.x, rect.max.y),
+            &[
+                radius(corner_radius.nw),
+                radius(corner_radius.ne),
+                radius(corner_radius.se),
+                radius(corner_radius.sw),
+            ],
+        )
+    };
+
+    if rect_shape.fill != egui::Color32::TRANSPARENT {
+        let mut paint = fill_paint(rect_shape.fill);
+        if rect_shape.blur_width > 0.0 {
+            // egui fades blurred rects linearly over `blur_width`, centered on the edge. A
+            // gaussian with the same variance looks the closest.
+            let sigma = rect_shape.blur_width / 12f32.sqrt();
+            paint.set_mask_filter(MaskFilter::blur(BlurStyle::Normal, sigma, true));
+        }
+        canvas.draw_rrect(rrect(rect, 0.0), &paint);
+    }
+
+    let stroke = rect_shape.stroke;
+    if !stroke.is_empty() {
+        // Skia strokes are centered on the outline.
+        let offset = match rect_shape.stroke_kind {
+            StrokeKind::Inside => -stroke.width / 2.0,
+            StrokeKind::Middle => 0.0,
+            StrokeKind::Outside => stroke.width / 2.0,
+        };
+        let paint = stroke_paint(stroke, pixels_per_point);
+        canvas.draw_rrect(rrect(rect.expand(offset), offset), &paint);
+    }
+}
+
+fn fill_and_stroke(
+    canvas: &Canvas,
+    path: &Path,
+    fill: egui::Color32,
+    stroke: &PathStroke,
+    pixels_per_point: f32,
+) {
+    if fill != egui::Color32::TRANSPARENT {
+        canvas.draw_path(path, &fill_paint(fill));
+    }
+    if let ColorMode::Solid(color) = stroke.color {
+        let stroke = egui::Stroke::new(stroke.width, color);
+        if !stroke.is_empty() {
+            canvas.draw_path(path, &stroke_paint(stroke, pixels_per_point));
+        }
+    }
+}
+
+fn point(pos: egui::Pos2) -> Point {
+    Point::new(pos.x, pos.y)
+}
+
+/// A drop shadow, in the units of the canvas it is drawn on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DropShadow {
+    /// How far the shadow is moved from the shape.
+    pub offset: egui::Vec2,
+    /// The blur radius, like the blur of css' `box-shadow`.
+    pub blur: f32,
+    pub color: egui::Color32,
+}
+
+impl DropShadow {
+    /// An image filter that draws the shadow behind whatever is drawn with it. Set it on the
+    /// paint of any draw call with [`Paint::set_image_filter`].
+    pub fn image_filter(&self) -> Option<ImageFilter> {
+        let [r, g, b, a] = self.color.to_srgba_unmultiplied();
+        // Skia's sigma is half of the blur radius.
+        let sigma = self.blur.max(0.0) / 2.0;
+        image_filters::drop_shadow(
+            (self.offset.x, self.offset.y),
+            (sigma, sigma),
+            Color::from_argb(a, r, g, b),
+            None,
+            None,
+            None,
+        )
+    }
+}
+
+/// Draw `path` with `paint` and `shadow` behind it, e.g. from a paint callback. Rects and
+/// rounded rects can be drawn as paths with [`Path::rect`] and [`Path::rrect`].
+pub fn draw_with_shadow(canvas: &Canvas, path: &Path, paint: &Paint, shadow: DropShadow) {
+    let mut paint = paint.clone();
+    paint.set_image_filter(shadow.image_filter());
+    canvas.draw_path(path, &paint);
+}
@@ -0,0 +1,152 @@
+//! Paint the viewports of a [`Context`](egui::Context) that uses egui's multi-viewport api into
+//! separate surfaces.
+
+use std::sync::Arc;
+
+use egui::epaint::ahash::AHashMap;
+use egui::epaint::ImageDelta;
+use egui::viewport::ViewportIdMap;
+use egui::{ClippedPrimitive, ImageData, TextureId, TexturesDelta, ViewportId};
+use skia_safe::Canvas;
+
+use crate::{EguiSkiaError, Painter};
+
+/// A [`Painter`] per viewport, for apps that open windows with [`egui::ViewportBuilder`].
+///
+/// egui sends texture updates with the output of whichever viewport it ran, but all viewports
+/// use the same textures. The `ViewportPainter` forwards every update to the painters of the
+/// other viewports, and keeps a copy of all textures to set up the painters of viewports that
+/// open later. Every painter has its own textures, so viewports can be painted onto surfaces
+/// of different gpu contexts.
+///
+/// Run each viewport with [`egui::Context::run`] and a [`egui::RawInput::viewport_id`], then
+/// paint its output with [`Self::paint`] onto the surface of its window. Call
+/// [`Self::retain_viewports`] with the viewports egui still wants, to free the painters of
+/// closed windows.
+#[derive(Default)]
+pub struct ViewportPainter {
+    painters: ViewportIdMap<Painter>,
+    /// Texture updates the painter of a viewport hasn't received yet.
+    pending: ViewportIdMap<TexturesDelta>,
+    /// The current, fully patched image of every texture.
+    textures: AHashMap<TextureId, ImageDelta>,
+}
+
+impl ViewportPainter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The painter of `viewport_id`, e.g. to set its options or gpu context. It is created if
+    /// the viewport doesn't have one yet.
+    pub fn painter_mut(&mut self, viewport_id: ViewportId) -> &mut Painter {
+        if !self.painters.contains_key(&viewport_id) {
+            // A new painter gets all textures that exist so far.
+            let set = self
+                .textures
+                .iter()
+                .map(|(id, delta)| (*id, delta.clone()))
+                .collect();
+            self.pending.insert(
+                viewport_id,
+                TexturesDelta {
+                    set,
+                    free: Vec::new(),
+                },
+            );
+            self.painters.insert(viewport_id, Painter::new());
+        }
+        self.painters.get_mut(&viewport_id).unwrap()
+    }
+
+    /// Paint the output of running `viewport_id` onto `canvas`, the canvas of the viewport's
+    /// window, and hand the texture updates on to the other viewports.
+    pub fn paint(
+        &mut self,
+        viewport_id: ViewportId,
+        canvas: &Canvas,
+        pixels_per_point: f32,
+        primitives: Vec<ClippedPrimitive>,
+        textures_delta: TexturesDelta,
+    ) -> Result<(), EguiSkiaError> {
+        self.painter_mut(viewport_id);
+
+        for (id, pending) in self.pending.iter_mut() {
+            if *id != viewport_id {
+                merge_delta(pending, textures_delta.clone());
+            }
+        }
+        self.update_textures(&textures_delta);
+
+        let mut delta = std::mem::take(self.pending.entry(viewport_id).or_default());
+        merge_delta(&mut delta, textures_delta);
+
+        self.painters
+            .get_mut(&viewport_id)
+            .unwrap()
+            .try_paint_and_update_textures(canvas, pixels_per_point, primitives, delta)
+    }
+
+    /// Drop the painters of all viewports not in `viewport_ids`, e.g. the keys of
+    /// [`egui::FullOutput::viewport_output`]. The root viewport is always kept.
+    pub fn retain_viewports(&mut self, viewport_ids: impl IntoIterator<Item = ViewportId>) {
+        let keep = viewport_ids.into_iter().collect::<Vec<_>>();
+        let retain = |id: &ViewportId| *id == ViewportId::ROOT || keep.contains(id);
+        self.painters.retain(|id, _| retain(id));
+        self.pending.retain(|id, _| retain(id));
+    }
+
+    /// Apply `textures_delta` to the copies of the textures.
+    fn update_textures(&mut self, textures_delta: &TexturesDelta) {
+        for (tex_id, delta) in &textures_delta.set {
+            match delta.pos {
+                None => {
+                    self.textures.insert(*tex_id, delta.clone());
+                }
+                Some(pos) => {
+                    let Some(texture) = self.textures.get_mut(tex_id) else {
+                        continue;
+                    };
+                    let (ImageData::Color(image), ImageData::Color(patch)) =
+                        (&mut texture.image, &delta.image);
+                    let image = Arc::make_mut(image);
+                    let [width, height] = image.size;
+                    // Pixels of a patch reaching past the right edge are dropped, instead of
+                    // wrapping into the next row.
+                    let row_len = patch.width().min(width.saturating_sub(pos[0]));
+                    for (row, pixels) in patch.pixels.chunks_exact(patch.width().max(1)).enumerate()
+                    {
+                        if pos[1] + row >= height {
+                            break;
+                        }
+                        let start = (pos[1] + row) * width + pos[0];
+                        let pixels = &pixels[..row_len];
+                        if let Some(target) = image.pixels.get_mut(start..start + pixels.len()) {
+                            target.copy_from_slice(pixels);
+                        }
+                    }
+                    texture.options = delta.options;
+                }
+            }
+        }
+        for tex_id in &textures_delta.free {
+            self.textures.remove(tex_id);
+        }
+    }
+}
+
+/// Add `delta` to the texture updates `pending` for a painter. A full image replaces all
+/// earlier updates of its texture, and freeing a texture drops its pending updates, so
+/// painters of hidden viewports don't pile up images they never paint.
+fn merge_delta(pending: &mut TexturesDelta, delta: TexturesDelta) {
+    for (tex_id, image_delta) in delta.set {
+        if image_delta.is_whole() {
+            pending.set.retain(|(id, _)| *id != tex_id);
+        }
+        pending.set.push((tex_id, image_delta));
+    }
+    for tex_id in delta.free {
+        pending.set.retain(|(id, _)| *id != tex_id);
+        pending.free.push(tex_id);
+    }
+}
@@ -0,0 +1,60 @@
+//! Ready-made egui widgets that draw with Skia.
+
+use std::sync::Arc;
+
+use egui::{Response, Sense, Ui, Vec2, Widget};
+use skia_safe::{Canvas, ClipOp, Rect};
+
+use crate::EguiSkiaPaintCallback;
+
+/// A widget of a fixed size that draws with Skia:
+///
+/// ```ignore
+/// ui.add(SkiaCanvas::new(egui::vec2(200.0, 100.0), |canvas| {
+///     canvas.draw_circle((50.0, 50.0), 40.0, &Paint::default());
+/// }));
+/// ```
+///
+/// The canvas is in points, with the origin at the top left corner of the widget, so the
+/// drawing is scaled with `pixels_per_point`. Drawing is clipped to the widget and to the clip
+/// rect of the ui. Nothing is drawn while the widget is scrolled out of view.
+pub struct SkiaCanvas<F> {
+    size: Vec2,
+    sense: Sense,
+    draw: F,
+}
+
+impl<F: Fn(&Canvas) + Send + Sync + 'static> SkiaCanvas<F> {
+    pub fn new(size: Vec2, draw: F) -> Self {
+        Self {
+            size,
+            sense: Sense::hover(),
+            draw,
+        }
+    }
+
+    /// The interactions the [`Response`] of the widget reports, e.g. [`Sense::drag`] for a
+    /// canvas the user draws on. Only hovers by default.
+    pub fn sense(mut self, sense: Sense) -> Self {
+        self.sense = sense;
+        self
+    }
+}
+
+impl<F: Fn(&Canvas) + Send + Sync + 'static> Widget for SkiaCanvas<F> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, response) = ui.allocate_exact_size(self.size, self.sense);
+        if ui.is_rect_visible(rect) {
+            let draw = self.draw;
+            let size = rect.size();
+            ui.painter().add(egui::PaintCallback {
+                rect,
+                callback: Arc::new(EguiSkiaPaintCallback::new(move |canvas| {
+                    canvas.clip_rect(Rect::from_wh(size.x, size.y), ClipOp::Intersect, true);
+                    draw(canvas);
+                })),
+            });
+        }
+        response
+    }
+}
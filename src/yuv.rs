@@ -0,0 +1,423 @@
+//! Convert painted frames to planar (I420) or semi-planar (NV12) YUV, so egui overlays can be
+//! fed into video encoding and broadcast pipelines, and show YUV video frames as textures, see
+//! [`Painter::set_yuv_texture`](crate::Painter::set_yuv_texture).
+//!
+//! The conversion runs as Skia color-matrix draws on surfaces compatible with the source
+//! surface, i.e. on the gpu for gpu surfaces. Only reading back the planes happens on the cpu.
+//!
+//! Video frames become Skia YUVA images on the gpu. Skia can only build those with a gpu
+//! context, raster painters convert the planes with a runtime shader instead.
+
+#[cfg(feature = "gpu")]
+use skia_safe::gpu::{DirectContext, Mipmapped};
+use skia_safe::runtime_effect::ChildPtr;
+use skia_safe::{
+    color_filters, images, AlphaType, BlendMode, Color, ColorType, Data, ISize, Image, ImageInfo,
+    Matrix, Paint, Rect, RuntimeEffect, SamplingOptions, Shader, Surface, TileMode,
+};
+#[cfg(feature = "gpu")]
+use skia_safe::{yuva_info, ColorSpace, Pixmap, YUVAInfo, YUVAPixmaps, YUVColorSpace};
+
+/// The memory layout of the converted frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum YuvLayout {
+    /// Three planes: full resolution Y, quarter resolution U and V.
+    I420,
+    /// Two planes: full resolution Y, quarter resolution interleaved UV.
+    Nv12,
+}
+
+/// The YUV matrix coefficients.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum YuvMatrix {
+    Bt601,
+    Bt709,
+}
+
+impl YuvMatrix {
+    /// The luma coefficients `(kr, kb)`.
+    fn coefficients(self) -> (f32, f32) {
+        match self {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct YuvOptions {
+    pub layout: YuvLayout,
+    pub matrix: YuvMatrix,
+    /// Use the full `0..=255` range instead of the limited (video) range.
+    pub full_range: bool,
+}
+
+impl Default for YuvOptions {
+    fn default() -> Self {
+        Self {
+            layout: YuvLayout::I420,
+            matrix: YuvMatrix::Bt709,
+            full_range: false,
+        }
+    }
+}
+
+/// A converted frame.
+pub struct YuvFrame {
+    pub width: i32,
+    pub height: i32,
+    pub layout: YuvLayout,
+    /// Full resolution luma, `width` bytes per row.
+    pub y: Vec<u8>,
+    /// For [`YuvLayout::I420`] the U plane, for [`YuvLayout::Nv12`] the interleaved UV plane.
+    pub uv: Vec<u8>,
+    /// For [`YuvLayout::I420`] the V plane, empty for [`YuvLayout::Nv12`].
+    pub v: Vec<u8>,
+}
+
+/// The planes of a decoded video frame, e.g. the output of a video decoder.
+///
+/// The chroma planes have half the width and height of the luma plane, rounded up.
+#[derive(Clone, Copy, Debug)]
+pub struct YuvPlanes<'a> {
+    pub width: i32,
+    pub height: i32,
+    pub options: YuvOptions,
+    /// Luma, `y_stride` bytes per row.
+    pub y: &'a [u8],
+    pub y_stride: usize,
+    /// For [`YuvLayout::I420`] the U plane, for [`YuvLayout::Nv12`] the interleaved UV plane.
+    pub uv: &'a [u8],
+    pub uv_stride: usize,
+    /// For [`YuvLayout::I420`] the V plane, ignored for [`YuvLayout::Nv12`].
+    pub v: &'a [u8],
+    pub v_stride: usize,
+}
+
+impl<'a> YuvPlanes<'a> {
+    /// The planes of a frame converted with [`surface_to_yuv`].
+    pub fn from_frame(frame: &'a YuvFrame, matrix: YuvMatrix, full_range: bool) -> Self {
+        let chroma_width = ((frame.width + 1) / 2) as usize;
+        Self {
+            width: frame.width,
+            height: frame.height,
+            options: YuvOptions {
+                layout: frame.layout,
+                matrix,
+                full_range,
+            },
+            y: &frame.y,
+            y_stride: frame.width as usize,
+            uv: &frame.uv,
+            uv_stride: match frame.layout {
+                YuvLayout::I420 => chroma_width,
+                YuvLayout::Nv12 => chroma_width * 2,
+            },
+            v: &frame.v,
+            v_stride: chroma_width,
+        }
+    }
+}
+
+const YUV_TO_RGB: &str = r#"
+    uniform shader y_plane;
+    uniform shader u_plane;
+    uniform shader v_plane;
+    uniform float4 r_row;
+    uniform float4 g_row;
+    uniform float4 b_row;
+    uniform float2 chroma_scale;
+    uniform float nv12;
+
+    half4 main(float2 coord) {
+        float2 chroma_coord = coord * chroma_scale;
+        half4 uv = u_plane.eval(chroma_coord);
+        float4 yuv = float4(
+            y_plane.eval(coord).r,
+            uv.r,
+            nv12 > 0.5 ? uv.g : v_plane.eval(chroma_coord).r,
+            1.0
+        );
+        return half4(
+            saturate(half(dot(r_row, yuv))),
+            saturate(half(dot(g_row, yuv))),
+            saturate(half(dot(b_row, yuv))),
+            1.0
+        );
+    }
+"#;
+
+thread_local! {
+    /// [`YUV_TO_RGB`], compiled once per thread.
+    static YUV_TO_RGB_EFFECT: Option<RuntimeEffect> = RuntimeEffect::make_for_shader(YUV_TO_RGB, None)
+        .map_err(|err| log::error!("Failed to compile the YUV shader: {err}"))
+        .ok();
+}
+
+/// The frame of `planes` as a gpu image, converted by Skia from a YUVA image.
+#[cfg(feature = "gpu")]
+pub(crate) fn yuva_texture(context: &mut DirectContext, planes: &YuvPlanes) -> Option<Image> {
+    let size = ISize::new(planes.width, planes.height);
+    let chroma_size = ISize::new((size.width + 1) / 2, (size.height + 1) / 2);
+
+    let color_space = match (planes.options.matrix, planes.options.full_range) {
+        (YuvMatrix::Bt601, true) => YUVColorSpace::JPEGFull,
+        (YuvMatrix::Bt601, false) => YUVColorSpace::Rec601Limited,
+        (YuvMatrix::Bt709, true) => YUVColorSpace::Rec709Full,
+        (YuvMatrix::Bt709, false) => YUVColorSpace::Rec709Limited,
+    };
+    let (config, plane_data) = match planes.options.layout {
+        YuvLayout::I420 => (
+            yuva_info::PlaneConfig::Y_U_V,
+            vec![
+                (planes.y, planes.y_stride, size, ColorType::Gray8),
+                (planes.uv, planes.uv_stride, chroma_size, ColorType::Gray8),
+                (planes.v, planes.v_stride, chroma_size, ColorType::Gray8),
+            ],
+        ),
+        YuvLayout::Nv12 => (
+            yuva_info::PlaneConfig::Y_UV,
+            vec![
+                (planes.y, planes.y_stride, size, ColorType::Gray8),
+                (
+                    planes.uv,
+                    planes.uv_stride,
+                    chroma_size,
+                    ColorType::R8G8UNorm,
+                ),
+            ],
+        ),
+    };
+
+    let info = YUVAInfo::new(
+        size,
+        config,
+        yuva_info::Subsampling::S420,
+        color_space,
+        None,
+        None,
+    )?;
+    let pixmaps = plane_data
+        .into_iter()
+        .map(|(pixels, row_bytes, size, color_type)| {
+            let info = ImageInfo::new(size, color_type, AlphaType::Opaque, None);
+            let pixels = pixels.get(..row_bytes * size.height as usize)?;
+            Pixmap::new(&info, pixels, row_bytes)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let pixmaps = YUVAPixmaps::from_external_pixmaps(&info, &pixmaps)?;
+
+    skia_safe::gpu::images::texture_from_yuva_pixmaps(
+        context,
+        &pixmaps,
+        Mipmapped::No,
+        Some(false),
+        Some(ColorSpace::new_srgb()),
+    )
+}
+
+/// A shader drawing `planes` as RGB, with the frame's pixels at `(0, 0)..(width, height)`.
+///
+/// Every plane is turned into an image with `upload`, e.g. to move it to the gpu, where the
+/// conversion then runs.
+pub(crate) fn yuv_shader(
+    planes: &YuvPlanes,
+    mut upload: impl FnMut(Image) -> Image,
+) -> Option<Shader> {
+    let size = ISize::new(planes.width, planes.height);
+    let chroma_size = ISize::new((size.width + 1) / 2, (size.height + 1) / 2);
+    let nv12 = planes.options.layout == YuvLayout::Nv12;
+
+    let mut plane = |pixels: &[u8], row_bytes: usize, size: ISize, color_type: ColorType| {
+        let info = ImageInfo::new(size, color_type, AlphaType::Opaque, None);
+        let pixels = pixels.get(..row_bytes * size.height as usize)?;
+        let image = images::raster_from_data(&info, Data::new_copy(pixels), row_bytes)?;
+        let image = upload(image);
+        // Sampled in unconverted plane values, without any color management.
+        image.to_shader(
+            (TileMode::Clamp, TileMode::Clamp),
+            SamplingOptions::new(skia_safe::FilterMode::Linear, skia_safe::MipmapMode::None),
+            &Matrix::default(),
+        )
+    };
+    let y = plane(planes.y, planes.y_stride, size, ColorType::Gray8)?;
+    let (u, v) = if nv12 {
+        let uv = plane(
+            planes.uv,
+            planes.uv_stride,
+            chroma_size,
+            ColorType::R8G8UNorm,
+        )?;
+        (uv.clone(), uv)
+    } else {
+        (
+            plane(planes.uv, planes.uv_stride, chroma_size, ColorType::Gray8)?,
+            plane(planes.v, planes.v_stride, chroma_size, ColorType::Gray8)?,
+        )
+    };
+
+    let [r_row, g_row, b_row] = rgb_rows(planes.options);
+    let uniforms = [
+        r_row.as_slice(),
+        &g_row,
+        &b_row,
+        &[
+            chroma_size.width as f32 / size.width as f32,
+            chroma_size.height as f32 / size.height as f32,
+        ],
+        &[if nv12 { 1.0 } else { 0.0 }],
+    ]
+    .concat()
+    .iter()
+    .flat_map(|value| value.to_ne_bytes())
+    .collect::<Vec<_>>();
+
+    YUV_TO_RGB_EFFECT.with(|effect| {
+        effect.as_ref()?.make_shader(
+            Data::new_copy(&uniforms),
+            &[
+                ChildPtr::Shader(y),
+                ChildPtr::Shader(u),
+                ChildPtr::Shader(v),
+            ],
+            None,
+        )
+    })
+}
+
+/// The rows of the matrix converting `(y, u, v, 1)` into red, green and blue.
+fn rgb_rows(options: YuvOptions) -> [[f32; 4]; 3] {
+    let (kr, kb) = options.matrix.coefficients();
+    let kg = 1.0 - kr - kb;
+
+    // Scale and offset of the normalized luma and chroma values.
+    let ((y_scale, y_offset), (c_scale, c_offset)) = if options.full_range {
+        ((1.0, 0.0), (1.0, -128.0 / 255.0))
+    } else {
+        (
+            (255.0 / 219.0, -16.0 / 219.0),
+            (255.0 / 224.0, -128.0 / 224.0),
+        )
+    };
+
+    let row = |cb: f32, cr: f32| {
+        [
+            y_scale,
+            cb * c_scale,
+            cr * c_scale,
+            y_offset + (cb + cr) * c_offset,
+        ]
+    };
+    [
+        row(0.0, 2.0 * (1.0 - kr)),
+        row(-2.0 * kb * (1.0 - kb) / kg, -2.0 * kr * (1.0 - kr) / kg),
+        row(2.0 * (1.0 - kb), 0.0),
+    ]
+}
+
+#[derive(Clone, Copy)]
+enum Plane {
+    Y,
+    U,
+    V,
+}
+
+/// Convert the current contents of `surface` to YUV.
+///
+/// Translucent pixels are treated as if composited over black.
+pub fn surface_to_yuv(surface: &mut Surface, options: YuvOptions) -> Option<YuvFrame> {
+    let size = ISize::new(surface.width(), surface.height());
+    let chroma_size = ISize::new((size.width + 1) / 2, (size.height + 1) / 2);
+
+    // Flatten onto black first, the color matrices work on unpremultiplied colors.
+    let image = {
+        let mut flattened = surface.new_surface(&ImageInfo::new_n32_premul(size, None))?;
+        flattened.canvas().clear(Color::BLACK);
+        flattened
+            .canvas()
+            .draw_image(surface.image_snapshot(), (0.0, 0.0), None);
+        flattened.image_snapshot()
+    };
+
+    let y = convert_plane(surface, &image, size, Plane::Y, options)?;
+    let u = convert_plane(surface, &image, chroma_size, Plane::U, options)?;
+    let v = convert_plane(surface, &image, chroma_size, Plane::V, options)?;
+
+    let (uv, v) = match options.layout {
+        YuvLayout::I420 => (u, v),
+        YuvLayout::Nv12 => (
+            u.iter().zip(&v).flat_map(|(u, v)| [*u, *v]).collect(),
+            Vec::new(),
+        ),
+    };
+
+    Some(YuvFrame {
+        width: size.width,
+        height: size.height,
+        layout: options.layout,
+        y,
+        uv,
+        v,
+    })
+}
+
+fn convert_plane(
+    surface: &mut Surface,
+    image: &Image,
+    size: ISize,
+    plane: Plane,
+    options: YuvOptions,
+) -> Option<Vec<u8>> {
+    let info = ImageInfo::new(size, ColorType::Alpha8, AlphaType::Premul, None);
+    let mut plane_surface = surface.new_surface(&info)?;
+
+    let mut paint = Paint::default();
+    paint.set_blend_mode(BlendMode::Src);
+    paint.set_color_filter(color_filters::matrix_row_major(
+        &plane_matrix(plane, options),
+        None,
+    ));
+
+    plane_surface.canvas().draw_image_rect_with_sampling_options(
+        image,
+        None,
+        Rect::from_isize(size),
+        SamplingOptions::new(skia_safe::FilterMode::Linear, skia_safe::MipmapMode::Linear),
+        &paint,
+    );
+
+    let row_bytes = size.width as usize;
+    let mut pixels = vec![0; row_bytes * size.height as usize];
+    plane_surface
+        .read_pixels(&info, &mut pixels, row_bytes, (0, 0))
+        .then_some(pixels)
+}
+
+/// A color matrix that writes the requested plane into the alpha channel.
+fn plane_matrix(plane: Plane, options: YuvOptions) -> [f32; 20] {
+    let (kr, kb) = options.matrix.coefficients();
+    let kg = 1.0 - kr - kb;
+
+    let (coefficients, offset) = match plane {
+        Plane::Y => ([kr, kg, kb], 0.0),
+        Plane::U => {
+            let s = 0.5 / (1.0 - kb);
+            ([-kr * s, -kg * s, 0.5], 0.5)
+        }
+        Plane::V => {
+            let s = 0.5 / (1.0 - kr);
+            ([0.5, -kg * s, -kb * s], 0.5)
+        }
+    };
+
+    let (scale, offset) = match (options.full_range, plane) {
+        (true, _) => (1.0, offset),
+        (false, Plane::Y) => (219.0 / 255.0, 16.0 / 255.0),
+        (false, _) => (224.0 / 255.0, 128.0 / 255.0),
+    };
+
+    let [r, g, b] = coefficients.map(|c| c * scale);
+    let mut matrix = [0.0; 20];
+    matrix[15..].copy_from_slice(&[r, g, b, 0.0, offset]);
+    matrix
+}
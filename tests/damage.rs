@@ -0,0 +1,86 @@
+//! Damage tracking across frames.
+
+use egui::epaint::{ClippedPrimitive, Primitive};
+use egui::{pos2, vec2, Color32, Mesh, Rect, TexturesDelta};
+use egui_skia::damage::DamageTracker;
+
+const SCREEN: Rect = Rect::from_min_max(pos2(0.0, 0.0), pos2(200.0, 100.0));
+
+fn rect_primitive(rect: Rect, color: Color32) -> ClippedPrimitive {
+    let mut mesh = Mesh::default();
+    mesh.add_colored_rect(rect, color);
+    ClippedPrimitive {
+        clip_rect: SCREEN,
+        primitive: Primitive::Mesh(mesh),
+    }
+}
+
+fn update(tracker: &mut DamageTracker, primitives: &[ClippedPrimitive]) -> Option<Rect> {
+    tracker.update(primitives, &TexturesDelta::default(), SCREEN)
+}
+
+#[test]
+fn first_frame_damages_everything() {
+    let mut tracker = DamageTracker::new();
+    let primitives = [rect_primitive(
+        Rect::from_min_size(pos2(10.0, 10.0), vec2(20.0, 20.0)),
+        Color32::RED,
+    )];
+    assert_eq!(update(&mut tracker, &primitives), Some(SCREEN));
+    assert_eq!(update(&mut tracker, &primitives), None);
+
+    tracker.invalidate();
+    assert_eq!(update(&mut tracker, &primitives), Some(SCREEN));
+}
+
+#[test]
+fn moved_primitive_damages_old_and_new_bounds() {
+    let mut tracker = DamageTracker::new();
+    let before = Rect::from_min_size(pos2(10.0, 10.0), vec2(20.0, 20.0));
+    let after = before.translate(vec2(50.0, 5.0));
+    let still = rect_primitive(
+        Rect::from_min_size(pos2(150.0, 60.0), vec2(10.0, 10.0)),
+        Color32::BLUE,
+    );
+
+    update(
+        &mut tracker,
+        &[still.clone(), rect_primitive(before, Color32::RED)],
+    );
+    let damage = update(&mut tracker, &[still, rect_primitive(after, Color32::RED)]);
+    assert_eq!(damage, Some(before.union(after)));
+}
+
+#[test]
+fn recolored_primitive_damages_its_bounds() {
+    let mut tracker = DamageTracker::new();
+    let rect = Rect::from_min_size(pos2(30.0, 40.0), vec2(15.0, 5.0));
+
+    update(&mut tracker, &[rect_primitive(rect, Color32::RED)]);
+    let damage = update(&mut tracker, &[rect_primitive(rect, Color32::GREEN)]);
+    assert_eq!(damage, Some(rect));
+}
+
+#[test]
+fn many_primitives() {
+    // More primitives than are compared one by one, side by side.
+    let rects: Vec<Rect> = (0..100)
+        .map(|i| {
+            let (x, y) = ((i % 20) as f32 * 10.0, (i / 20) as f32 * 10.0);
+            Rect::from_min_size(pos2(x, y), vec2(8.0, 8.0))
+        })
+        .collect();
+    let frame = |last_color: Color32| {
+        let mut primitives: Vec<_> = rects
+            .iter()
+            .map(|rect| rect_primitive(*rect, Color32::GRAY))
+            .collect();
+        *primitives.last_mut().unwrap() = rect_primitive(rects[99], last_color);
+        primitives
+    };
+
+    let mut tracker = DamageTracker::new();
+    update(&mut tracker, &frame(Color32::GRAY));
+    assert_eq!(update(&mut tracker, &frame(Color32::GRAY)), None);
+    assert_eq!(update(&mut tracker, &frame(Color32::RED)), Some(rects[99]));
+}
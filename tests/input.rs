@@ -0,0 +1,95 @@
+//! Translation of window events into egui input.
+
+use egui::{pos2, vec2, Event, Key, Modifiers, MouseWheelUnit, PointerButton, Rect, TouchPhase};
+use egui_skia::input::InputState;
+
+#[test]
+fn positions_are_converted_to_points() {
+    let mut input = InputState::new([800, 600], 2.0);
+    input.on_pointer_moved((100.0, 50.0));
+    input.on_pointer_button(PointerButton::Primary, true);
+    input.on_wheel(MouseWheelUnit::Point, vec2(0.0, -40.0));
+    input.on_wheel(MouseWheelUnit::Line, vec2(0.0, -3.0));
+
+    let raw_input = input.take_egui_input();
+    assert_eq!(
+        raw_input.screen_rect,
+        Some(Rect::from_min_max(pos2(0.0, 0.0), pos2(400.0, 300.0)))
+    );
+    assert!(matches!(
+        raw_input.events.as_slice(),
+        [
+            Event::PointerMoved(moved),
+            Event::PointerButton { pos, button: PointerButton::Primary, pressed: true, .. },
+            Event::MouseWheel { unit: MouseWheelUnit::Point, delta: points, .. },
+            Event::MouseWheel { unit: MouseWheelUnit::Line, delta: lines, .. },
+        ] if *moved == pos2(50.0, 25.0)
+            && *pos == pos2(50.0, 25.0)
+            && *points == vec2(0.0, -20.0)
+            && *lines == vec2(0.0, -3.0)
+    ));
+
+    // The events were taken.
+    assert!(input.take_egui_input().events.is_empty());
+}
+
+#[test]
+fn button_without_pointer_is_ignored() {
+    let mut input = InputState::new([100, 100], 1.0);
+    input.on_pointer_button(PointerButton::Primary, true);
+    input.on_pointer_moved((10.0, 10.0));
+    input.on_pointer_left();
+    input.on_pointer_button(PointerButton::Primary, false);
+
+    let events = input.take_egui_input().events;
+    assert!(matches!(
+        events.as_slice(),
+        [Event::PointerMoved(_), Event::PointerGone]
+    ));
+}
+
+#[test]
+fn primary_touch_drives_the_pointer() {
+    let mut input = InputState::new([100, 100], 2.0);
+    input.on_touch(0, 1, TouchPhase::Start, (20.0, 40.0), None);
+    // A second finger doesn't move the pointer.
+    input.on_touch(0, 2, TouchPhase::Start, (60.0, 60.0), None);
+    input.on_touch(0, 1, TouchPhase::End, (20.0, 40.0), None);
+
+    let pointer_events: Vec<Event> = input
+        .take_egui_input()
+        .events
+        .into_iter()
+        .filter(|event| !matches!(event, Event::Touch { .. }))
+        .collect();
+    let pos = pos2(10.0, 20.0);
+    assert!(matches!(
+        pointer_events.as_slice(),
+        [
+            Event::PointerMoved(moved),
+            Event::PointerButton { pos: pressed_at, pressed: true, .. },
+            Event::PointerButton { pos: released_at, pressed: false, .. },
+            Event::PointerGone,
+        ] if *moved == pos && *pressed_at == pos && *released_at == pos
+    ));
+}
+
+#[test]
+fn keys_and_text() {
+    let mut input = InputState::new([100, 100], 1.0);
+    input.set_modifiers(Modifiers::COMMAND);
+    input.on_key(Key::C, Some(Key::C), true, false);
+    input.set_modifiers(Modifiers::NONE);
+    input.on_text("a\u{8}\r");
+
+    let raw_input = input.take_egui_input();
+    assert_eq!(raw_input.modifiers, Modifiers::NONE);
+    assert!(matches!(
+        raw_input.events.as_slice(),
+        [
+            Event::Copy,
+            Event::Key { key: Key::C, pressed: true, modifiers, .. },
+            Event::Text(text),
+        ] if modifiers.command && text == "a"
+    ));
+}
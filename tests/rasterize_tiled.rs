@@ -0,0 +1,68 @@
+//! Tiled rasterizing produces the same image as rasterizing in one piece.
+
+use egui::{Color32, Context, Stroke};
+use egui_skia::{rasterize, rasterize_tiled, read_pixels};
+use skia_safe::ColorType;
+
+fn ui(ctx: &Context) {
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.heading("Tiles");
+        ui.label("Text and shapes crossing the edges of the tiles.");
+        let painter = ui.painter();
+        let rect = ui.max_rect();
+        painter.circle(
+            rect.center(),
+            rect.width().min(rect.height()) / 3.0,
+            Color32::from_rgba_unmultiplied(200, 80, 40, 160),
+            Stroke::new(1.5, Color32::WHITE),
+        );
+        painter.line_segment(
+            [rect.left_top(), rect.right_bottom()],
+            (2.0, Color32::GREEN),
+        );
+    });
+}
+
+/// Rasterize with `tile_size` and return the rows together with the y of every band.
+fn rasterize_with_tiles(size: (i32, i32), tile_size: i32) -> (Vec<u8>, Vec<i32>) {
+    let mut pixels = Vec::new();
+    let mut tops = Vec::new();
+    rasterize_tiled(size, ui, None, tile_size, |top, rows| {
+        tops.push(top);
+        pixels.extend_from_slice(rows);
+    })
+    .unwrap();
+    (pixels, tops)
+}
+
+#[test]
+fn tiles_match_whole_image() {
+    let size = (123, 77);
+    let mut surface = rasterize(size, ui, None);
+    let expected = read_pixels(&mut surface, ColorType::N32).unwrap();
+
+    // A tile size that doesn't divide the image leaves partial tiles at the right and bottom.
+    let (pixels, tops) = rasterize_with_tiles(size, 32);
+    assert_eq!(tops, [0, 32, 64]);
+    assert_eq!(pixels.len(), expected.len());
+
+    let row_bytes = size.0 as usize * 4;
+    for (y, (row, expected_row)) in pixels
+        .chunks(row_bytes)
+        .zip(expected.chunks(row_bytes))
+        .enumerate()
+    {
+        assert!(row == expected_row, "row {y} differs from the whole image");
+    }
+}
+
+#[test]
+fn tile_larger_than_image() {
+    let size = (40, 30);
+    let mut surface = rasterize(size, ui, None);
+    let expected = read_pixels(&mut surface, ColorType::N32).unwrap();
+
+    let (pixels, tops) = rasterize_with_tiles(size, 4096);
+    assert_eq!(tops, [0]);
+    assert!(pixels == expected);
+}
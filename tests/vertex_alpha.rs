@@ -0,0 +1,103 @@
+//! The vertex alpha modes, compared with egui's premultiplied blending in gamma space.
+
+use egui::epaint::{ClippedPrimitive, Primitive};
+use egui::{
+    vec2, Color32, ColorImage, ImageDelta, Mesh, Pos2, Rect, Rgba, TextureId, TextureOptions,
+    TexturesDelta,
+};
+use egui_skia::{read_pixels, Painter, PainterOptions, VertexAlpha};
+use skia_safe::{surfaces, Color, ColorType};
+
+const BACKGROUND: [u8; 3] = [20, 40, 60];
+
+/// Paint a quad of `color` over [`BACKGROUND`] and return the pixel in its center.
+fn paint_quad(vertex_alpha: VertexAlpha, color: Color32) -> [u8; 4] {
+    let mut surface = surfaces::raster_n32_premul((8, 8)).unwrap();
+    let [r, g, b] = BACKGROUND;
+    surface.canvas().clear(Color::from_rgb(r, g, b));
+
+    let mut painter = Painter::new();
+    painter.set_options(PainterOptions {
+        vertex_alpha,
+        ..Default::default()
+    });
+
+    // egui samples the white texel of the font texture for untextured meshes.
+    let font_image = ColorImage::from_rgba_unmultiplied([1, 1], &[255; 4]);
+    let textures_delta = TexturesDelta {
+        set: vec![(
+            TextureId::default(),
+            ImageDelta::full(font_image, TextureOptions::LINEAR),
+        )],
+        free: Vec::new(),
+    };
+
+    let mut mesh = Mesh::default();
+    mesh.add_colored_rect(Rect::from_min_size(Pos2::ZERO, vec2(8.0, 8.0)), color);
+    let primitives = vec![ClippedPrimitive {
+        clip_rect: Rect::EVERYTHING,
+        primitive: Primitive::Mesh(mesh),
+    }];
+    painter.paint_and_update_textures(surface.canvas(), 1.0, primitives, textures_delta);
+
+    let pixels = read_pixels(&mut surface, ColorType::RGBA8888).unwrap();
+    let offset = (4 * 8 + 4) * 4;
+    pixels[offset..offset + 4].try_into().unwrap()
+}
+
+/// egui's blending of the premultiplied `src` over an opaque `dst`.
+fn egui_blend(src: Color32, dst: [u8; 3]) -> [u8; 4] {
+    let [r, g, b, a] = src.to_array();
+    let blend =
+        |src: u8, dst: u8| (src as f32 + dst as f32 * (255 - a) as f32 / 255.0).round() as u8;
+    [blend(r, dst[0]), blend(g, dst[1]), blend(b, dst[2]), 255]
+}
+
+fn assert_close(actual: [u8; 4], expected: [u8; 4], tolerance: u8) {
+    let close = actual
+        .iter()
+        .zip(&expected)
+        .all(|(actual, expected)| actual.abs_diff(*expected) <= tolerance);
+    assert!(close, "painted {actual:?}, expected {expected:?}");
+}
+
+#[test]
+fn premultiplied() {
+    for color in [
+        Color32::from_rgba_unmultiplied(200, 100, 50, 128),
+        Color32::from_rgba_unmultiplied(255, 255, 255, 30),
+        Color32::from_rgba_unmultiplied(10, 220, 90, 240),
+        Color32::from_rgb(200, 100, 50),
+    ] {
+        let actual = paint_quad(VertexAlpha::Premultiplied, color);
+        assert_close(actual, egui_blend(color, BACKGROUND), 2);
+    }
+}
+
+#[test]
+fn premultiplied_transparent() {
+    let actual = paint_quad(VertexAlpha::Premultiplied, Color32::TRANSPARENT);
+    assert_close(actual, egui_blend(Color32::TRANSPARENT, BACKGROUND), 0);
+}
+
+#[test]
+fn premultiplied_linear() {
+    for [r, g, b, a] in [[200, 100, 50, 128], [255, 255, 255, 30], [10, 220, 90, 240]] {
+        let color = Color32::from(Rgba::from_srgba_unmultiplied(r, g, b, a));
+        let actual = paint_quad(VertexAlpha::PremultipliedLinear, color);
+        // Premultiplying in linear light loses some precision of dark channels.
+        let expected = Color32::from_rgba_unmultiplied(r, g, b, a);
+        assert_close(actual, egui_blend(expected, BACKGROUND), 3);
+    }
+}
+
+#[test]
+fn straight() {
+    for [r, g, b, a] in [[200, 100, 50, 128], [255, 255, 255, 30], [10, 220, 90, 240]] {
+        // Stores the channels unchanged.
+        let color = Color32::from_rgba_premultiplied(r, g, b, a);
+        let actual = paint_quad(VertexAlpha::Straight, color);
+        let expected = Color32::from_rgba_unmultiplied(r, g, b, a);
+        assert_close(actual, egui_blend(expected, BACKGROUND), 2);
+    }
+}